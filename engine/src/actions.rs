@@ -0,0 +1,304 @@
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::input::{GamepadAxis, GamepadButton, Input, Key, MouseKey};
+
+// Gamepad bindings always use the first pad
+const PAD: usize = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Button {
+    Key(Key),
+    Mouse(MouseKey),
+    Gamepad(GamepadButton),
+}
+
+impl Button {
+    fn held(self, input: &dyn Input) -> bool {
+        match self {
+            Button::Key(key) => input.is_key_held(key),
+            Button::Mouse(button) => input.is_mouse_held(button),
+            Button::Gamepad(button) => input.is_gamepad_held(PAD, button),
+        }
+    }
+
+    fn pressed(self, input: &dyn Input) -> bool {
+        match self {
+            Button::Key(key) => input.is_key_down_this_frame(key),
+            Button::Mouse(button) => input.is_mouse_down_this_frame(button),
+            Button::Gamepad(button) => input.is_gamepad_down_this_frame(PAD, button),
+        }
+    }
+
+    fn released(self, input: &dyn Input) -> bool {
+        match self {
+            Button::Key(key) => input.is_key_released(key),
+            Button::Mouse(button) => input.is_mouse_released(button),
+            Button::Gamepad(button) => input.is_gamepad_released(PAD, button),
+        }
+    }
+}
+
+/// A button, optionally combined with modifier keys that have to be held with it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub button: Button,
+    #[serde(default)]
+    pub modifiers: Vec<Key>,
+}
+
+impl Binding {
+    pub fn new(button: Button) -> Self {
+        Binding {
+            button,
+            modifiers: vec![],
+        }
+    }
+
+    pub fn with_modifier(mut self, key: Key) -> Self {
+        self.modifiers.push(key);
+        self
+    }
+
+    fn modifiers_held(&self, input: &dyn Input) -> bool {
+        self.modifiers.iter().all(|&key| input.is_key_held(key))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AxisSource {
+    Buttons { negative: Button, positive: Button }, // -1, 0 or 1
+    MouseX,                                         // Mouse movement this frame, in pixels
+    MouseY,
+    MouseWheel,           // Vertical scroll this frame
+    Gamepad(GamepadAxis), // Triggers from 0 to 1, with the default deadzones
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AxisBinding {
+    pub source: AxisSource,
+    #[serde(default = "unit_scale")]
+    pub scale: f32,
+}
+
+fn unit_scale() -> f32 {
+    1.0
+}
+
+impl AxisBinding {
+    pub fn new(source: AxisSource) -> Self {
+        AxisBinding { source, scale: 1.0 }
+    }
+
+    fn value(&self, input: &dyn Input) -> f32 {
+        let raw = match self.source {
+            AxisSource::Buttons { negative, positive } => {
+                positive.held(input) as i32 as f32 - negative.held(input) as i32 as f32
+            }
+            AxisSource::MouseX => input.get_mouse_delta().x,
+            AxisSource::MouseY => input.get_mouse_delta().y,
+            AxisSource::MouseWheel => input.get_mouse_wheel().y,
+            AxisSource::Gamepad(axis) => input.gamepad_axis(PAD, axis),
+        };
+        raw * self.scale
+    }
+}
+
+/// Named actions and axes, each with any number of bindings. Unknown names are never
+/// active, so a missing binding disables a feature instead of failing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActionMap {
+    #[serde(default)]
+    pub actions: BTreeMap<String, Vec<Binding>>,
+    #[serde(default)]
+    pub axes: BTreeMap<String, Vec<AxisBinding>>,
+}
+
+impl ActionMap {
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(ron::from_str(text)?)
+    }
+
+    pub fn to_ron(&self) -> Result<String, Box<dyn Error>> {
+        Ok(ron::ser::to_string_pretty(self, PrettyConfig::default())?)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+
+    pub fn bind_action(&mut self, action: &str, binding: Binding) -> &mut Self {
+        self.actions
+            .entry(action.to_string())
+            .or_default()
+            .push(binding);
+        self
+    }
+
+    pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) -> &mut Self {
+        self.axes.entry(axis.to_string()).or_default().push(binding);
+        self
+    }
+
+    /// Removes all bindings of an action, e.g. before rebinding it
+    pub fn unbind_action(&mut self, action: &str) {
+        self.actions.remove(action);
+    }
+
+    pub fn unbind_axis(&mut self, axis: &str) {
+        self.axes.remove(axis);
+    }
+
+    fn bindings(&self, action: &str) -> impl Iterator<Item = &Binding> {
+        self.actions.get(action).into_iter().flatten()
+    }
+
+    /// True while any binding of the action is held with its modifiers
+    pub fn action_held(&self, input: &dyn Input, action: &str) -> bool {
+        self.bindings(action)
+            .any(|b| b.button.held(input) && b.modifiers_held(input))
+    }
+
+    /// True in the frame the button of any binding went down, with its modifiers held
+    pub fn action_pressed(&self, input: &dyn Input, action: &str) -> bool {
+        self.bindings(action)
+            .any(|b| b.button.pressed(input) && b.modifiers_held(input))
+    }
+
+    /// True in the frame the button of any binding was let go. Modifiers are not checked,
+    /// since they are often released first.
+    pub fn action_released(&self, input: &dyn Input, action: &str) -> bool {
+        self.bindings(action).any(|b| b.button.released(input))
+    }
+
+    /// Sum of all bindings of the axis
+    pub fn axis_value(&self, input: &dyn Input, axis: &str) -> f32 {
+        self.axes
+            .get(axis)
+            .into_iter()
+            .flatten()
+            .map(|b| b.value(input))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{InputFrame, ScriptedInput};
+    use crate::primitives::Float2;
+
+    fn input(frame: InputFrame) -> ScriptedInput {
+        let mut input = ScriptedInput::new([frame]);
+        input.next_frame();
+        input
+    }
+
+    fn test_map() -> ActionMap {
+        let mut map = ActionMap::default();
+        map.bind_action("jump", Binding::new(Button::Key(Key::Space)))
+            .bind_action("jump", Binding::new(Button::Mouse(MouseKey::Right)))
+            .bind_action(
+                "save",
+                Binding::new(Button::Key(Key::S)).with_modifier(Key::LeftControl),
+            )
+            .bind_axis(
+                "move",
+                AxisBinding::new(AxisSource::Buttons {
+                    negative: Button::Key(Key::A),
+                    positive: Button::Key(Key::D),
+                }),
+            )
+            .bind_axis(
+                "move",
+                AxisBinding {
+                    source: AxisSource::MouseX,
+                    scale: 0.1,
+                },
+            )
+            .bind_axis(
+                "move",
+                AxisBinding::new(AxisSource::Gamepad(GamepadAxis::LeftX)),
+            )
+            .bind_axis("zoom", AxisBinding::new(AxisSource::MouseWheel));
+        map
+    }
+
+    #[test]
+    fn test_actions() {
+        let map = test_map();
+
+        // Either binding triggers the action
+        let frame = InputFrame::default().with_mouse_pressed(MouseKey::Right);
+        assert!(map.action_pressed(&input(frame.clone()), "jump"));
+        assert!(map.action_held(&input(frame), "jump"));
+        let frame = InputFrame::default().with_key_held(Key::Space);
+        assert!(!map.action_pressed(&input(frame.clone()), "jump"));
+        assert!(map.action_held(&input(frame), "jump"));
+
+        // Modifiers have to be held
+        let frame = InputFrame::default().with_key_pressed(Key::S);
+        assert!(!map.action_pressed(&input(frame.clone()), "save"));
+        let frame = frame.with_key_held(Key::LeftControl);
+        assert!(map.action_pressed(&input(frame.clone()), "save"));
+
+        // Unknown actions are never active
+        assert!(!map.action_held(&input(frame), "fly"));
+
+        let frame = InputFrame::default().with_mouse_released(MouseKey::Right);
+        assert!(map.action_released(&input(frame), "jump"));
+    }
+
+    #[test]
+    fn test_axes() {
+        let map = test_map();
+        let frame = InputFrame::default().with_key_held(Key::D);
+        assert_eq!(map.axis_value(&input(frame), "move"), 1.0);
+
+        // Opposite keys cancel out, bindings add up
+        let frame = InputFrame::default()
+            .with_key_held(Key::A)
+            .with_key_held(Key::D)
+            .with_mouse(Float2::ZERO, Float2::new(-5.0, 0.0));
+        assert_eq!(map.axis_value(&input(frame), "move"), -0.5);
+        assert_eq!(map.axis_value(&input(InputFrame::default()), "turn"), 0.0);
+
+        let frame = InputFrame::default()
+            .with_gamepad_axis(0, GamepadAxis::LeftX, 0.1) // Inside the deadzone
+            .with_mouse_wheel(Float2::new(0.0, 3.0));
+        assert_eq!(map.axis_value(&input(frame.clone()), "move"), 0.0);
+        assert_eq!(map.axis_value(&input(frame), "zoom"), 3.0);
+        let frame = InputFrame::default().with_gamepad_axis(0, GamepadAxis::LeftX, -1.0);
+        assert_eq!(map.axis_value(&input(frame), "move"), -1.0);
+    }
+
+    #[test]
+    fn test_rebind_and_round_trip() {
+        let mut map = test_map();
+        map.unbind_action("jump");
+        map.bind_action("jump", Binding::new(Button::Key(Key::J)));
+
+        let frame = InputFrame::default().with_key_pressed(Key::Space);
+        assert!(!map.action_pressed(&input(frame), "jump"));
+
+        let text = map.to_ron().unwrap();
+        assert_eq!(ActionMap::parse(&text).unwrap(), map);
+
+        // Modifiers and scales are optional in files
+        let map = ActionMap::parse(
+            "(actions: {\"jump\": [(button: Key(Space))]}, axes: {\"look\": [(source: MouseX)]})",
+        )
+        .unwrap();
+        assert_eq!(map.axes["look"][0].scale, 1.0);
+    }
+}
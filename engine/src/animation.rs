@@ -0,0 +1,480 @@
+use serde::{Deserialize, Serialize};
+
+use std::ops::{Add, Mul, Sub};
+use std::sync::Arc;
+
+use crate::camera_path::CameraPath;
+use crate::morph::SharedMorphWeights;
+use crate::pose_graph::SharedPGNode;
+use crate::primitives::{Float3, Quaternion, Transform, VectorOps};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpolation {
+    Step, // Hold each key until the next one
+    #[default]
+    Linear,
+    Cubic, // Catmull-Rom, or squad for rotations
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoopMode {
+    #[default]
+    Once, // Hold the last key
+    Loop,
+    PingPong,
+}
+
+// Values that can be keyframed
+pub trait Animatable: Copy {
+    fn interpolate(a: Self, b: Self, t: f32) -> Self;
+
+    // Cubic interpolation between `p1` and `p2`, with `p0` and `p3` as neighbours.
+    // The tangents at `p1` and `p2` are the differences between their neighbours, times
+    // `scale`: the segment's length over the span between those neighbours.
+    fn cubic_scaled(p0: Self, p1: Self, p2: Self, p3: Self, scale: (f32, f32), t: f32) -> Self;
+
+    // Same, for evenly spaced points
+    fn cubic(p0: Self, p1: Self, p2: Self, p3: Self, t: f32) -> Self {
+        Self::cubic_scaled(p0, p1, p2, p3, (0.5, 0.5), t)
+    }
+}
+
+// Catmull-Rom spline through p1 and p2, as a Hermite curve
+fn catmull_rom<T>(p0: T, p1: T, p2: T, p3: T, scale: (f32, f32), t: f32) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    let (t2, t3) = (t * t, t * t * t);
+    let m1 = (p2 - p0) * scale.0;
+    let m2 = (p3 - p1) * scale.1;
+    p1 * (2.0 * t3 - 3.0 * t2 + 1.0)
+        + m1 * (t3 - 2.0 * t2 + t)
+        + p2 * (3.0 * t2 - 2.0 * t3)
+        + m2 * (t3 - t2)
+}
+
+impl Animatable for f32 {
+    fn interpolate(a: Self, b: Self, t: f32) -> Self {
+        a + (b - a) * t
+    }
+
+    fn cubic_scaled(p0: Self, p1: Self, p2: Self, p3: Self, scale: (f32, f32), t: f32) -> Self {
+        catmull_rom(p0, p1, p2, p3, scale, t)
+    }
+}
+
+impl Animatable for Float3 {
+    fn interpolate(a: Self, b: Self, t: f32) -> Self {
+        a.lerp(b, t)
+    }
+
+    fn cubic_scaled(p0: Self, p1: Self, p2: Self, p3: Self, scale: (f32, f32), t: f32) -> Self {
+        catmull_rom(p0, p1, p2, p3, scale, t)
+    }
+}
+
+impl Animatable for Quaternion {
+    fn interpolate(a: Self, b: Self, t: f32) -> Self {
+        a.slerp(b, t)
+    }
+
+    fn cubic_scaled(p0: Self, p1: Self, p2: Self, p3: Self, scale: (f32, f32), t: f32) -> Self {
+        Quaternion::squad_scaled(p0, p1, p2, p3, scale, t)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe<T> {
+    pub time: f32,
+    pub value: T,
+}
+
+/// Keyframes of a single value, kept sorted by time
+#[derive(Debug, Clone, Default)]
+pub struct Track<T> {
+    keys: Vec<Keyframe<T>>,
+    pub interpolation: Interpolation,
+}
+
+impl<T: Animatable> Track<T> {
+    pub fn new(interpolation: Interpolation) -> Self {
+        Track {
+            keys: vec![],
+            interpolation,
+        }
+    }
+
+    pub fn with_key(mut self, time: f32, value: T) -> Self {
+        self.insert(time, value);
+        self
+    }
+
+    /// Adds a key, replacing any existing key at the same time
+    pub fn insert(&mut self, time: f32, value: T) {
+        let idx = self.keys.partition_point(|k| k.time < time);
+        match self.keys.get_mut(idx) {
+            Some(key) if key.time == time => key.value = value,
+            _ => self.keys.insert(idx, Keyframe { time, value }),
+        }
+    }
+
+    pub fn keys(&self) -> &[Keyframe<T>] {
+        &self.keys
+    }
+
+    /// Time of the last key
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |k| k.time)
+    }
+
+    /// Value at `time`, clamped to the first and last keys. None for an empty track.
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let (first, last) = (self.keys.first()?, self.keys.last()?);
+        if time <= first.time {
+            return Some(first.value);
+        }
+        if time >= last.time {
+            return Some(last.value);
+        }
+
+        // keys[i] is the last key at or before `time`
+        let i = self.keys.partition_point(|k| k.time <= time) - 1;
+        let (a, b) = (&self.keys[i], &self.keys[i + 1]);
+        let t = (time - a.time) / (b.time - a.time);
+
+        Some(match self.interpolation {
+            Interpolation::Step => a.value,
+            Interpolation::Linear => T::interpolate(a.value, b.value, t),
+            Interpolation::Cubic => {
+                // Missing neighbours repeat the end key, one segment further out
+                let span = b.time - a.time;
+                let (p0, t0) = match i.checked_sub(1) {
+                    Some(j) => (self.keys[j].value, self.keys[j].time),
+                    None => (a.value, a.time - span),
+                };
+                let (p3, t3) = self
+                    .keys
+                    .get(i + 2)
+                    .map_or((b.value, b.time + span), |k| (k.value, k.time));
+                let scale = (span / (b.time - t0), span / (t3 - a.time));
+                T::cubic_scaled(p0, a.value, b.value, p3, scale, t)
+            }
+        })
+    }
+}
+
+/// Tracks for the channels of a transform. Channels without a track keep the value
+/// they had when the clip was bound.
+#[derive(Debug, Clone, Default)]
+pub struct AnimationClip {
+    pub position: Option<Track<Float3>>,
+    pub rotation: Option<Track<Quaternion>>,
+    pub scale: Option<Track<Float3>>,
+    pub loop_mode: LoopMode,
+}
+
+impl AnimationClip {
+    pub fn duration(&self) -> f32 {
+        let position = self.position.as_ref().map_or(0.0, Track::duration);
+        let rotation = self.rotation.as_ref().map_or(0.0, Track::duration);
+        let scale = self.scale.as_ref().map_or(0.0, Track::duration);
+        position.max(rotation).max(scale)
+    }
+
+    /// Maps the playback time to the clip's own timeline, according to the loop mode
+    pub fn local_time(&self, time: f32) -> f32 {
+        loop_time(time, self.duration(), self.loop_mode)
+    }
+
+    /// The transform at `time`, with unanimated channels taken from `base`
+    pub fn sample(&self, time: f32, base: Transform) -> Transform {
+        let t = self.local_time(time);
+        Transform {
+            position: sample_or(&self.position, t, base.position),
+            rotation: sample_or(&self.rotation, t, base.rotation),
+            scale: sample_or(&self.scale, t, base.scale),
+        }
+    }
+}
+
+/// Tracks for the morph target weights of an entity, indexed like the mesh's targets.
+/// Weights without a track are left as they are.
+#[derive(Debug, Clone, Default)]
+pub struct MorphClip {
+    pub weights: Vec<Option<Track<f32>>>,
+    pub loop_mode: LoopMode,
+}
+
+impl MorphClip {
+    pub fn duration(&self) -> f32 {
+        self.weights
+            .iter()
+            .flatten()
+            .map(Track::duration)
+            .fold(0.0, f32::max)
+    }
+
+    pub fn local_time(&self, time: f32) -> f32 {
+        loop_time(time, self.duration(), self.loop_mode)
+    }
+
+    /// Writes the weights at `time` into `weights`, growing it if needed
+    pub fn sample(&self, time: f32, weights: &mut Vec<f32>) {
+        let t = self.local_time(time);
+        if weights.len() < self.weights.len() {
+            weights.resize(self.weights.len(), 0.0);
+        }
+        for (weight, track) in weights.iter_mut().zip(&self.weights) {
+            *weight = sample_or(track, t, *weight);
+        }
+    }
+}
+
+pub(crate) fn loop_time(time: f32, duration: f32, loop_mode: LoopMode) -> f32 {
+    if duration <= 0.0 {
+        return 0.0;
+    }
+
+    match loop_mode {
+        LoopMode::Once => time.clamp(0.0, duration),
+        LoopMode::Loop => time.rem_euclid(duration),
+        LoopMode::PingPong => {
+            let t = time.rem_euclid(2.0 * duration);
+            if t > duration { 2.0 * duration - t } else { t }
+        }
+    }
+}
+
+fn sample_or<T: Animatable>(track: &Option<Track<T>>, time: f32, fallback: T) -> T {
+    track
+        .as_ref()
+        .and_then(|track| track.sample(time))
+        .unwrap_or(fallback)
+}
+
+#[derive(Debug)]
+enum Binding {
+    Pose {
+        node: SharedPGNode,
+        clip: Arc<AnimationClip>,
+        base: Transform,
+    },
+    Morph {
+        weights: SharedMorphWeights,
+        clip: Arc<MorphClip>,
+    },
+    Path {
+        node: SharedPGNode,
+        path: Arc<CameraPath>,
+        base: Transform,
+    },
+}
+
+impl Binding {
+    fn is_finished(&self, time: f32) -> bool {
+        let (loop_mode, duration) = match self {
+            Binding::Pose { clip, .. } => (clip.loop_mode, clip.duration()),
+            Binding::Morph { clip, .. } => (clip.loop_mode, clip.duration()),
+            Binding::Path { path, .. } => (path.loop_mode, path.duration()),
+        };
+        loop_mode == LoopMode::Once && time >= duration
+    }
+
+    fn apply(&self, time: f32) {
+        match self {
+            Binding::Pose { node, clip, base } => {
+                node.write().set_transform(clip.sample(time, *base));
+            }
+            Binding::Morph { weights, clip } => clip.sample(time, &mut weights.write()),
+            Binding::Path { node, path, base } => {
+                node.write().set_transform(path.sample(time, *base));
+            }
+        }
+    }
+}
+
+/// Plays clips and camera paths on pose graph nodes and morph weights, all sharing
+/// one clock.
+#[derive(Debug)]
+pub struct AnimationPlayer {
+    bindings: Vec<Binding>,
+    time: f32,
+    pub speed: f32,
+    pub paused: bool,
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        AnimationPlayer {
+            bindings: vec![],
+            time: 0.0,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+}
+
+impl AnimationPlayer {
+    /// Animates `node` with `clip`. Its current local transform is kept for the channels
+    /// the clip doesn't animate.
+    pub fn bind(&mut self, node: SharedPGNode, clip: Arc<AnimationClip>) {
+        let base = node.read().transform();
+        self.bindings.push(Binding::Pose { node, clip, base });
+    }
+
+    pub fn bind_morph(&mut self, weights: SharedMorphWeights, clip: Arc<MorphClip>) {
+        self.bindings.push(Binding::Morph { weights, clip });
+    }
+
+    /// Moves `node` along `path`, keeping its current scale
+    pub fn bind_path(&mut self, node: SharedPGNode, path: Arc<CameraPath>) {
+        let base = node.read().transform();
+        self.bindings.push(Binding::Path { node, path, base });
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// True once every clip has played to its end. Looping clips never finish.
+    pub fn is_finished(&self) -> bool {
+        self.bindings.iter().all(|b| b.is_finished(self.time))
+    }
+
+    /// Jumps to `time` and updates every bound node and weight set
+    pub fn seek(&mut self, time: f32) {
+        self.time = time;
+        for binding in &self.bindings {
+            binding.apply(self.time);
+        }
+    }
+
+    pub fn update(&mut self, time_delta: f32) {
+        if !self.paused {
+            self.seek(self.time + time_delta * self.speed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pose_graph::PoseGraph;
+
+    fn ramp(interpolation: Interpolation) -> Track<f32> {
+        Track::new(interpolation)
+            .with_key(2.0, 4.0)
+            .with_key(0.0, 0.0)
+            .with_key(1.0, 1.0)
+    }
+
+    #[test]
+    fn test_track_interpolation() {
+        let step = ramp(Interpolation::Step);
+        assert_eq!(step.sample(0.99), Some(0.0));
+        assert_eq!(step.sample(1.5), Some(1.0));
+
+        let linear = ramp(Interpolation::Linear);
+        assert_eq!(linear.duration(), 2.0);
+        assert_eq!(linear.sample(-1.0), Some(0.0));
+        assert_eq!(linear.sample(1.5), Some(2.5));
+        assert_eq!(linear.sample(3.0), Some(4.0));
+
+        // Cubic passes through the keys, but bends between them
+        let cubic = ramp(Interpolation::Cubic);
+        assert_eq!(cubic.sample(1.0), Some(1.0));
+        assert!((cubic.sample(1.5).unwrap() - 2.5625).abs() < 1e-6);
+
+        // Tangents follow the key spacing, so between inner keys on a line it stays straight
+        let uneven = Track::new(Interpolation::Cubic)
+            .with_key(0.0, 0.0)
+            .with_key(1.0, 1.0)
+            .with_key(4.0, 4.0)
+            .with_key(5.0, 5.0);
+        for time in [1.0, 1.5, 2.5, 3.9] {
+            assert!((uneven.sample(time).unwrap() - time).abs() < 1e-5);
+        }
+
+        assert_eq!(Track::<f32>::default().sample(0.0), None);
+    }
+
+    #[test]
+    fn test_loop_modes() {
+        let track = Track::new(Interpolation::Linear)
+            .with_key(0.0, Float3::ZERO)
+            .with_key(2.0, Float3::X * 2.0);
+        let mut clip = AnimationClip {
+            position: Some(track),
+            ..Default::default()
+        };
+
+        let x_at = |clip: &AnimationClip, t| clip.sample(t, Transform::default()).position.x;
+        assert_eq!(x_at(&clip, 3.0), 2.0);
+
+        clip.loop_mode = LoopMode::Loop;
+        assert!((x_at(&clip, 3.0) - 1.0).abs() < 1e-6);
+
+        clip.loop_mode = LoopMode::PingPong;
+        assert!((x_at(&clip, 2.5) - 1.5).abs() < 1e-6);
+        assert!((x_at(&clip, 4.5) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_player_drives_node() {
+        let root = PoseGraph::root();
+        let node = PoseGraph::new("node", root.clone()).unwrap();
+        node.write().apply_translation(Float3::Y);
+
+        let quarter = Quaternion::from_y_angle(std::f32::consts::FRAC_PI_2);
+        let clip = AnimationClip {
+            rotation: Some(
+                Track::new(Interpolation::Linear)
+                    .with_key(0.0, Quaternion::IDENTITY)
+                    .with_key(1.0, quarter),
+            ),
+            ..Default::default()
+        };
+
+        let mut player = AnimationPlayer::default();
+        player.bind(node.clone(), Arc::new(clip));
+        player.update(0.5);
+        assert!(!player.is_finished());
+        player.update(0.75);
+        assert!(player.is_finished());
+
+        // The unanimated position is kept, the rotation comes from the clip
+        let world = PoseGraph::world_transform(&node);
+        assert!(VectorOps::approx_eq(world.position, Float3::Y, 1e-6));
+        let forward = world.forward_vec();
+        assert!(VectorOps::approx_eq(
+            forward,
+            quarter * Float3::FORWARD,
+            1e-5
+        ));
+    }
+
+    #[test]
+    fn test_player_drives_morph_weights() {
+        let weights: SharedMorphWeights = Default::default();
+        let clip = MorphClip {
+            weights: vec![
+                None,
+                Some(
+                    Track::new(Interpolation::Linear)
+                        .with_key(0.0, 0.0)
+                        .with_key(2.0, 1.0),
+                ),
+            ],
+            loop_mode: LoopMode::Loop,
+        };
+
+        let mut player = AnimationPlayer::default();
+        player.bind_morph(weights.clone(), Arc::new(clip));
+        player.update(0.5);
+        assert_eq!(*weights.read(), vec![0.0, 0.25]);
+
+        player.update(2.0);
+        assert_eq!(*weights.read(), vec![0.0, 0.25]);
+        assert!(!player.is_finished());
+    }
+}
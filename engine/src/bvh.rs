@@ -0,0 +1,463 @@
+use crate::primitives::{Aabb, FaceData3D, Float3, Ray, RayHit, Tri, VectorOps};
+
+// Nodes with this many triangles or fewer are not split
+const MAX_LEAF_SIZE: usize = 4;
+// Candidate split planes per axis are placed between this many bins
+const SAH_BINS: usize = 12;
+// Cost of visiting a node, relative to intersecting a triangle
+const TRAVERSAL_COST: f32 = 1.0;
+
+#[derive(Debug, Clone)]
+struct BvhNode {
+    bounds: Aabb,
+    start: usize, // First triangle of a leaf, or the left child (the right one follows it)
+    count: usize, // Triangles of a leaf, zero for inner nodes
+}
+
+impl BvhNode {
+    fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+/// Bounding volume hierarchy over the triangles of a mesh, split by the surface area
+/// heuristic. Queries report indices into the faces it was built from.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    tris: Vec<Tri<Float3>>, // In leaf order
+    faces: Vec<usize>,      // Face index of each triangle
+}
+
+impl Bvh {
+    pub fn new(faces: &[FaceData3D]) -> Self {
+        let bounds: Vec<_> = faces.iter().map(|f| f.vertices.bounds()).collect();
+        let centroids: Vec<_> = bounds.iter().map(Aabb::center).collect();
+        let mut bvh = Bvh {
+            nodes: vec![],
+            tris: vec![],
+            faces: (0..faces.len()).collect(),
+        };
+
+        if !faces.is_empty() {
+            bvh.nodes.push(BvhNode {
+                bounds: Aabb::EMPTY,
+                start: 0,
+                count: faces.len(),
+            });
+            bvh.split(0, &bounds, &centroids);
+        }
+        bvh.tris = bvh
+            .faces
+            .iter()
+            .map(|&i| faces[i].vertices.clone())
+            .collect();
+        bvh
+    }
+
+    // Traversal stack holding the root, empty if there are no triangles
+    fn root(&self) -> Vec<usize> {
+        if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::EMPTY, |n| n.bounds)
+    }
+
+    /// Closest hit before `max_t`
+    pub fn intersect_ray(&self, ray: &Ray, max_t: f32) -> Option<RayHit> {
+        self.closest_hit(ray, max_t, false)
+    }
+
+    /// Closest hit before `max_t` on a front face, where the vertices wind counter-clockwise
+    /// as seen from the ray origin. Matches what the rasterizer keeps after culling.
+    pub fn intersect_ray_front(&self, ray: &Ray, max_t: f32) -> Option<RayHit> {
+        self.closest_hit(ray, max_t, true)
+    }
+
+    fn closest_hit(&self, ray: &Ray, mut max_t: f32, front_only: bool) -> Option<RayHit> {
+        let mut closest: Option<RayHit> = None;
+        self.traverse_ray(ray, &mut max_t, front_only, |i, t, barycentric| {
+            closest = Some(RayHit {
+                face: i,
+                t,
+                barycentric,
+            });
+            false
+        });
+        closest
+    }
+
+    /// True if anything is hit before `max_t`, e.g. for shadow rays. Stops at the first
+    /// hit instead of looking for the closest one.
+    pub fn occluded(&self, ray: &Ray, mut max_t: f32) -> bool {
+        let mut hit = false;
+        self.traverse_ray(ray, &mut max_t, false, |_, _, _| {
+            hit = true;
+            true
+        });
+        hit
+    }
+
+    // Calls `on_hit` with the face, distance and weights of every hit closer than
+    // `max_t`, which shrinks to each hit. Nearer children are visited first. Stops
+    // when `on_hit` returns true. With `front_only`, back faces are skipped.
+    fn traverse_ray(
+        &self,
+        ray: &Ray,
+        max_t: &mut f32,
+        front_only: bool,
+        mut on_hit: impl FnMut(usize, f32, Tri<f32>) -> bool,
+    ) {
+        if self.nodes.is_empty() || self.nodes[0].bounds.intersect_ray(ray, *max_t).is_none() {
+            return;
+        }
+
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if node.is_leaf() {
+                for tri in node.start..node.start + node.count {
+                    if front_only && self.tris[tri].normal().dot(ray.direction) >= 0.0 {
+                        continue;
+                    }
+                    match ray.intersect_tri(&self.tris[tri]) {
+                        Some((t, weights)) if t < *max_t => {
+                            *max_t = t;
+                            if on_hit(self.faces[tri], t, weights) {
+                                return;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+
+            let (left, right) = (node.start, node.start + 1);
+            let t_left = self.nodes[left].bounds.intersect_ray(ray, *max_t);
+            let t_right = self.nodes[right].bounds.intersect_ray(ray, *max_t);
+            match (t_left, t_right) {
+                (Some(a), Some(b)) if a <= b => stack.extend([right, left]),
+                (Some(_), Some(_)) => stack.extend([left, right]),
+                (Some(_), None) => stack.push(left),
+                (None, Some(_)) => stack.push(right),
+                (None, None) => {}
+            }
+        }
+    }
+
+    /// Faces whose triangles overlap `bounds`, in no particular order
+    pub fn overlapping(&self, bounds: &Aabb) -> Vec<usize> {
+        let mut out = vec![];
+        let mut stack = self.root();
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if !node.bounds.overlaps(bounds) {
+                continue;
+            }
+
+            if node.is_leaf() {
+                let tris = node.start..node.start + node.count;
+                out.extend(
+                    tris.filter(|&t| self.tris[t].overlaps_aabb(bounds))
+                        .map(|t| self.faces[t]),
+                );
+            } else {
+                stack.extend([node.start, node.start + 1]);
+            }
+        }
+        out
+    }
+
+    /// The face closest to `p`, and the closest point on it. None for an empty BVH.
+    pub fn closest_point(&self, p: Float3) -> Option<(usize, Float3)> {
+        let mut best: Option<(usize, Float3)> = None;
+        let mut best_dist_sq = f32::INFINITY;
+        let mut stack = self.root();
+
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if node.bounds.distance_sq(p) >= best_dist_sq {
+                continue;
+            }
+
+            if node.is_leaf() {
+                for tri in node.start..node.start + node.count {
+                    let q = self.tris[tri].closest_point(p);
+                    let dist_sq = (q - p).sqr_magnitude();
+                    if dist_sq < best_dist_sq {
+                        best_dist_sq = dist_sq;
+                        best = Some((self.faces[tri], q));
+                    }
+                }
+                continue;
+            }
+
+            // Visit the nearer child first, so the farther one is more likely pruned
+            let (left, right) = (node.start, node.start + 1);
+            let d_left = self.nodes[left].bounds.distance_sq(p);
+            let d_right = self.nodes[right].bounds.distance_sq(p);
+            match d_left <= d_right {
+                true => stack.extend([right, left]),
+                false => stack.extend([left, right]),
+            }
+        }
+        best
+    }
+
+    // Fits the node to its triangles, then splits it where the SAH is lowest, unless
+    // keeping it as a leaf is cheaper
+    fn split(&mut self, node: usize, bounds: &[Aabb], centroids: &[Float3]) {
+        let BvhNode { start, count, .. } = self.nodes[node];
+        let range = start..start + count;
+        let node_bounds = self.faces[range.clone()]
+            .iter()
+            .fold(Aabb::EMPTY, |b, &f| b.union(bounds[f]));
+        self.nodes[node].bounds = node_bounds;
+        if count <= MAX_LEAF_SIZE {
+            return;
+        }
+
+        let Some((axis, plane)) = self.best_split(range.clone(), bounds, centroids, &node_bounds)
+        else {
+            return;
+        };
+
+        // Partition in place, then hand both halves to new children
+        let faces = &mut self.faces[range];
+        let mut mid = 0;
+        for i in 0..faces.len() {
+            if centroids[faces[i]][axis] < plane {
+                faces.swap(i, mid);
+                mid += 1;
+            }
+        }
+        if mid == 0 || mid == count {
+            return;
+        }
+
+        let left = self.nodes.len();
+        for (start, count) in [(start, mid), (start + mid, count - mid)] {
+            self.nodes.push(BvhNode {
+                bounds: Aabb::EMPTY,
+                start,
+                count,
+            });
+        }
+        self.nodes[node].start = left;
+        self.nodes[node].count = 0;
+        self.split(left, bounds, centroids);
+        self.split(left + 1, bounds, centroids);
+    }
+
+    // Axis and position of the cheapest split plane, binning triangles by centroid.
+    // None if no split beats a leaf.
+    fn best_split(
+        &self,
+        range: std::ops::Range<usize>,
+        bounds: &[Aabb],
+        centroids: &[Float3],
+        node_bounds: &Aabb,
+    ) -> Option<(usize, f32)> {
+        let faces = &self.faces[range];
+        let centroid_bounds = Aabb::from_points(faces.iter().map(|&f| centroids[f]));
+        let leaf_cost = faces.len() as f32;
+        let mut best: Option<(usize, f32)> = None;
+        let mut best_cost = leaf_cost;
+
+        for axis in [0, 1, 2] {
+            let (min, max) = (centroid_bounds.min[axis], centroid_bounds.max[axis]);
+            if max <= min {
+                continue;
+            }
+
+            let scale = SAH_BINS as f32 / (max - min);
+            let mut bins = [(Aabb::EMPTY, 0usize); SAH_BINS];
+            for &f in faces {
+                let bin = (((centroids[f][axis] - min) * scale) as usize).min(SAH_BINS - 1);
+                bins[bin].0 = bins[bin].0.union(bounds[f]);
+                bins[bin].1 += 1;
+            }
+
+            // Areas and counts left of each plane, then combined with the right side
+            let mut left = [(0.0, 0); SAH_BINS - 1];
+            let (mut acc, mut n) = (Aabb::EMPTY, 0);
+            for (plane, bin) in bins[..SAH_BINS - 1].iter().enumerate() {
+                acc = acc.union(bin.0);
+                n += bin.1;
+                left[plane] = (acc.surface_area(), n);
+            }
+
+            let (mut acc, mut n) = (Aabb::EMPTY, 0);
+            for plane in (0..SAH_BINS - 1).rev() {
+                let bin = bins[plane + 1];
+                acc = acc.union(bin.0);
+                n += bin.1;
+
+                let (left_area, left_count) = left[plane];
+                let cost = TRAVERSAL_COST
+                    + (left_area * left_count as f32 + acc.surface_area() * n as f32)
+                        / node_bounds.surface_area();
+                if left_count > 0 && n > 0 && cost < best_cost {
+                    best_cost = cost;
+                    best = Some((axis, min + (plane + 1) as f32 / scale));
+                }
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::ENGINE;
+    use crate::mesh::Mesh;
+
+    const MODELS: [&str; 2] = [
+        "../resources/models/dagger.obj",
+        "../resources/models/fox.obj",
+    ];
+
+    // Deterministic points in [0, 1), so failures can be reproduced
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> f32 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        // Inside `bounds` grown by half its size each way
+        fn point_near(&mut self, bounds: &Aabb) -> Float3 {
+            let size = bounds.size();
+            let t = Float3::new(self.next(), self.next(), self.next()) * 2.0 - Float3::ONE * 0.5;
+            bounds.min + size * t
+        }
+    }
+
+    fn meshes() -> Vec<Mesh> {
+        MODELS
+            .iter()
+            .map(|path| Mesh::from_obj_file(path, ENGINE).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_structure() {
+        for mesh in meshes() {
+            let bvh = Bvh::new(&mesh.data);
+            assert_eq!(bvh.bounds(), mesh.bounds());
+
+            // Every face ends up in exactly one leaf, inside its node's bounds
+            let mut faces = bvh.faces.clone();
+            faces.sort();
+            assert_eq!(faces, (0..mesh.data.len()).collect::<Vec<_>>());
+            for node in bvh.nodes.iter().filter(|n| n.is_leaf()) {
+                for tri in &bvh.tris[node.start..node.start + node.count] {
+                    let b = tri.bounds();
+                    assert_eq!(node.bounds.union(b), node.bounds);
+                }
+            }
+            assert!(bvh.nodes.len() > 1);
+        }
+        assert!(
+            Bvh::new(&[])
+                .intersect_ray(&Ray::new(Float3::ZERO, Float3::X), 1.0)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_ray_matches_brute_force() {
+        let mut rng = Lcg(1);
+        for mesh in meshes() {
+            let bvh = Bvh::new(&mesh.data);
+            let bounds = mesh.bounds();
+            let mut hits = 0;
+
+            for _ in 0..500 {
+                let origin = rng.point_near(&bounds);
+                let ray = Ray::new(origin, rng.point_near(&bounds) - origin);
+                let expected = ray.intersect_faces(&mesh.data);
+                let hit = bvh.intersect_ray(&ray, f32::INFINITY);
+                assert_eq!(bvh.occluded(&ray, f32::INFINITY), expected.is_some());
+
+                match (hit, expected) {
+                    // Rays through shared edges may report either face
+                    (Some(hit), Some(expected)) => {
+                        assert!((hit.t - expected.t).abs() < 1e-5);
+                        hits += 1;
+                    }
+                    (None, None) => {}
+                    (hit, expected) => panic!("{hit:?} vs {expected:?}"),
+                }
+            }
+            assert!(hits > 50);
+        }
+    }
+
+    #[test]
+    fn test_front_faces() {
+        let cube = Mesh::from_obj_file("../resources/models/cube.obj", ENGINE).unwrap();
+        let bvh = Bvh::new(&cube.data);
+        let inward = Ray::new(Float3::Z * 5.0, Float3::FORWARD);
+        let outward = Ray::new(Float3::ZERO, Float3::FORWARD);
+
+        // From outside the near side faces the ray, from inside every side faces away
+        let hit = bvh.intersect_ray_front(&inward, f32::INFINITY).unwrap();
+        assert_eq!(hit.t, bvh.intersect_ray(&inward, f32::INFINITY).unwrap().t);
+        assert!(bvh.intersect_ray(&outward, f32::INFINITY).is_some());
+        assert!(bvh.intersect_ray_front(&outward, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_overlap_matches_brute_force() {
+        let mut rng = Lcg(2);
+        for mesh in meshes() {
+            let bvh = Bvh::new(&mesh.data);
+            let bounds = mesh.bounds();
+
+            for _ in 0..100 {
+                let query = Aabb::from_points([rng.point_near(&bounds), rng.point_near(&bounds)]);
+                let mut found = bvh.overlapping(&query);
+                found.sort();
+                let expected: Vec<_> = (0..mesh.data.len())
+                    .filter(|&i| mesh.data[i].vertices.overlaps_aabb(&query))
+                    .collect();
+                assert_eq!(found, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_closest_point_matches_brute_force() {
+        let mut rng = Lcg(3);
+        for mesh in meshes() {
+            let bvh = Bvh::new(&mesh.data);
+            let bounds = mesh.bounds();
+
+            for _ in 0..200 {
+                let p = rng.point_near(&bounds);
+                let (face, q) = bvh.closest_point(p).unwrap();
+                let expected = mesh
+                    .data
+                    .iter()
+                    .map(|f| (f.vertices.closest_point(p) - p).length())
+                    .fold(f32::INFINITY, f32::min);
+
+                assert!(((q - p).length() - expected).abs() < 1e-5);
+                let on_face = mesh.data[face].vertices.closest_point(p);
+                assert!(VectorOps::approx_eq(on_face, q, 1e-6));
+            }
+        }
+    }
+}
@@ -1,25 +1,98 @@
-use crate::primitives::{Float2, Float3, Tri};
+use crate::primitives::{Float2, Float3, Quaternion, Ray, Tri, VectorOps};
+
+// Camera-space depth of the near plane (Right-Handed, so negative)
+pub const NEAR_CLIP: f32 = -0.01;
+
+/// Rotation of a camera turned by `yaw` around Y, then tilted by `pitch` around X
+pub fn look_rotation(yaw: f32, pitch: f32) -> Quaternion {
+    Quaternion::from_y_angle(yaw) * Quaternion::from_x_angle(pitch)
+}
+
+/// Inverse of `look_rotation`: the yaw and pitch that point a camera along `forward`
+pub fn look_angles(forward: Float3) -> (f32, f32) {
+    let f = forward.normalized();
+    (f32::atan2(-f.x, -f.z), f.y.clamp(-1.0, 1.0).asin())
+}
 
 // Abstract Right-Handed camera model (fully sync)
 // FOV is in radians for perspective cameras, scale for orthographic cameras
-#[derive(Debug, Clone, Copy, Default)]
-pub struct CameraModel<const WIDTH: usize, const HEIGHT: usize> {
+// The vertical FOV is fixed, the horizontal one follows the aspect of the viewport
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CameraModel {
     screen_height: f32,
+    width: usize, // Viewport size in pixels
+    height: usize,
     pub perspective: bool,
 }
 
-impl<const WIDTH: usize, const HEIGHT: usize> CameraModel<WIDTH, HEIGHT> {
-    pub fn new(fov_deg: f32, perspective: bool) -> Self {
+impl CameraModel {
+    pub fn new(fov_deg: f32, perspective: bool, width: usize, height: usize) -> Self {
         let fov_rad = f32::to_radians(fov_deg);
         Self {
             screen_height: 2.0 * f32::tan(fov_rad / 2.0),
+            width,
+            height,
             perspective,
         }
     }
 
+    /// Orthographic camera showing `view_height` world units vertically
+    pub fn orthographic(view_height: f32, width: usize, height: usize) -> Self {
+        Self {
+            screen_height: view_height,
+            width,
+            height,
+            perspective: false,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+
+    /// Changes the resolution the camera projects to, e.g. after the window was resized
+    pub fn set_viewport(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Inverse of the `fov_deg` given to `new`
+    pub fn fov_deg(&self) -> f32 {
+        f32::to_degrees(2.0 * f32::atan(self.screen_height / 2.0))
+    }
+
+    /// World-space height covered by the view, `depth` units in front of the camera
+    pub fn view_height(&self, depth: f32) -> f32 {
+        if self.perspective {
+            self.screen_height * depth
+        } else {
+            self.screen_height
+        }
+    }
+
+    /// Distance from the center of a sphere at which it fits the view both ways.
+    /// Orthographic views don't shrink with distance, the sphere only has to be in front.
+    pub fn fit_distance(&self, radius: f32) -> f32 {
+        if !self.perspective {
+            return radius * 2.0;
+        }
+
+        // Half angle of the narrower FOV
+        let tan_half_fov = self.screen_height / 2.0 * self.aspect().min(1.0);
+        radius / f32::atan(tan_half_fov).sin()
+    }
+
     pub fn point_to_screen(&self, p: Float3) -> Float2 {
-        let center = Float2::new(WIDTH as f32, HEIGHT as f32) / 2.0;
-        let mut pixels_per_world_unit = HEIGHT as f32 / self.screen_height;
+        let center = Float2::new(self.width as f32, self.height as f32) / 2.0;
+        let mut pixels_per_world_unit = self.height as f32 / self.screen_height;
         if self.perspective {
             pixels_per_world_unit /= -p.z; // Right-Handed camera
         }
@@ -27,6 +100,21 @@ impl<const WIDTH: usize, const HEIGHT: usize> CameraModel<WIDTH, HEIGHT> {
         center + Float2::new(p.x, -p.y) * pixels_per_world_unit
     }
 
+    /// Inverse of `point_to_screen`: the camera-space ray through a pixel position. Its
+    /// direction reaches depth one for perspective cameras.
+    pub fn screen_to_ray(&self, p: Float2) -> Ray {
+        let center = Float2::new(self.width as f32, self.height as f32) / 2.0;
+        let world_units_per_pixel = self.screen_height / self.height as f32;
+        let offset = (p - center) * world_units_per_pixel;
+        let offset = Float3::new(offset.x, -offset.y, 0.0);
+
+        if self.perspective {
+            Ray::new(Float3::ZERO, offset + Float3::FORWARD)
+        } else {
+            Ray::new(offset, Float3::FORWARD)
+        }
+    }
+
     pub fn tri_to_screen(&self, tri: &Tri<Float3>) -> Tri<Float2> {
         Tri::new(
             self.point_to_screen(tri.vertices[0]),
@@ -40,29 +128,28 @@ impl<const WIDTH: usize, const HEIGHT: usize> CameraModel<WIDTH, HEIGHT> {
 mod tests {
     use super::*;
     use crate::pose_graph::{PoseGraph, SharedPGNode};
+    use crate::primitives::VectorOps;
 
     const WIDTH: usize = 800;
     const HEIGHT: usize = 600;
 
     fn setup_pg() -> (SharedPGNode, SharedPGNode) {
         let root = PoseGraph::root();
-        let cam = PoseGraph::new("cam", root.clone());
-        let mesh = PoseGraph::new("mesh", root.clone());
+        let cam = PoseGraph::new("cam", root.clone()).unwrap();
+        let mesh = PoseGraph::new("mesh", root.clone()).unwrap();
 
-        cam.borrow_mut()
-            .apply_translation(Float3::new(0.0, 0.0, 1.0));
+        cam.write().apply_translation(Float3::new(0.0, 0.0, 1.0));
 
-        mesh.borrow_mut()
-            .apply_translation(Float3::new(0.2, 0.0, 0.0));
+        mesh.write().apply_translation(Float3::new(0.2, 0.0, 0.0));
 
         (cam, mesh)
     }
 
     #[test]
     fn perspective_cam_project_center() {
-        let cam = CameraModel::<WIDTH, HEIGHT>::new(45.0, true);
+        let cam = CameraModel::new(45.0, true, WIDTH, HEIGHT);
         let cam_pose = PoseGraph::root();
-        let world_to_cam = cam_pose.borrow().transform;
+        let world_to_cam = cam_pose.read().transform();
 
         let p = Float3::new(0.0, 0.0, -1.0); // RH: negative z is forward
         let screen = cam.point_to_screen(world_to_cam.apply(p));
@@ -71,9 +158,9 @@ mod tests {
 
     #[test]
     fn orthographic_cam_project_center() {
-        let cam = CameraModel::<WIDTH, HEIGHT>::new(45.0, false);
+        let cam = CameraModel::new(45.0, false, WIDTH, HEIGHT);
         let cam_pose = PoseGraph::root();
-        let world_to_cam = cam_pose.borrow().transform;
+        let world_to_cam = cam_pose.read().transform();
 
         let p = Float3::new(0.0, 0.0, 0.0);
         let screen = cam.point_to_screen(world_to_cam.apply(p));
@@ -82,7 +169,7 @@ mod tests {
 
     #[test]
     fn perspective_cam_project_offset() {
-        let cam = CameraModel::<WIDTH, HEIGHT>::new(45.0, true);
+        let cam = CameraModel::new(45.0, true, WIDTH, HEIGHT);
         let (cam_pose, mesh) = setup_pg();
         let mesh_to_cam = PoseGraph::relative_transform(&mesh, &cam_pose);
 
@@ -96,7 +183,7 @@ mod tests {
     #[test]
     fn orthographic_cam_project_offset() {
         let (cam_pose, mesh) = setup_pg();
-        let cam = CameraModel::<WIDTH, HEIGHT>::new(45.0, false);
+        let cam = CameraModel::new(45.0, false, WIDTH, HEIGHT);
         let mesh_to_cam = PoseGraph::relative_transform(&mesh, &cam_pose);
 
         let p = Float3::new(1.0, 1.0, 0.0);
@@ -105,4 +192,53 @@ mod tests {
         assert!(screen.x > WIDTH as f32 / 2.0);
         assert!(screen.y < HEIGHT as f32 / 2.0);
     }
+
+    #[test]
+    fn viewport_resize_keeps_vertical_fov() {
+        let mut cam = CameraModel::new(90.0, true, WIDTH, HEIGHT);
+        let p = Float3::new(1.0, 1.0, -1.0); // Top right corner of a 90 degree FOV
+        assert!(VectorOps::approx_eq(
+            cam.point_to_screen(p),
+            Float2::new(700.0, 0.0),
+            1e-3
+        ));
+
+        cam.set_viewport(400, 600);
+        assert!((cam.aspect() - 2.0 / 3.0).abs() < 1e-6);
+        assert!(VectorOps::approx_eq(
+            cam.point_to_screen(p),
+            Float2::new(500.0, 0.0),
+            1e-3
+        ));
+        assert!((cam.fov_deg() - 90.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn screen_to_ray_round_trip() {
+        for perspective in [true, false] {
+            let cam = CameraModel::new(60.0, perspective, WIDTH, HEIGHT);
+            let pixel = Float2::new(123.0, 456.0);
+            let ray = cam.screen_to_ray(pixel);
+            let back = cam.point_to_screen(ray.at(3.0));
+            assert!(VectorOps::approx_eq(back, pixel, 1e-3));
+        }
+    }
+
+    #[test]
+    fn test_look_angles() {
+        let (yaw, pitch) = (-0.5, 0.3);
+        let forward = look_rotation(yaw, pitch) * Float3::FORWARD;
+        let (y, p) = look_angles(forward);
+        assert!((y - yaw).abs() < 1e-5 && (p - pitch).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_fit_distance() {
+        // A 90 degree FOV fits a unit sphere at sqrt(2), the narrower axis decides
+        let mut cam = CameraModel::new(90.0, true, WIDTH, HEIGHT);
+        assert!((cam.fit_distance(1.0) - 2f32.sqrt()).abs() < 1e-5);
+        cam.set_viewport(300, 600);
+        assert!(cam.fit_distance(1.0) > 2f32.sqrt());
+        assert!((cam.view_height(2.0) - 4.0).abs() < 1e-5);
+    }
 }
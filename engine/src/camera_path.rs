@@ -0,0 +1,456 @@
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::animation::{Animatable, Interpolation, LoopMode, loop_time};
+use crate::camera::{look_angles, look_rotation};
+use crate::primitives::{Float3, Quaternion, Transform, VectorOps};
+
+// Samples per segment in the arc length table
+const ARC_SAMPLES: usize = 32;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Spline {
+    #[default]
+    CatmullRom, // Through every key
+    Bezier, // Through every third key, with the two keys in between as handles
+}
+
+/// A control pose of a camera path. With `look_at` set, the rotation is replaced by one
+/// facing that point.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PathKey {
+    pub position: Float3,
+    #[serde(default = "identity")]
+    pub rotation: Quaternion,
+    #[serde(default)]
+    pub look_at: Option<Float3>,
+}
+
+fn identity() -> Quaternion {
+    Quaternion::IDENTITY
+}
+
+fn cubic() -> Interpolation {
+    Interpolation::Cubic
+}
+
+impl PathKey {
+    pub fn new(position: Float3, rotation: Quaternion) -> Self {
+        PathKey {
+            position,
+            rotation,
+            look_at: None,
+        }
+    }
+
+    pub fn looking_at(position: Float3, target: Float3) -> Self {
+        PathKey {
+            position,
+            rotation: Quaternion::IDENTITY,
+            look_at: Some(target),
+        }
+    }
+
+    fn resolved_rotation(&self) -> Quaternion {
+        match self.look_at {
+            Some(target) => {
+                let (yaw, pitch) = look_angles(target - self.position);
+                look_rotation(yaw, pitch)
+            }
+            None => self.rotation,
+        }
+    }
+}
+
+// What camera path files contain, everything else is derived from it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PathDesc {
+    keys: Vec<PathKey>,
+    #[serde(default)]
+    spline: Spline,
+    #[serde(default)]
+    closed: bool,
+    speed: f32,
+    #[serde(default = "cubic")]
+    rotation: Interpolation,
+    #[serde(default)]
+    loop_mode: LoopMode,
+}
+
+/// Camera flythrough, stored as RON. Positions follow a spline, rotations are
+/// interpolated between the keys the path passes through. Samples are spaced by
+/// distance along the path, so the camera moves at a constant speed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "PathDesc", into = "PathDesc")]
+pub struct CameraPath {
+    keys: Vec<PathKey>,
+    spline: Spline,
+    closed: bool,                // The last key connects back to the first
+    pub speed: f32,              // World units per second
+    pub rotation: Interpolation, // Linear slerps, cubic uses squad
+    pub loop_mode: LoopMode,
+    rotations: Vec<Quaternion>, // Of the keys the path passes through
+    arc_lengths: Vec<f32>,      // Distance from the start at each table sample
+}
+
+impl TryFrom<PathDesc> for CameraPath {
+    type Error = Box<dyn Error>;
+
+    fn try_from(desc: PathDesc) -> Result<Self, Self::Error> {
+        Ok(CameraPath::new(desc.keys, desc.spline, desc.closed)?
+            .with_speed(desc.speed)
+            .with_rotation(desc.rotation)
+            .with_loop_mode(desc.loop_mode))
+    }
+}
+
+impl From<CameraPath> for PathDesc {
+    fn from(path: CameraPath) -> Self {
+        PathDesc {
+            keys: path.keys,
+            spline: path.spline,
+            closed: path.closed,
+            speed: path.speed,
+            rotation: path.rotation,
+            loop_mode: path.loop_mode,
+        }
+    }
+}
+
+impl CameraPath {
+    /// Fails if there are too few keys for the spline, or Bezier handles are incomplete.
+    /// Plays at one unit per second, with cubic rotations, once.
+    pub fn new(keys: Vec<PathKey>, spline: Spline, closed: bool) -> Result<Self, Box<dyn Error>> {
+        let n = keys.len();
+        let valid = match (spline, closed) {
+            (Spline::CatmullRom, _) => n >= 2,
+            (Spline::Bezier, false) => n >= 4 && (n - 1).is_multiple_of(3),
+            (Spline::Bezier, true) => n >= 3 && n.is_multiple_of(3),
+        };
+        if !valid {
+            let expected = match (spline, closed) {
+                (Spline::CatmullRom, _) => "at least 2",
+                (Spline::Bezier, false) => "3n + 1",
+                (Spline::Bezier, true) => "3n",
+            };
+            return Err(format!("{spline:?} camera path needs {expected} keys, got {n}").into());
+        }
+
+        let step = match spline {
+            Spline::CatmullRom => 1,
+            Spline::Bezier => 3,
+        };
+        let rotations = keys
+            .iter()
+            .step_by(step)
+            .map(PathKey::resolved_rotation)
+            .collect();
+
+        let mut path = CameraPath {
+            keys,
+            spline,
+            closed,
+            speed: 1.0,
+            rotation: Interpolation::Cubic,
+            loop_mode: LoopMode::Once,
+            rotations,
+            arc_lengths: vec![],
+        };
+        path.build_arc_lengths();
+        Ok(path)
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: Interpolation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_loop_mode(mut self, loop_mode: LoopMode) -> Self {
+        self.loop_mode = loop_mode;
+        self
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(ron::from_str(text)?)
+    }
+
+    pub fn to_ron(&self) -> Result<String, Box<dyn Error>> {
+        Ok(ron::ser::to_string_pretty(self, PrettyConfig::default())?)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+
+    pub fn keys(&self) -> &[PathKey] {
+        &self.keys
+    }
+
+    /// Total arc length, approximated by the sample table
+    pub fn length(&self) -> f32 {
+        self.arc_lengths.last().copied().unwrap_or(0.0)
+    }
+
+    /// Time to travel the whole path once
+    pub fn duration(&self) -> f32 {
+        if self.speed > 0.0 {
+            self.length() / self.speed
+        } else {
+            0.0
+        }
+    }
+
+    /// The pose at `time` according to the speed and loop mode, with the scale of `base`
+    pub fn sample(&self, time: f32, base: Transform) -> Transform {
+        let distance = loop_time(time, self.duration(), self.loop_mode) * self.speed;
+        Transform {
+            scale: base.scale,
+            ..self.sample_distance(distance)
+        }
+    }
+
+    /// The pose `distance` units along the path, clamped to its ends
+    pub fn sample_distance(&self, distance: f32) -> Transform {
+        let (segment, t) = self.param_at(distance);
+        Transform {
+            position: self.position(segment, t),
+            rotation: self.rotation(segment, t),
+            scale: Float3::ONE,
+        }
+    }
+
+    fn segments(&self) -> usize {
+        let n = self.keys.len();
+        match (self.spline, self.closed) {
+            (Spline::CatmullRom, false) => n - 1,
+            (Spline::CatmullRom, true) => n,
+            (Spline::Bezier, false) => (n - 1) / 3,
+            (Spline::Bezier, true) => n / 3,
+        }
+    }
+
+    // Wraps around closed paths, clamps to the ends of open ones
+    fn wrap(&self, i: isize, n: usize) -> usize {
+        if self.closed {
+            i.rem_euclid(n as isize) as usize
+        } else {
+            i.clamp(0, n as isize - 1) as usize
+        }
+    }
+
+    fn position(&self, segment: usize, t: f32) -> Float3 {
+        let p = |i: isize| self.keys[self.wrap(i, self.keys.len())].position;
+        match self.spline {
+            Spline::CatmullRom => {
+                let s = segment as isize;
+                Float3::cubic(p(s - 1), p(s), p(s + 1), p(s + 2), t)
+            }
+            Spline::Bezier => {
+                let s = segment as isize * 3;
+                let u = 1.0 - t;
+                p(s) * (u * u * u)
+                    + p(s + 1) * (3.0 * u * u * t)
+                    + p(s + 2) * (3.0 * u * t * t)
+                    + p(s + 3) * (t * t * t)
+            }
+        }
+    }
+
+    fn rotation(&self, segment: usize, t: f32) -> Quaternion {
+        let r = |i: isize| self.rotations[self.wrap(i, self.rotations.len())];
+        let s = segment as isize;
+        match self.rotation {
+            Interpolation::Step => r(s),
+            Interpolation::Linear => Quaternion::interpolate(r(s), r(s + 1), t),
+            Interpolation::Cubic => Quaternion::cubic(r(s - 1), r(s), r(s + 1), r(s + 2), t),
+        }
+    }
+
+    fn build_arc_lengths(&mut self) {
+        let samples = self.segments() * ARC_SAMPLES;
+        let mut lengths = Vec::with_capacity(samples + 1);
+        let mut total = 0.0;
+        let mut prev = self.position(0, 0.0);
+        lengths.push(total);
+
+        for i in 1..=samples {
+            let (segment, t) = ((i - 1) / ARC_SAMPLES, (i - 1) % ARC_SAMPLES + 1);
+            let p = self.position(segment, t as f32 / ARC_SAMPLES as f32);
+            total += (p - prev).length();
+            lengths.push(total);
+            prev = p;
+        }
+        self.arc_lengths = lengths;
+    }
+
+    // Segment and spline parameter at a distance along the path
+    fn param_at(&self, distance: f32) -> (usize, f32) {
+        let d = distance.clamp(0.0, self.length());
+        let last = self.arc_lengths.len() - 1;
+        // First sample past `d`, so d lies in [lengths[i - 1], lengths[i]]
+        let i = self.arc_lengths.partition_point(|&l| l <= d).clamp(1, last);
+        let (a, b) = (self.arc_lengths[i - 1], self.arc_lengths[i]);
+        let f = if b > a { (d - a) / (b - a) } else { 0.0 };
+
+        let u = ((i - 1) as f32 + f) / ARC_SAMPLES as f32;
+        let segment = (u as usize).min(self.segments() - 1);
+        (segment, u - segment as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::AnimationPlayer;
+    use crate::pose_graph::PoseGraph;
+
+    use std::sync::Arc;
+
+    // Unevenly spaced keys, so the spline parameter alone would change speed
+    fn keys() -> Vec<PathKey> {
+        [
+            Float3::ZERO,
+            Float3::new(1.0, 0.0, 0.0),
+            Float3::new(6.0, 2.0, 0.0),
+            Float3::new(7.0, 2.0, -5.0),
+        ]
+        .map(|p| PathKey::looking_at(p, Float3::new(3.0, 0.0, -10.0)))
+        .to_vec()
+    }
+
+    fn approx(a: Float3, b: Float3) -> bool {
+        VectorOps::approx_eq(a, b, 1e-4)
+    }
+
+    // Length of the curve between two distances, measured in small steps
+    fn arc(path: &CameraPath, from: f32, to: f32) -> f32 {
+        (0..50)
+            .map(|i| {
+                let d = |i: usize| from + (to - from) * i as f32 / 50.0;
+                let a = path.sample_distance(d(i)).position;
+                let b = path.sample_distance(d(i + 1)).position;
+                (b - a).length()
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_constant_speed() {
+        let path = CameraPath::new(keys(), Spline::CatmullRom, false).unwrap();
+        let step = path.length() / 20.0;
+        for i in 0..20 {
+            let len = arc(&path, i as f32 * step, (i + 1) as f32 * step);
+            assert!((len - step).abs() < step * 0.01, "{len} vs {step}");
+        }
+    }
+
+    #[test]
+    fn test_passes_through_keys() {
+        let keys = keys();
+        let path = CameraPath::new(keys.clone(), Spline::CatmullRom, false).unwrap();
+        assert!(approx(path.sample_distance(-1.0).position, Float3::ZERO));
+        let end = path.sample_distance(path.length() + 1.0);
+        assert!(approx(end.position, keys[3].position));
+
+        // Rotations follow the keys, here all facing the same point
+        let to_target = (Float3::new(3.0, 0.0, -10.0) - keys[3].position).normalized();
+        assert!(approx(end.forward_vec(), to_target));
+
+        // Bezier curves only touch the first and last key
+        let bezier = CameraPath::new(keys.clone(), Spline::Bezier, false).unwrap();
+        let end = bezier.sample_distance(bezier.length());
+        assert!(approx(end.position, keys[3].position));
+        assert!(bezier.length() < path.length());
+    }
+
+    #[test]
+    fn test_closed_loop() {
+        let path = CameraPath::new(keys(), Spline::CatmullRom, true)
+            .unwrap()
+            .with_speed(2.0)
+            .with_loop_mode(LoopMode::Loop);
+        let open = CameraPath::new(keys(), Spline::CatmullRom, false).unwrap();
+        assert!(path.length() > open.length());
+
+        let start = path.sample(0.0, Transform::default());
+        let lap = path.sample(path.duration(), Transform::default());
+        assert!(approx(start.position, lap.position));
+        // Half a second in, the camera has covered one unit
+        let later = path.sample(path.duration() + 0.5, Transform::default());
+        assert!((arc(&path, 0.0, 1.0) - 1.0).abs() < 0.01);
+        assert!(approx(later.position, path.sample_distance(1.0).position));
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let keys = keys();
+        assert!(CameraPath::new(keys[..1].to_vec(), Spline::CatmullRom, false).is_err());
+        assert!(CameraPath::new(keys[..3].to_vec(), Spline::Bezier, false).is_err());
+        assert!(CameraPath::new(keys[..3].to_vec(), Spline::Bezier, true).is_ok());
+        assert!(CameraPath::parse("(keys: [], speed: 1.0)").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = CameraPath::new(keys(), Spline::Bezier, false)
+            .unwrap()
+            .with_speed(3.0)
+            .with_rotation(Interpolation::Linear);
+        let text = path.to_ron().unwrap();
+        assert_eq!(CameraPath::parse(&text).unwrap(), path);
+
+        let path = CameraPath::parse(
+            "(keys: [(position: (x: 0.0, y: 0.0, z: 0.0)), (position: (x: 0.0, y: 0.0, z: -4.0))], speed: 2.0)",
+        )
+        .unwrap();
+        assert_eq!(path.duration(), 2.0);
+        assert_eq!(path.rotation, Interpolation::Cubic);
+
+        let path = CameraPath::from_file("../resources/paths/flythrough.ron").unwrap();
+        assert!(approx(path.sample_distance(0.0).position, Float3::ZERO));
+    }
+
+    #[test]
+    fn test_player_drives_camera() {
+        let root = PoseGraph::root();
+        let cam = PoseGraph::new("cam", root.clone()).unwrap();
+        cam.write().apply_scale(Float3::ONE * 2.0);
+
+        let keys = vec![
+            PathKey::new(Float3::ZERO, Quaternion::IDENTITY),
+            PathKey::new(Float3::X * 10.0, Quaternion::from_y_angle(1.0)),
+        ];
+        let path = CameraPath::new(keys, Spline::CatmullRom, false)
+            .unwrap()
+            .with_speed(4.0);
+
+        let mut player = AnimationPlayer::default();
+        player.bind_path(cam.clone(), Arc::new(path));
+        player.update(1.0);
+        let pose = cam.read().transform();
+        assert!(VectorOps::approx_eq(pose.position, Float3::X * 4.0, 1e-3));
+        assert_eq!(pose.scale, Float3::ONE * 2.0);
+
+        player.update(2.0);
+        assert!(player.is_finished());
+        assert!(VectorOps::approx_eq(
+            cam.read().transform().position,
+            Float3::X * 10.0,
+            1e-3
+        ));
+    }
+}
@@ -1,10 +1,12 @@
+use serde::{Deserialize, Serialize};
+
 use crate::primitives::Float3;
 
 pub const ENGINE: CoordinateSystem = CoordinateSystem::RightHandedYUp;
 pub const BLENDER: CoordinateSystem = CoordinateSystem::RightHandedZUp;
 
 // This engine defaults to Right-Handed/Y-Up
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CoordinateSystem {
     RightHandedYUp,
     RightHandedZUp,
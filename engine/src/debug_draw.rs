@@ -0,0 +1,420 @@
+use crate::camera::{CameraModel, NEAR_CLIP};
+use crate::pose_graph::{PoseGraph, SharedPGNode};
+use crate::primitives::{Aabb, Float2, Float3, VectorOps};
+use crate::render_buffer::RenderBuffer;
+use crate::text::BitmapFont;
+
+const ARROW_HEAD_SIZE: f32 = 0.15; // Relative to the arrow length
+const LIGHT_COLOR: Float3 = Float3::new(1.0, 0.9, 0.2);
+
+// Pairs of corner indices (see Aabb::corners) forming the box edges
+const AABB_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (1, 2),
+    (2, 3),
+    (3, 0),
+    (4, 5),
+    (5, 6),
+    (6, 7),
+    (7, 4),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+// World-space shapes are depth-tested, screen-space ones are drawn on top.
+#[derive(Debug, Clone)]
+enum DebugShape {
+    Line {
+        a: Float3,
+        b: Float3,
+        color: Float3,
+    },
+    Line2D {
+        a: Float2,
+        b: Float2,
+        color: Float3,
+    },
+    Rect {
+        min: Float2,
+        max: Float2,
+        color: Float3,
+        filled: bool,
+    },
+    Circle {
+        center: Float2,
+        radius: f32,
+        color: Float3,
+    },
+    Text {
+        pos: Float2,
+        text: String,
+        color: Float3,
+    },
+}
+
+/// Immediate-mode debug shapes. Queue them while updating the scene, then
+/// `flush` them over the rendered frame, which also clears the queue.
+#[derive(Debug, Default)]
+pub struct DebugDraw {
+    shapes: Vec<DebugShape>,
+    pub font: BitmapFont,
+}
+
+impl DebugDraw {
+    pub fn line(&mut self, a: Float3, b: Float3, color: Float3) {
+        self.shapes.push(DebugShape::Line { a, b, color });
+    }
+
+    pub fn arrow(&mut self, origin: Float3, vec: Float3, color: Float3) {
+        if vec.length() < f32::EPSILON {
+            return; // No direction to point in
+        }
+
+        let tip = origin + vec;
+        let dir = vec.normalized();
+        let helper = if dir.x.abs() < 0.9 {
+            Float3::X
+        } else {
+            Float3::Y
+        };
+        let side = dir.cross(helper).normalized();
+        let head = vec.length() * ARROW_HEAD_SIZE;
+
+        self.line(origin, tip, color);
+        self.line(tip, tip - (dir - side) * head, color);
+        self.line(tip, tip - (dir + side) * head, color);
+    }
+
+    /// Box given in the local space of `pose`
+    pub fn aabb(&mut self, aabb: &Aabb, pose: &SharedPGNode, color: Float3) {
+        let to_world = PoseGraph::world_transform(pose);
+        let corners = aabb.corners().map(|c| to_world.apply(c));
+
+        for (i, j) in AABB_EDGES {
+            self.line(corners[i], corners[j], color);
+        }
+    }
+
+    /// Axis gizmo for a pose: X is red, Y is green and Z is blue.
+    pub fn axes(&mut self, pose: &SharedPGNode, size: f32) {
+        let to_world = PoseGraph::world_transform(pose);
+        for axis in [Float3::X, Float3::Y, Float3::Z] {
+            let tip = to_world.position + to_world.rotation * axis * size;
+            self.line(to_world.position, tip, axis);
+        }
+    }
+
+    /// Arrow along the forward vector of a light's pose, starting at `origin`.
+    pub fn light_direction(&mut self, light: &SharedPGNode, origin: Float3, length: f32) {
+        let dir = PoseGraph::world_transform(light).forward_vec();
+        self.arrow(origin, dir * length, LIGHT_COLOR);
+    }
+
+    pub fn line_2d(&mut self, a: Float2, b: Float2, color: Float3) {
+        self.shapes.push(DebugShape::Line2D { a, b, color });
+    }
+
+    pub fn rect(&mut self, min: Float2, max: Float2, color: Float3) {
+        let filled = false;
+        self.shapes.push(DebugShape::Rect {
+            min,
+            max,
+            color,
+            filled,
+        });
+    }
+
+    pub fn filled_rect(&mut self, min: Float2, max: Float2, color: Float3) {
+        let filled = true;
+        self.shapes.push(DebugShape::Rect {
+            min,
+            max,
+            color,
+            filled,
+        });
+    }
+
+    pub fn circle(&mut self, center: Float2, radius: f32, color: Float3) {
+        self.shapes.push(DebugShape::Circle {
+            center,
+            radius,
+            color,
+        });
+    }
+
+    /// Screen-space text, with its top-left corner at `pos`.
+    pub fn text(&mut self, pos: Float2, text: impl Into<String>, color: Float3) {
+        let text = text.into();
+        self.shapes.push(DebugShape::Text { pos, text, color });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.shapes.clear();
+    }
+
+    pub fn flush(
+        &mut self,
+        buffer: &RenderBuffer,
+        cam_model: &CameraModel,
+        cam_pose: &SharedPGNode,
+    ) {
+        let world_to_cam = PoseGraph::world_transform(cam_pose).inverse();
+
+        for shape in self.shapes.drain(..) {
+            match shape {
+                DebugShape::Line { a, b, color } => {
+                    let (a, b) = (world_to_cam.apply(a), world_to_cam.apply(b));
+                    if let Some((a, b)) = clip_near(a, b) {
+                        let (a_screen, b_screen) =
+                            (cam_model.point_to_screen(a), cam_model.point_to_screen(b));
+                        buffer.draw_line(a_screen, b_screen, (-a.z, -b.z), color);
+                    }
+                }
+                DebugShape::Line2D { a, b, color } => buffer.draw_overlay_line(a, b, color),
+                DebugShape::Rect {
+                    min,
+                    max,
+                    color,
+                    filled: false,
+                } => {
+                    let (top_right, bottom_left) =
+                        (Float2::new(max.x, min.y), Float2::new(min.x, max.y));
+                    buffer.draw_overlay_line(min, top_right, color);
+                    buffer.draw_overlay_line(top_right, max, color);
+                    buffer.draw_overlay_line(max, bottom_left, color);
+                    buffer.draw_overlay_line(bottom_left, min, color);
+                }
+                DebugShape::Rect {
+                    min,
+                    max,
+                    color,
+                    filled: true,
+                } => {
+                    let min = Float2::new(min.x.round(), min.y.round());
+                    let max = Float2::new(max.x.round(), max.y.round());
+                    let (width, height) = (buffer.width() as f32, buffer.height() as f32);
+                    if max.x < 0.0 || max.y < 0.0 || min.x >= width || min.y >= height {
+                        continue; // Entirely off screen
+                    }
+
+                    let start_x = min.x.max(0.0) as usize;
+                    let start_y = min.y.max(0.0) as usize;
+                    let end_x = (max.x as usize).min(buffer.width() - 1);
+                    let end_y = (max.y as usize).min(buffer.height() - 1);
+
+                    for y in start_y..=end_y {
+                        for x in start_x..=end_x {
+                            buffer.set_pixel(x, y, color);
+                        }
+                    }
+                }
+                DebugShape::Circle {
+                    center,
+                    radius,
+                    color,
+                } => draw_circle(buffer, center, radius, color),
+                DebugShape::Text { pos, text, color } => {
+                    self.font.draw_text(buffer, pos, &text, color)
+                }
+            }
+        }
+    }
+}
+
+// Clips a camera-space segment to the part in front of the near plane.
+fn clip_near(a: Float3, b: Float3) -> Option<(Float3, Float3)> {
+    match (a.z < NEAR_CLIP, b.z < NEAR_CLIP) {
+        (true, true) => Some((a, b)),
+        (false, false) => None,
+        (a_visible, _) => {
+            let t = (NEAR_CLIP - a.z) / (b.z - a.z);
+            let p = a + (b - a) * t;
+            Some(if a_visible { (a, p) } else { (p, b) })
+        }
+    }
+}
+
+// Midpoint circle algorithm, mirrored over the eight octants.
+fn draw_circle(buffer: &RenderBuffer, center: Float2, radius: f32, color: Float3) {
+    if !(radius.is_finite() && radius > 0.0 && center.x.is_finite() && center.y.is_finite()) {
+        return;
+    }
+
+    // Skip circles that miss the buffer or enclose it entirely, which also
+    // bounds the radius by the buffer diagonal plus the center's distance
+    let (width, height) = (buffer.width() as f32, buffer.height() as f32);
+    let nearest = Float2::new(center.x.clamp(0.0, width), center.y.clamp(0.0, height));
+    let farthest = Float2::new(
+        if center.x < width / 2.0 { width } else { 0.0 },
+        if center.y < height / 2.0 { height } else { 0.0 },
+    );
+    if (center - nearest).length() > radius + 1.0 || (center - farthest).length() + 1.0 < radius {
+        return;
+    }
+
+    let (cx, cy) = (center.x.round() as i64, center.y.round() as i64);
+    let (w, h) = (buffer.width() as i64, buffer.height() as i64);
+    let (mut x, mut y) = (radius.round() as i64, 0);
+    let mut err = 1 - x;
+
+    while x >= y {
+        // Every octant point has one coordinate at the center plus or minus y,
+        // so once that is off screen on all sides nothing more can be drawn
+        if cx - y < 0 && cx + y >= w && cy - y < 0 && cy + y >= h {
+            break;
+        }
+
+        for (dx, dy) in [
+            (x, y),
+            (y, x),
+            (-y, x),
+            (-x, y),
+            (-x, -y),
+            (-y, -x),
+            (y, -x),
+            (x, -y),
+        ] {
+            let (px, py) = (cx + dx, cy + dy);
+            if px >= 0 && py >= 0 {
+                buffer.set_pixel(px as usize, py as usize, color);
+            }
+        }
+
+        y += 1;
+        if err < 0 {
+            err += 2 * y + 1;
+        } else {
+            x -= 1;
+            err += 2 * (y - x) + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 64;
+    const HEIGHT: usize = 48;
+
+    fn count_color(buffer: &RenderBuffer, color: Float3) -> usize {
+        buffer.pixels.iter().filter(|p| p.lock().0 == color).count()
+    }
+
+    #[test]
+    fn test_flush_clears_queue() {
+        let buffer = RenderBuffer::new(WIDTH, HEIGHT);
+        let cam_model = CameraModel::new(60.0, true, WIDTH, HEIGHT);
+        let mut debug = DebugDraw::default();
+
+        debug.line_2d(Float2::new(0.0, 0.0), Float2::new(10.0, 0.0), Float3::ONE);
+        assert!(!debug.is_empty());
+
+        debug.flush(&buffer, &cam_model, &PoseGraph::root());
+        assert!(debug.is_empty());
+        assert_eq!(count_color(&buffer, Float3::ONE), 11);
+    }
+
+    #[test]
+    fn test_line_clipped_at_near_plane() {
+        let (a, b) = (Float3::new(0.0, 0.0, -2.0), Float3::new(0.0, 0.0, 2.0));
+        let (near_a, near_b) = clip_near(a, b).unwrap();
+
+        assert_eq!(near_a, a);
+        assert!((near_b.z - NEAR_CLIP).abs() < 1e-6);
+        assert!(clip_near(-a, -a * 0.5).is_none());
+    }
+
+    #[test]
+    fn test_world_line_depth_tested() {
+        let buffer = RenderBuffer::new(WIDTH, HEIGHT);
+        let cam_model = CameraModel::new(60.0, true, WIDTH, HEIGHT);
+        let mut debug = DebugDraw::default();
+
+        // Occluder at depth 1 over the left half of the screen
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH / 2 {
+                buffer.pixels[y * WIDTH + x].lock().1 = 1.0;
+            }
+        }
+
+        let color = Float3::new(1.0, 0.0, 1.0);
+        debug.line(
+            Float3::new(-5.0, 0.0, -5.0),
+            Float3::new(5.0, 0.0, -5.0),
+            color,
+        );
+        debug.flush(&buffer, &cam_model, &PoseGraph::root());
+
+        let row = HEIGHT / 2 * WIDTH;
+        assert_eq!(buffer.pixels[row + WIDTH / 4].lock().0, Float3::ZERO);
+        assert_eq!(buffer.pixels[row + 3 * WIDTH / 4].lock().0, color);
+    }
+
+    #[test]
+    fn test_degenerate_circles_skipped() {
+        let buffer = RenderBuffer::new(WIDTH, HEIGHT);
+        let cam_model = CameraModel::new(60.0, true, WIDTH, HEIGHT);
+        let mut debug = DebugDraw::default();
+        let red = Float3::X;
+        let center = Float2::new(32.0, 24.0);
+
+        for radius in [f32::INFINITY, f32::NAN, -5.0, 0.0, 1e30, 1e9] {
+            debug.circle(center, radius, red);
+        }
+        debug.circle(Float2::new(1e30, 24.0), 1e30, red);
+        debug.circle(Float2::new(-500.0, 24.0), 100.0, red);
+        debug.flush(&buffer, &cam_model, &PoseGraph::root());
+        assert_eq!(count_color(&buffer, red), 0);
+
+        // A huge circle crossing the buffer still draws its visible arc
+        debug.circle(Float2::new(-1e5, 24.0), 1e5 + 10.0, red);
+        debug.flush(&buffer, &cam_model, &PoseGraph::root());
+        assert_eq!(buffer.pixels[24 * WIDTH + 10].lock().0, red);
+    }
+
+    #[test]
+    fn test_rect_and_circle() {
+        let buffer = RenderBuffer::new(WIDTH, HEIGHT);
+        let cam_model = CameraModel::new(60.0, true, WIDTH, HEIGHT);
+        let mut debug = DebugDraw::default();
+        let (red, green) = (Float3::X, Float3::Y);
+
+        debug.filled_rect(Float2::new(2.0, 2.0), Float2::new(5.0, 4.0), red);
+        debug.circle(Float2::new(40.0, 20.0), 5.0, green);
+        debug.flush(&buffer, &cam_model, &PoseGraph::root());
+
+        assert_eq!(count_color(&buffer, red), 4 * 3);
+        assert_eq!(buffer.pixels[20 * WIDTH + 45].lock().0, green);
+        assert_eq!(buffer.pixels[20 * WIDTH + 40].lock().0, Float3::ZERO);
+
+        // Off screen on each side, nothing reaches the border rows and columns
+        let blue = Float3::Z;
+        debug.filled_rect(Float2::new(-9.0, 2.0), Float2::new(-2.0, 8.0), blue);
+        debug.filled_rect(Float2::new(2.0, -9.0), Float2::new(8.0, -2.0), blue);
+        debug.filled_rect(Float2::new(70.0, 2.0), Float2::new(80.0, 8.0), blue);
+        debug.filled_rect(Float2::new(2.0, 50.0), Float2::new(8.0, 60.0), blue);
+        debug.flush(&buffer, &cam_model, &PoseGraph::root());
+        assert_eq!(count_color(&buffer, blue), 0);
+
+        // Partly on screen, clipped to it
+        debug.filled_rect(Float2::new(-9.0, -9.0), Float2::new(1.0, 0.0), blue);
+        debug.flush(&buffer, &cam_model, &PoseGraph::root());
+        assert_eq!(count_color(&buffer, blue), 2);
+    }
+
+    #[test]
+    fn test_zero_arrow_skipped() {
+        let mut debug = DebugDraw::default();
+        debug.arrow(Float3::ONE, Float3::ZERO, Float3::ONE);
+        assert!(debug.is_empty());
+        debug.arrow(Float3::ONE, Float3::X, Float3::ONE);
+        assert!(!debug.is_empty());
+    }
+}
@@ -1,18 +1,88 @@
+use serde::{Deserialize, Serialize};
+
+use std::borrow::Cow;
 use std::sync::Arc;
 
+use crate::material::Material;
 use crate::mesh::Mesh;
-use crate::pose_graph::SharedPGNode;
+use crate::morph::SharedMorphWeights;
+use crate::pose_graph::{PoseGraph, PoseSnapshot, SharedPGNode};
+use crate::primitives::{Aabb, FaceData3D};
 use crate::shader::PixelShader;
+use crate::skinning::Skeleton;
+
+// How an entity's triangles are drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PolygonMode {
+    #[default]
+    Fill,
+    Wireframe,
+    Points,
+    FillWireframe,
+}
 
 #[derive(Debug, Clone)]
 pub struct Entity {
     pub pose: SharedPGNode,
     pub mesh: Arc<Mesh>,
     pub shader: Arc<dyn PixelShader + Sync + Send>,
+    pub material: Material,                        // For the path tracer
+    pub polygon_mode: Option<PolygonMode>,         // Overrides the renderer's global mode
+    pub skeleton: Option<Arc<Skeleton>>,           // Deforms the mesh if it has a skin
+    pub morph_weights: Option<SharedMorphWeights>, // One per morph target of the mesh
 }
 
 impl Entity {
     pub fn new(pose: SharedPGNode, mesh: Arc<Mesh>, shader: Arc<dyn PixelShader>) -> Self {
-        Self { pose, mesh, shader }
+        Self {
+            pose,
+            mesh,
+            shader,
+            material: Material::default(),
+            polygon_mode: None,
+            skeleton: None,
+            morph_weights: None,
+        }
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub fn with_polygon_mode(mut self, mode: PolygonMode) -> Self {
+        self.polygon_mode = Some(mode);
+        self
+    }
+
+    pub fn with_skeleton(mut self, skeleton: Arc<Skeleton>) -> Self {
+        self.skeleton = Some(skeleton);
+        self
+    }
+
+    pub fn with_morph_weights(mut self, weights: SharedMorphWeights) -> Self {
+        self.morph_weights = Some(weights);
+        self
+    }
+
+    /// The mesh in its local space, deformed by the morph weights and the skeleton.
+    /// Joints are read from `poses` if given, otherwise from the live pose graph.
+    pub fn deformed_faces(&self, poses: Option<&PoseSnapshot>) -> Cow<'_, [FaceData3D]> {
+        let skin_transforms = self.skeleton.as_ref().map(|skeleton| {
+            poses
+                .and_then(|poses| skeleton.skin_transforms_in(poses, self.pose.read().id()))
+                .unwrap_or_else(|| skeleton.skin_transforms(&self.pose))
+        });
+        let morph_weights = self.morph_weights.as_ref().map(|w| w.read());
+        self.mesh.deformed(
+            morph_weights.as_deref().map(Vec::as_slice),
+            skin_transforms.as_deref(),
+        )
+    }
+
+    /// World-space bounds of the mesh, without skinning or morphs
+    pub fn world_bounds(&self) -> Aabb {
+        let world = PoseGraph::world_transform(&self.pose);
+        self.mesh.bounds().transformed(&world)
     }
 }
@@ -1,9 +1,12 @@
 // Input abstraction to avoid having to use raylib outside the engine crate
 use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::collections::VecDeque;
 
 use crate::primitives::Float2;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Key {
     Null = 0,
     Apostrophe = 39,
@@ -97,13 +100,105 @@ pub enum Key {
 }
 
 impl Key {
+    // Every key except `Null`, e.g. to poll the full keyboard state
+    pub const ALL: [Key; 88] = [
+        Key::Apostrophe,
+        Key::Comma,
+        Key::Minus,
+        Key::Period,
+        Key::Slash,
+        Key::Zero,
+        Key::One,
+        Key::Two,
+        Key::Three,
+        Key::Four,
+        Key::Five,
+        Key::Six,
+        Key::Seven,
+        Key::Eight,
+        Key::Nine,
+        Key::Semicolon,
+        Key::Equal,
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+        Key::G,
+        Key::H,
+        Key::I,
+        Key::J,
+        Key::K,
+        Key::L,
+        Key::M,
+        Key::N,
+        Key::O,
+        Key::P,
+        Key::Q,
+        Key::R,
+        Key::S,
+        Key::T,
+        Key::U,
+        Key::V,
+        Key::W,
+        Key::X,
+        Key::Y,
+        Key::Z,
+        Key::Space,
+        Key::Escape,
+        Key::Enter,
+        Key::Tab,
+        Key::Backspace,
+        Key::Insert,
+        Key::Delete,
+        Key::Right,
+        Key::Left,
+        Key::Down,
+        Key::Up,
+        Key::PageUp,
+        Key::PageDown,
+        Key::Home,
+        Key::End,
+        Key::CapsLock,
+        Key::ScrollLock,
+        Key::NumLock,
+        Key::PrintScreen,
+        Key::Pause,
+        Key::F1,
+        Key::F2,
+        Key::F3,
+        Key::F4,
+        Key::F5,
+        Key::F6,
+        Key::F7,
+        Key::F8,
+        Key::F9,
+        Key::F10,
+        Key::F11,
+        Key::F12,
+        Key::LeftShift,
+        Key::LeftControl,
+        Key::LeftAlt,
+        Key::LeftSuper,
+        Key::RightShift,
+        Key::RightControl,
+        Key::RightAlt,
+        Key::RightSuper,
+        Key::KeyboardMenu,
+        Key::LeftBracket,
+        Key::Backslash,
+        Key::RightBracket,
+        Key::Grave,
+    ];
+
     fn to_rl(self) -> KeyboardKey {
         // Safety: Key values are guaranteed to be the same as KeyboardKey values
         unsafe { std::mem::transmute(self as u32) }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MouseKey {
     Left = 0,
     Right = 1,
@@ -111,55 +206,675 @@ pub enum MouseKey {
 }
 
 impl MouseKey {
+    pub const ALL: [MouseKey; 3] = [MouseKey::Left, MouseKey::Right, MouseKey::Middle];
+
     fn to_rl(self) -> MouseButton {
         // Safety: MouseKey values are guaranteed to be the same as MouseButton values
         unsafe { std::mem::transmute(self as u32) }
     }
 }
 
+// Xbox-style names: the face buttons are A (down), B (right), X (left) and Y (up)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GamepadButton {
+    DpadUp = 1,
+    DpadRight = 2,
+    DpadDown = 3,
+    DpadLeft = 4,
+    Y = 5,
+    B = 6,
+    A = 7,
+    X = 8,
+    LeftBumper = 9,
+    LeftTrigger = 10,
+    RightBumper = 11,
+    RightTrigger = 12,
+    Select = 13,
+    Guide = 14,
+    Start = 15,
+    LeftThumb = 16,
+    RightThumb = 17,
+}
+
+impl GamepadButton {
+    pub const ALL: [GamepadButton; 17] = [
+        GamepadButton::DpadUp,
+        GamepadButton::DpadRight,
+        GamepadButton::DpadDown,
+        GamepadButton::DpadLeft,
+        GamepadButton::Y,
+        GamepadButton::B,
+        GamepadButton::A,
+        GamepadButton::X,
+        GamepadButton::LeftBumper,
+        GamepadButton::LeftTrigger,
+        GamepadButton::RightBumper,
+        GamepadButton::RightTrigger,
+        GamepadButton::Select,
+        GamepadButton::Guide,
+        GamepadButton::Start,
+        GamepadButton::LeftThumb,
+        GamepadButton::RightThumb,
+    ];
+
+    fn to_rl(self) -> raylib::consts::GamepadButton {
+        // Safety: GamepadButton values are guaranteed to be the same as raylib's
+        unsafe { std::mem::transmute(self as u32) }
+    }
+}
+
+// Sticks range from -1 to 1, with positive Y pointing down. Raw trigger values rest at -1
+// and reach 1 when fully pressed, `Input::gamepad_axis` maps them to 0..1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    LeftX = 0,
+    LeftY = 1,
+    RightX = 2,
+    RightY = 3,
+    LeftTrigger = 4,
+    RightTrigger = 5,
+}
+
+impl GamepadAxis {
+    pub const ALL: [GamepadAxis; 6] = [
+        GamepadAxis::LeftX,
+        GamepadAxis::LeftY,
+        GamepadAxis::RightX,
+        GamepadAxis::RightY,
+        GamepadAxis::LeftTrigger,
+        GamepadAxis::RightTrigger,
+    ];
+
+    pub fn is_trigger(self) -> bool {
+        matches!(self, GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger)
+    }
+
+    fn to_rl(self) -> raylib::consts::GamepadAxis {
+        // Safety: GamepadAxis values are guaranteed to be the same as raylib's
+        unsafe { std::mem::transmute(self as u32) }
+    }
+}
+
+pub const MAX_GAMEPADS: usize = 4;
+pub const GAMEPAD_DEADZONE: f32 = 0.15;
+pub const TRIGGER_DEADZONE: f32 = 0.05; // Of the 0..1 trigger range
+
+/// Zero inside the deadzone, rescaled so the output still covers the full range outside
+pub fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+    match value.abs() <= deadzone {
+        true => 0.0,
+        false => value.signum() * (value.abs() - deadzone) / (1.0 - deadzone),
+    }
+}
+
+// Input state for a single frame. `Scene::update_state` and controllers only see this
+// trait, so they can be driven by a script in tests.
+pub trait Input {
+    fn lock_cursor(&mut self);
+
+    fn unlock_cursor(&mut self);
+
+    fn is_key_down_this_frame(&self, key: Key) -> bool;
+
+    fn is_key_held(&self, key: Key) -> bool;
+
+    fn is_mouse_down_this_frame(&self, button: MouseKey) -> bool;
+
+    fn is_mouse_held(&self, button: MouseKey) -> bool;
+
+    fn get_mouse_position(&self) -> Float2;
+
+    fn get_mouse_delta(&self) -> Float2;
+
+    // Window size in pixels
+    fn screen_size(&self) -> Float2;
+
+    fn is_key_released(&self, key: Key) -> bool;
+
+    fn is_mouse_released(&self, button: MouseKey) -> bool;
+
+    // Scroll this frame, positive Y is away from the user
+    fn get_mouse_wheel(&self) -> Float2;
+
+    // Characters typed this frame, with keyboard layout and key repeat applied
+    fn typed_text(&self) -> &str;
+
+    fn is_gamepad_available(&self, pad: usize) -> bool;
+
+    fn is_gamepad_down_this_frame(&self, pad: usize, button: GamepadButton) -> bool;
+
+    fn is_gamepad_held(&self, pad: usize, button: GamepadButton) -> bool;
+
+    fn is_gamepad_released(&self, pad: usize, button: GamepadButton) -> bool;
+
+    // Axis position without a deadzone
+    fn gamepad_axis_raw(&self, pad: usize, axis: GamepadAxis) -> f32;
+
+    // Sticks from -1 to 1 and triggers from 0 to 1, with their deadzones applied.
+    // Zero for missing pads.
+    fn gamepad_axis(&self, pad: usize, axis: GamepadAxis) -> f32 {
+        if !self.is_gamepad_available(pad) {
+            return 0.0;
+        }
+        let raw = self.gamepad_axis_raw(pad, axis);
+        match axis.is_trigger() {
+            true => apply_deadzone(((raw + 1.0) / 2.0).clamp(0.0, 1.0), TRIGGER_DEADZONE),
+            false => apply_deadzone(raw, GAMEPAD_DEADZONE),
+        }
+    }
+}
+
 #[derive(Debug)]
-pub struct Input<'a> {
+pub struct RaylibInput<'a> {
     rl: &'a mut RaylibHandle,
+    text: String,
 }
 
-impl<'a> Input<'a> {
+impl<'a> RaylibInput<'a> {
+    // Should be created once per frame, since it drains raylib's queue of typed characters
     pub fn new(rl: &'a mut RaylibHandle) -> Self {
-        Input { rl }
+        let mut text = String::new();
+        while let Some(c) = rl.get_char_pressed() {
+            text.push(c);
+        }
+        RaylibInput { rl, text }
+    }
+
+    /// The complete input state of this frame, e.g. for recording
+    pub fn capture(&self) -> InputFrame {
+        InputFrame {
+            keys_held: Key::ALL
+                .into_iter()
+                .filter(|&k| self.is_key_held(k))
+                .collect(),
+            keys_pressed: Key::ALL
+                .into_iter()
+                .filter(|&k| self.is_key_down_this_frame(k))
+                .collect(),
+            mouse_held: MouseKey::ALL
+                .into_iter()
+                .filter(|&b| self.is_mouse_held(b))
+                .collect(),
+            mouse_pressed: MouseKey::ALL
+                .into_iter()
+                .filter(|&b| self.is_mouse_down_this_frame(b))
+                .collect(),
+            mouse_position: self.get_mouse_position(),
+            mouse_delta: self.get_mouse_delta(),
+            screen_size: self.screen_size(),
+            keys_released: Key::ALL
+                .into_iter()
+                .filter(|&k| self.is_key_released(k))
+                .collect(),
+            mouse_released: MouseKey::ALL
+                .into_iter()
+                .filter(|&b| self.is_mouse_released(b))
+                .collect(),
+            mouse_wheel: self.get_mouse_wheel(),
+            text: self.text.clone(),
+            gamepads: (0..MAX_GAMEPADS)
+                .map(|pad| self.capture_gamepad(pad))
+                .collect(),
+        }
+    }
+
+    fn capture_gamepad(&self, pad: usize) -> GamepadFrame {
+        if !self.is_gamepad_available(pad) {
+            return GamepadFrame::default();
+        }
+
+        let buttons = |f: &dyn Fn(GamepadButton) -> bool| {
+            GamepadButton::ALL.into_iter().filter(|&b| f(b)).collect()
+        };
+        GamepadFrame {
+            connected: true,
+            held: buttons(&|b| self.is_gamepad_held(pad, b)),
+            pressed: buttons(&|b| self.is_gamepad_down_this_frame(pad, b)),
+            released: buttons(&|b| self.is_gamepad_released(pad, b)),
+            axes: GamepadAxis::ALL.map(|axis| self.gamepad_axis_raw(pad, axis)),
+        }
     }
+}
 
-    pub fn lock_cursor(&mut self) {
+impl Input for RaylibInput<'_> {
+    fn lock_cursor(&mut self) {
         self.rl.hide_cursor();
         self.rl.disable_cursor();
     }
 
-    pub fn unlock_cursor(&mut self) {
+    fn unlock_cursor(&mut self) {
         self.rl.enable_cursor();
         self.rl.show_cursor();
     }
 
-    pub fn is_key_down_this_frame(&self, key: Key) -> bool {
+    fn is_key_down_this_frame(&self, key: Key) -> bool {
         self.rl.is_key_pressed(key.to_rl())
     }
 
-    pub fn is_key_held(&self, key: Key) -> bool {
+    fn is_key_held(&self, key: Key) -> bool {
         self.rl.is_key_down(key.to_rl())
     }
 
-    pub fn is_mouse_down_this_frame(&self, button: MouseKey) -> bool {
+    fn is_mouse_down_this_frame(&self, button: MouseKey) -> bool {
         self.rl.is_mouse_button_pressed(button.to_rl())
     }
 
-    pub fn is_mouse_held(&self, button: MouseKey) -> bool {
+    fn is_mouse_held(&self, button: MouseKey) -> bool {
         self.rl.is_mouse_button_down(button.to_rl())
     }
 
-    pub fn get_mouse_position(&self) -> Float2 {
+    fn get_mouse_position(&self) -> Float2 {
         let v = self.rl.get_mouse_position();
         Float2::new(v.x, v.y)
     }
 
-    pub fn get_mouse_delta(&self) -> Float2 {
+    fn get_mouse_delta(&self) -> Float2 {
         let v = self.rl.get_mouse_delta();
         Float2::new(v.x, v.y)
     }
+
+    fn screen_size(&self) -> Float2 {
+        Float2::new(
+            self.rl.get_screen_width() as f32,
+            self.rl.get_screen_height() as f32,
+        )
+    }
+
+    fn is_key_released(&self, key: Key) -> bool {
+        self.rl.is_key_released(key.to_rl())
+    }
+
+    fn is_mouse_released(&self, button: MouseKey) -> bool {
+        self.rl.is_mouse_button_released(button.to_rl())
+    }
+
+    fn get_mouse_wheel(&self) -> Float2 {
+        let v = self.rl.get_mouse_wheel_move_v();
+        Float2::new(v.x, v.y)
+    }
+
+    fn typed_text(&self) -> &str {
+        &self.text
+    }
+
+    fn is_gamepad_available(&self, pad: usize) -> bool {
+        self.rl.is_gamepad_available(pad as i32)
+    }
+
+    fn is_gamepad_down_this_frame(&self, pad: usize, button: GamepadButton) -> bool {
+        self.rl
+            .is_gamepad_button_pressed(pad as i32, button.to_rl())
+    }
+
+    fn is_gamepad_held(&self, pad: usize, button: GamepadButton) -> bool {
+        self.rl.is_gamepad_button_down(pad as i32, button.to_rl())
+    }
+
+    fn is_gamepad_released(&self, pad: usize, button: GamepadButton) -> bool {
+        self.rl
+            .is_gamepad_button_released(pad as i32, button.to_rl())
+    }
+
+    fn gamepad_axis_raw(&self, pad: usize, axis: GamepadAxis) -> f32 {
+        self.rl.get_gamepad_axis_movement(pad as i32, axis.to_rl())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadFrame {
+    pub connected: bool,
+    pub held: Vec<GamepadButton>,
+    pub pressed: Vec<GamepadButton>,
+    pub released: Vec<GamepadButton>,
+    pub axes: [f32; 6], // Raw values, indexed by `GamepadAxis`
+}
+
+// Sticks centered and triggers released
+impl Default for GamepadFrame {
+    fn default() -> Self {
+        GamepadFrame {
+            connected: false,
+            held: vec![],
+            pressed: vec![],
+            released: vec![],
+            axes: GamepadAxis::ALL.map(|axis| if axis.is_trigger() { -1.0 } else { 0.0 }),
+        }
+    }
+}
+
+/// Snapshot of the input state of one frame. Keys and buttons pressed this frame also
+/// count as held.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputFrame {
+    pub keys_held: Vec<Key>,
+    pub keys_pressed: Vec<Key>,
+    pub mouse_held: Vec<MouseKey>,
+    pub mouse_pressed: Vec<MouseKey>,
+    pub mouse_position: Float2,
+    pub mouse_delta: Float2,
+    pub screen_size: Float2,
+    pub keys_released: Vec<Key>,
+    pub mouse_released: Vec<MouseKey>,
+    pub mouse_wheel: Float2,
+    pub text: String,
+    pub gamepads: Vec<GamepadFrame>, // Indexed by pad
+}
+
+impl InputFrame {
+    pub fn with_key_held(mut self, key: Key) -> Self {
+        self.keys_held.push(key);
+        self
+    }
+
+    pub fn with_key_pressed(mut self, key: Key) -> Self {
+        self.keys_pressed.push(key);
+        self.with_key_held(key)
+    }
+
+    pub fn with_mouse_held(mut self, button: MouseKey) -> Self {
+        self.mouse_held.push(button);
+        self
+    }
+
+    pub fn with_mouse_pressed(mut self, button: MouseKey) -> Self {
+        self.mouse_pressed.push(button);
+        self.with_mouse_held(button)
+    }
+
+    pub fn with_mouse(mut self, position: Float2, delta: Float2) -> Self {
+        self.mouse_position = position;
+        self.mouse_delta = delta;
+        self
+    }
+
+    pub fn with_screen_size(mut self, width: f32, height: f32) -> Self {
+        self.screen_size = Float2::new(width, height);
+        self
+    }
+
+    pub fn with_key_released(mut self, key: Key) -> Self {
+        self.keys_released.push(key);
+        self
+    }
+
+    pub fn with_mouse_released(mut self, button: MouseKey) -> Self {
+        self.mouse_released.push(button);
+        self
+    }
+
+    pub fn with_mouse_wheel(mut self, wheel: Float2) -> Self {
+        self.mouse_wheel = wheel;
+        self
+    }
+
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text.push_str(text);
+        self
+    }
+
+    pub fn with_gamepad_held(mut self, pad: usize, button: GamepadButton) -> Self {
+        self.gamepad_mut(pad).held.push(button);
+        self
+    }
+
+    pub fn with_gamepad_pressed(mut self, pad: usize, button: GamepadButton) -> Self {
+        self.gamepad_mut(pad).pressed.push(button);
+        self.with_gamepad_held(pad, button)
+    }
+
+    pub fn with_gamepad_released(mut self, pad: usize, button: GamepadButton) -> Self {
+        self.gamepad_mut(pad).released.push(button);
+        self
+    }
+
+    pub fn with_gamepad_axis(mut self, pad: usize, axis: GamepadAxis, value: f32) -> Self {
+        self.gamepad_mut(pad).axes[axis as usize] = value;
+        self
+    }
+
+    // Connects the pad, and any missing ones before it
+    fn gamepad_mut(&mut self, pad: usize) -> &mut GamepadFrame {
+        if self.gamepads.len() <= pad {
+            self.gamepads.resize_with(pad + 1, GamepadFrame::default);
+        }
+        let gamepad = &mut self.gamepads[pad];
+        gamepad.connected = true;
+        gamepad
+    }
+
+    fn gamepad(&self, pad: usize) -> Option<&GamepadFrame> {
+        self.gamepads.get(pad).filter(|g| g.connected)
+    }
+}
+
+/// Plays back a list of input frames, one per call to `next_frame`
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedInput {
+    frames: VecDeque<InputFrame>,
+    current: InputFrame,
+    cursor_locked: bool,
+}
+
+impl ScriptedInput {
+    pub fn new(frames: impl IntoIterator<Item = InputFrame>) -> Self {
+        ScriptedInput {
+            frames: frames.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Moves on to the next frame of the script. Returns false once the script has run
+    /// out, after which nothing is held or pressed.
+    pub fn next_frame(&mut self) -> bool {
+        match self.frames.pop_front() {
+            Some(frame) => {
+                self.current = frame;
+                true
+            }
+            None => {
+                // Keep the window size, drop everything else
+                self.current = InputFrame::default()
+                    .with_screen_size(self.current.screen_size.x, self.current.screen_size.y);
+                false
+            }
+        }
+    }
+
+    pub fn is_cursor_locked(&self) -> bool {
+        self.cursor_locked
+    }
+}
+
+impl Input for ScriptedInput {
+    fn lock_cursor(&mut self) {
+        self.cursor_locked = true;
+    }
+
+    fn unlock_cursor(&mut self) {
+        self.cursor_locked = false;
+    }
+
+    fn is_key_down_this_frame(&self, key: Key) -> bool {
+        self.current.keys_pressed.contains(&key)
+    }
+
+    fn is_key_held(&self, key: Key) -> bool {
+        self.current.keys_held.contains(&key)
+    }
+
+    fn is_mouse_down_this_frame(&self, button: MouseKey) -> bool {
+        self.current.mouse_pressed.contains(&button)
+    }
+
+    fn is_mouse_held(&self, button: MouseKey) -> bool {
+        self.current.mouse_held.contains(&button)
+    }
+
+    fn get_mouse_position(&self) -> Float2 {
+        self.current.mouse_position
+    }
+
+    fn get_mouse_delta(&self) -> Float2 {
+        self.current.mouse_delta
+    }
+
+    fn screen_size(&self) -> Float2 {
+        self.current.screen_size
+    }
+
+    fn is_key_released(&self, key: Key) -> bool {
+        self.current.keys_released.contains(&key)
+    }
+
+    fn is_mouse_released(&self, button: MouseKey) -> bool {
+        self.current.mouse_released.contains(&button)
+    }
+
+    fn get_mouse_wheel(&self) -> Float2 {
+        self.current.mouse_wheel
+    }
+
+    fn typed_text(&self) -> &str {
+        &self.current.text
+    }
+
+    fn is_gamepad_available(&self, pad: usize) -> bool {
+        self.current.gamepad(pad).is_some()
+    }
+
+    fn is_gamepad_down_this_frame(&self, pad: usize, button: GamepadButton) -> bool {
+        self.current
+            .gamepad(pad)
+            .is_some_and(|g| g.pressed.contains(&button))
+    }
+
+    fn is_gamepad_held(&self, pad: usize, button: GamepadButton) -> bool {
+        self.current
+            .gamepad(pad)
+            .is_some_and(|g| g.held.contains(&button))
+    }
+
+    fn is_gamepad_released(&self, pad: usize, button: GamepadButton) -> bool {
+        self.current
+            .gamepad(pad)
+            .is_some_and(|g| g.released.contains(&button))
+    }
+
+    fn gamepad_axis_raw(&self, pad: usize, axis: GamepadAxis) -> f32 {
+        self.current
+            .gamepad(pad)
+            .map_or(0.0, |g| g.axes[axis as usize])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripted_input() {
+        let mut input = ScriptedInput::new([
+            InputFrame::default().with_key_pressed(Key::W),
+            InputFrame::default()
+                .with_key_held(Key::W)
+                .with_mouse_held(MouseKey::Left)
+                .with_mouse(Float2::new(5.0, 6.0), Float2::new(1.0, -1.0))
+                .with_screen_size(640.0, 480.0),
+        ]);
+        assert!(!input.is_key_held(Key::W)); // Nothing before the first frame
+
+        assert!(input.next_frame());
+        assert!(input.is_key_down_this_frame(Key::W));
+        assert!(input.is_key_held(Key::W));
+
+        assert!(input.next_frame());
+        assert!(!input.is_key_down_this_frame(Key::W));
+        assert!(input.is_key_held(Key::W));
+        assert!(input.is_mouse_held(MouseKey::Left));
+        assert!(!input.is_mouse_down_this_frame(MouseKey::Left));
+        assert_eq!(input.get_mouse_delta(), Float2::new(1.0, -1.0));
+
+        assert!(!input.next_frame());
+        assert!(!input.is_key_held(Key::W));
+        assert_eq!(input.screen_size(), Float2::new(640.0, 480.0));
+    }
+
+    #[test]
+    fn test_scripted_events() {
+        let mut input = ScriptedInput::new([InputFrame::default()
+            .with_key_released(Key::Space)
+            .with_mouse_released(MouseKey::Right)
+            .with_mouse_wheel(Float2::new(0.0, -2.0))
+            .with_text("hé")]);
+        input.next_frame();
+
+        assert!(input.is_key_released(Key::Space));
+        assert!(!input.is_key_held(Key::Space));
+        assert!(input.is_mouse_released(MouseKey::Right));
+        assert_eq!(input.get_mouse_wheel(), Float2::new(0.0, -2.0));
+        assert_eq!(input.typed_text(), "hé");
+    }
+
+    #[test]
+    fn test_scripted_gamepad() {
+        let mut input = ScriptedInput::new([InputFrame::default()
+            .with_gamepad_pressed(1, GamepadButton::A)
+            .with_gamepad_axis(1, GamepadAxis::LeftX, 0.1)
+            .with_gamepad_axis(1, GamepadAxis::LeftY, -1.0)]);
+        input.next_frame();
+
+        assert!(!input.is_gamepad_available(0));
+        assert!(input.is_gamepad_available(1));
+        assert!(input.is_gamepad_down_this_frame(1, GamepadButton::A));
+        assert!(input.is_gamepad_held(1, GamepadButton::A));
+        assert!(!input.is_gamepad_held(0, GamepadButton::A));
+
+        // Small stick movements are ignored, full ones stay full
+        assert_eq!(input.gamepad_axis_raw(1, GamepadAxis::LeftX), 0.1);
+        assert_eq!(input.gamepad_axis(1, GamepadAxis::LeftX), 0.0);
+        assert_eq!(input.gamepad_axis(1, GamepadAxis::LeftY), -1.0);
+    }
+
+    #[test]
+    fn test_trigger_range() {
+        let trigger = |frame: InputFrame| {
+            let mut input = ScriptedInput::new([frame]);
+            input.next_frame();
+            input.gamepad_axis(0, GamepadAxis::LeftTrigger)
+        };
+        let raw = |value: f32| {
+            InputFrame::default().with_gamepad_axis(0, GamepadAxis::LeftTrigger, value)
+        };
+
+        // Released triggers read 0, also if the frame never set them
+        assert_eq!(trigger(raw(-1.0)), 0.0);
+        assert_eq!(
+            trigger(InputFrame::default().with_gamepad_pressed(0, GamepadButton::A)),
+            0.0
+        );
+        assert_eq!(trigger(raw(-0.95)), 0.0); // Inside the deadzone
+        assert!((trigger(raw(0.0)) - apply_deadzone(0.5, TRIGGER_DEADZONE)).abs() < 1e-6);
+        assert_eq!(trigger(raw(1.0)), 1.0);
+
+        // Missing pads don't report half pressed triggers
+        assert_eq!(trigger(InputFrame::default()), 0.0);
+    }
+
+    #[test]
+    fn test_deadzone() {
+        assert_eq!(apply_deadzone(0.2, 0.2), 0.0);
+        assert!((apply_deadzone(0.6, 0.2) - 0.5).abs() < 1e-6);
+        assert!((apply_deadzone(-0.6, 0.2) + 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_scripted_cursor_lock() {
+        let mut input = ScriptedInput::default();
+        input.lock_cursor();
+        assert!(input.is_cursor_locked());
+        input.unlock_cursor();
+        assert!(!input.is_cursor_locked());
+    }
 }
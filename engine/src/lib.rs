@@ -1,11 +1,25 @@
+pub mod actions;
+pub mod animation;
+pub mod bvh;
 pub mod camera;
+pub mod camera_path;
 pub mod coords;
+pub mod debug_draw;
 pub mod entity;
 pub mod input;
+pub mod material;
 pub mod mesh;
+pub mod morph;
+pub mod orbit;
+pub mod picking;
 pub mod pose_graph;
 pub mod primitives;
+pub mod recording;
 pub mod render_buffer;
+pub mod render_scale;
 pub mod scene;
+pub mod scene_file;
 pub mod shader;
+pub mod skinning;
+pub mod text;
 pub mod texture;
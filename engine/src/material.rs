@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+use crate::primitives::Float3;
+
+/// How a surface scatters and emits light, for renderers that simulate it. The
+/// rasterizer uses the entity's shader instead. Missing fields take the default, a
+/// matte light grey.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Material {
+    pub albedo: Float3,   // Diffuse reflectance per channel
+    pub specular: f32,    // Fraction of light reflected like a mirror, from 0 to 1
+    pub roughness: f32,   // Spread of the specular reflection, 0 is a perfect mirror
+    pub emission: Float3, // Emitted radiance, which makes the mesh an area light
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            albedo: Float3::ONE * 0.8,
+            specular: 0.0,
+            roughness: 0.0,
+            emission: Float3::ZERO,
+        }
+    }
+}
+
+impl Material {
+    pub fn diffuse(albedo: Float3) -> Self {
+        Material {
+            albedo,
+            ..Default::default()
+        }
+    }
+
+    pub fn emissive(emission: Float3) -> Self {
+        Material {
+            albedo: Float3::ZERO,
+            emission,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emission.x > 0.0 || self.emission.y > 0.0 || self.emission.z > 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_defaults() {
+        let material: Material = ron::from_str("(specular: 0.5)").unwrap();
+        assert_eq!(material.specular, 0.5);
+        assert_eq!(material.albedo, Material::default().albedo);
+        assert!(!material.is_emissive());
+
+        let lamp: Material = ron::from_str("(emission: (x: 4.0, y: 4.0, z: 3.0))").unwrap();
+        assert!(lamp.is_emissive());
+    }
+}
@@ -1,26 +1,66 @@
+use std::borrow::Cow;
+use std::error::Error;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
+use crate::bvh::Bvh;
 use crate::coords::CoordinateSystem;
-use crate::primitives::{FaceData3D, Float2, Float3, Tri, VectorOps};
+use crate::morph::{MorphTarget, morph_faces};
+use crate::primitives::{Aabb, FaceData3D, Float2, Float3, Transform, Tri, VectorOps};
+use crate::skinning::{JointDesc, Skin, VertexWeights, skin_faces};
+
+// File a mesh was loaded from
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MeshSource {
+    pub path: PathBuf,
+    pub coords: CoordinateSystem,
+}
 
 #[derive(Default, Debug, Clone)]
 pub struct Mesh {
     pub data: Vec<FaceData3D>,
+    pub skin: Option<Skin>,
+    morph_targets: Vec<MorphTarget>, // Each has one delta per face in `data`
+    pub source: Option<MeshSource>,
+    bvh: OnceLock<Bvh>,
 }
 
 impl Mesh {
+    pub fn new(data: Vec<FaceData3D>) -> Self {
+        Mesh {
+            data,
+            ..Default::default()
+        }
+    }
+
+    // Besides the standard statements, skinned meshes use two extensions:
+    //   j <name> <parent index or -1> <x> <y> <z>   joint, with its bind position relative to the parent
+    //   vw <joint> <weight> [<joint> <weight> ...]  weights of the vertex from the matching `v` line
     pub fn from_obj_file<P: AsRef<Path>>(
         path: P,
         coords: CoordinateSystem,
     ) -> std::io::Result<Self> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
+        let file = File::open(&path)?;
+        let mut mesh = Mesh::read_obj(BufReader::new(file), coords)?;
+        mesh.source = Some(MeshSource {
+            path: path.as_ref().to_path_buf(),
+            coords,
+        });
+        Ok(mesh)
+    }
+
+    fn read_obj<R: BufRead>(reader: R, coords: CoordinateSystem) -> std::io::Result<Self> {
+        let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
 
         let mut vertices = Vec::new();
         let mut normals = Vec::new();
         let mut uvs = Vec::new();
+        let mut joints = Vec::new();
+        let mut vertex_weights = Vec::new();
+        let mut face_weights = Vec::new();
+        let mut max_joint = None; // Highest joint index used by a `vw` line
         let mut mesh = Mesh::default();
 
         for line in reader.lines() {
@@ -46,6 +86,38 @@ impl Mesh {
                         let nums: Vec<f32> = rest.split_whitespace().flat_map(str::parse).collect();
                         uvs.push(Float2::new(nums[0], nums[1]));
                     }
+                    "j" => {
+                        let parts: Vec<&str> = rest.split_whitespace().collect();
+                        // -1 for a root, otherwise one of the joints listed before
+                        let parent = match parts.get(1).and_then(|p| p.parse::<i64>().ok()) {
+                            Some(-1) => None,
+                            Some(p) if (0..joints.len() as i64).contains(&p) => Some(p as usize),
+                            _ => return Err(invalid(format!("invalid joint parent: {line}"))),
+                        };
+                        let nums: Vec<f32> = parts[2..].iter().flat_map(|s| s.parse()).collect();
+                        let position = Float3::new(nums[0], nums[1], nums[2]);
+                        joints.push(JointDesc {
+                            name: parts[0].to_string(),
+                            parent,
+                            position: coords.to_engine_coords(position),
+                        });
+                    }
+                    "vw" => {
+                        let parts: Vec<&str> = rest.split_whitespace().collect();
+                        let influences = parts
+                            .chunks(2)
+                            .map(|jw| match jw {
+                                [joint, weight] => {
+                                    Some((joint.parse().ok()?, weight.parse().ok()?))
+                                }
+                                _ => None,
+                            })
+                            .collect::<Option<Vec<(u16, f32)>>>()
+                            .ok_or_else(|| invalid(format!("invalid joint weights: {line}")))?;
+                        let line_max = influences.iter().map(|&(joint, _)| joint).max();
+                        max_joint = max_joint.max(line_max);
+                        vertex_weights.push(VertexWeights::from_influences(&influences));
+                    }
                     "f" => {
                         let parts: Vec<&str> = rest.split_whitespace().collect();
                         let mut vertex_vals = Vec::new();
@@ -58,6 +130,9 @@ impl Mesh {
                                 indices[0].map_or(Float3::ZERO, |i| vertices[i]),
                                 indices[2].map_or(Float3::ZERO, |i| normals[i]),
                                 indices[1].map_or(Float2::ZERO, |i| uvs[i]),
+                                indices[0]
+                                    .and_then(|i| vertex_weights.get(i).copied())
+                                    .unwrap_or_default(),
                             ));
                         }
 
@@ -72,6 +147,7 @@ impl Mesh {
                                 normals: Tri::new(v1.1, v2.1, v3.1),
                                 uvs: Tri::new(v1.2, v2.2, v3.2),
                             });
+                            face_weights.push(Tri::new(v1.3, v2.3, v3.3));
                         }
                     }
                     _ => {}
@@ -79,18 +155,133 @@ impl Mesh {
             }
         }
 
+        if let Some(joint) = max_joint.filter(|&j| j as usize >= joints.len()) {
+            let count = joints.len();
+            return Err(invalid(format!(
+                "joint {joint} out of range, {count} joints"
+            )));
+        }
+        if !joints.is_empty() {
+            mesh.skin = Some(Skin {
+                joints,
+                weights: face_weights,
+            });
+        }
         Ok(mesh)
     }
+
+    /// BVH over the undeformed faces, built on first use. `data` must not change after.
+    pub fn bvh(&self) -> &Bvh {
+        self.bvh.get_or_init(|| Bvh::new(&self.data))
+    }
+
+    pub fn morph_targets(&self) -> &[MorphTarget] {
+        &self.morph_targets
+    }
+
+    /// Adds a blend shape, which must have a position and normal delta for every face
+    pub fn add_morph_target(&mut self, target: MorphTarget) -> Result<(), Box<dyn Error>> {
+        let faces = self.data.len();
+        if target.position_deltas.len() != faces || target.normal_deltas.len() != faces {
+            return Err(format!(
+                "Morph target '{}' has {} position and {} normal deltas, but the mesh has {faces} faces",
+                target.name,
+                target.position_deltas.len(),
+                target.normal_deltas.len()
+            )
+            .into());
+        }
+        self.morph_targets.push(target);
+        Ok(())
+    }
+
+    /// The faces with morph targets applied first, then skinning. Borrows the base
+    /// mesh if neither applies.
+    pub fn deformed(
+        &self,
+        morph_weights: Option<&[f32]>,
+        skin_transforms: Option<&[Transform]>,
+    ) -> Cow<'_, [FaceData3D]> {
+        let mut faces = Cow::Borrowed(self.data.as_slice());
+
+        if let Some(weights) = morph_weights.filter(|_| !self.morph_targets.is_empty()) {
+            faces = Cow::Owned(morph_faces(&faces, &self.morph_targets, weights));
+        }
+        if let (Some(skin), Some(transforms)) = (&self.skin, skin_transforms) {
+            faces = Cow::Owned(skin_faces(&faces, skin, transforms));
+        }
+        faces
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(self.data.iter().flat_map(|f| f.vertices.vertices))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::coords::ENGINE;
+    use crate::primitives::Ray;
 
     #[test]
     fn test_mesh_from_obj() {
         let mesh = Mesh::from_obj_file("../resources/models/cube.obj", ENGINE).unwrap();
         assert_eq!(mesh.data.len(), 12);
     }
+
+    #[test]
+    fn test_mesh_bounds() {
+        let mesh = Mesh::from_obj_file("../resources/models/cube.obj", ENGINE).unwrap();
+        let bounds = mesh.bounds();
+        assert_eq!(bounds.min, Float3::ONE * -1.0);
+        assert_eq!(bounds.max, Float3::ONE);
+    }
+
+    #[test]
+    fn test_bvh_built_once() {
+        let mesh = Mesh::from_obj_file("../resources/models/cube.obj", ENGINE).unwrap();
+        assert!(std::ptr::eq(mesh.bvh(), mesh.bvh()));
+
+        let ray = Ray::new(Float3::Z * 5.0, Float3::FORWARD);
+        let hit = mesh.bvh().intersect_ray(&ray, f32::INFINITY).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_add_morph_target() {
+        let mut mesh = Mesh::from_obj_file("../resources/models/cube.obj", ENGINE).unwrap();
+        let target = MorphTarget::from_meshes("same", &mesh, &mesh).unwrap();
+
+        let mut short = target.clone();
+        short.normal_deltas.pop();
+        assert!(mesh.add_morph_target(short).is_err());
+        assert!(mesh.morph_targets().is_empty());
+
+        mesh.add_morph_target(target).unwrap();
+        assert_eq!(mesh.morph_targets().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_joint_weights() {
+        let obj = |weights: &str| format!("j root -1 0 0 0\nj tip 0 0 1 0\nv 0 0 0\n{weights}\n");
+        let read = |weights: &str| Mesh::read_obj(obj(weights).as_bytes(), ENGINE);
+
+        assert!(read("vw 0 0.5 1 0.5").unwrap().skin.is_some());
+        assert!(read("vw 2 1.0").is_err()); // Only two joints
+        assert!(read("vw 0 heavy").is_err());
+        assert!(read("vw 0 0.5 1").is_err());
+    }
+
+    #[test]
+    fn test_invalid_joint_parents() {
+        let read = |joints: &str| Mesh::read_obj(format!("{joints}\nv 0 0 0\n").as_bytes(), ENGINE);
+
+        assert!(read("j root -1 0 0 0\nj tip 0 0 1 0").is_ok());
+        assert!(read("j root -1 0 0 0\nj tip 1 0 1 0").is_err()); // Its own index
+        assert!(read("j root -1 0 0 0\nj tip 2 0 1 0").is_err()); // Not listed yet
+        assert!(read("j root -2 0 0 0").is_err());
+        assert!(read("j root O 0 0 0").is_err()); // Typo, not a root
+        assert!(read("j root").is_err());
+    }
 }
@@ -0,0 +1,142 @@
+use parking_lot::RwLock;
+
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::mesh::Mesh;
+use crate::primitives::{FaceData3D, Float3, Tri, VectorOps};
+
+// Per-entity morph target weights, shared with the animation player.
+pub type SharedMorphWeights = Arc<RwLock<Vec<f32>>>;
+
+// Weights below this are skipped when deforming
+const MIN_WEIGHT: f32 = 1e-4;
+
+/// A blend shape: position and normal offsets from the base mesh, running parallel to
+/// `Mesh::data`.
+#[derive(Debug, Clone)]
+pub struct MorphTarget {
+    pub name: String,
+    pub position_deltas: Vec<Tri<Float3>>,
+    pub normal_deltas: Vec<Tri<Float3>>,
+}
+
+impl MorphTarget {
+    /// Offsets between two meshes with the same topology, e.g. a base model and the
+    /// same model with a different facial expression.
+    pub fn from_meshes(name: &str, base: &Mesh, target: &Mesh) -> Result<Self, Box<dyn Error>> {
+        if base.data.len() != target.data.len() {
+            return Err(format!(
+                "Morph target '{name}' has {} faces, but the base mesh has {}",
+                target.data.len(),
+                base.data.len()
+            )
+            .into());
+        }
+
+        let (position_deltas, normal_deltas) = base
+            .data
+            .iter()
+            .zip(&target.data)
+            .map(|(b, t)| (&t.vertices - &b.vertices, &t.normals - &b.normals))
+            .unzip();
+
+        Ok(MorphTarget {
+            name: name.to_string(),
+            position_deltas,
+            normal_deltas,
+        })
+    }
+}
+
+/// The faces with the weighted morph target offsets added. Targets without a weight
+/// are left out.
+pub fn morph_faces(
+    faces: &[FaceData3D],
+    targets: &[MorphTarget],
+    weights: &[f32],
+) -> Vec<FaceData3D> {
+    let active: Vec<_> = targets
+        .iter()
+        .zip(weights)
+        .filter(|(_, w)| w.abs() > MIN_WEIGHT)
+        .collect();
+
+    faces
+        .iter()
+        .enumerate()
+        .map(|(i, face)| {
+            let mut vertices = face.vertices.clone();
+            let mut normals = face.normals.clone();
+            for &(target, &weight) in &active {
+                for c in 0..3 {
+                    vertices[c] += target.position_deltas[i][c] * weight;
+                    normals[c] += target.normal_deltas[i][c] * weight;
+                }
+            }
+
+            FaceData3D {
+                vertices,
+                normals: Tri::new(
+                    normals[0].normalized(),
+                    normals[1].normalized(),
+                    normals[2].normalized(),
+                ),
+                uvs: face.uvs.clone(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::ENGINE;
+
+    fn cube() -> Mesh {
+        Mesh::from_obj_file("../resources/models/cube.obj", ENGINE).unwrap()
+    }
+
+    // Moves every vertex by `offset`
+    fn shifted(mesh: &Mesh, offset: Float3) -> Mesh {
+        let mut out = mesh.clone();
+        for face in &mut out.data {
+            face.vertices = Tri::new(
+                face.vertices[0] + offset,
+                face.vertices[1] + offset,
+                face.vertices[2] + offset,
+            );
+        }
+        out
+    }
+
+    #[test]
+    fn test_target_from_meshes() {
+        let base = cube();
+        let target = MorphTarget::from_meshes("up", &base, &shifted(&base, Float3::Y)).unwrap();
+        assert_eq!(target.position_deltas.len(), base.data.len());
+        assert_eq!(target.position_deltas[3][1], Float3::Y);
+        assert_eq!(target.normal_deltas[3][1], Float3::ZERO);
+
+        let mut smaller = base.clone();
+        smaller.data.pop();
+        assert!(MorphTarget::from_meshes("broken", &base, &smaller).is_err());
+    }
+
+    #[test]
+    fn test_weighted_blend() {
+        let base = cube();
+        let targets = [
+            MorphTarget::from_meshes("up", &base, &shifted(&base, Float3::Y)).unwrap(),
+            MorphTarget::from_meshes("right", &base, &shifted(&base, Float3::X)).unwrap(),
+        ];
+
+        let faces = morph_faces(&base.data, &targets, &[0.5, 2.0]);
+        let expected = base.data[0].vertices[0] + Float3::new(2.0, 0.5, 0.0);
+        assert!(VectorOps::approx_eq(faces[0].vertices[0], expected, 1e-6));
+
+        // Missing or zero weights leave the base mesh untouched
+        let faces = morph_faces(&base.data, &targets, &[0.0]);
+        assert_eq!(faces[0].vertices[0], base.data[0].vertices[0]);
+    }
+}
@@ -0,0 +1,283 @@
+use crate::actions::{ActionMap, AxisBinding, AxisSource, Binding, Button};
+use crate::camera::{CameraModel, look_angles, look_rotation};
+use crate::entity::Entity;
+use crate::input::{Input, Key, MouseKey};
+use crate::pose_graph::{PoseGraph, SharedPGNode};
+use crate::primitives::{Aabb, Float3, Transform, VectorOps};
+
+const ORBIT_SENSITIVITY: f32 = 4.0; // Radians per screen width
+const DOLLY_STEP: f32 = 1.15; // Distance factor per scroll notch
+const MIN_DISTANCE: f32 = 0.05;
+const MAX_PITCH: f32 = f32::to_radians(89.0); // Short of the poles, where yaw flips
+
+/// Moves the camera back along its view direction until `bounds` (world space) fit,
+/// keeping its orientation
+pub fn frame_bounds(cam_pose: &SharedPGNode, cam_model: &CameraModel, bounds: &Aabb) {
+    let mut world = PoseGraph::world_transform(cam_pose);
+    let distance = cam_model.fit_distance(bounds.size().length() / 2.0);
+    world.position = bounds.center() - world.forward_vec() * distance;
+    PoseGraph::set_world_transform(cam_pose, world);
+}
+
+pub fn frame_entity(cam_pose: &SharedPGNode, cam_model: &CameraModel, entity: &Entity) {
+    frame_bounds(cam_pose, cam_model, &entity.world_bounds());
+}
+
+/// Rotates the camera around a target point, pans the target along the view plane and
+/// dollies towards it. Uses the "orbit" and "pan" actions, and the "look_x", "look_y"
+/// and "dolly" axes.
+#[derive(Debug, Default)]
+pub struct OrbitController {
+    pose: SharedPGNode,
+    actions: ActionMap,
+    target: Float3, // World space
+    distance: f32,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl OrbitController {
+    /// Orbits around `target`, starting from the camera's current position
+    pub fn new(pose: SharedPGNode, actions: ActionMap, target: Float3) -> Self {
+        let mut controller = Self {
+            pose,
+            actions,
+            ..Default::default()
+        };
+        controller.set_target(target);
+        controller
+    }
+
+    /// Left drag orbits, middle or shift + left drag pans, the wheel dollies
+    pub fn default_bindings() -> ActionMap {
+        let mut map = ActionMap::default();
+        map.bind_action("orbit", Binding::new(Button::Mouse(MouseKey::Left)))
+            .bind_action("pan", Binding::new(Button::Mouse(MouseKey::Middle)))
+            .bind_action(
+                "pan",
+                Binding::new(Button::Mouse(MouseKey::Left)).with_modifier(Key::LeftShift),
+            )
+            .bind_axis("look_x", AxisBinding::new(AxisSource::MouseX))
+            .bind_axis("look_y", AxisBinding::new(AxisSource::MouseY))
+            .bind_axis("dolly", AxisBinding::new(AxisSource::MouseWheel));
+        map
+    }
+
+    pub fn target(&self) -> Float3 {
+        self.target
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    /// Turns the camera towards `target` without moving it
+    pub fn set_target(&mut self, target: Float3) {
+        let world = PoseGraph::world_transform(&self.pose);
+        let offset = target - world.position;
+        let forward = match offset.length() > MIN_DISTANCE {
+            true => offset,
+            false => world.forward_vec(),
+        };
+
+        let (yaw, pitch) = look_angles(forward);
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        self.target = target;
+        self.distance = offset.length().max(MIN_DISTANCE);
+        self.apply();
+    }
+
+    /// Targets the center of `bounds` (world space) and backs off until they fit the
+    /// view, keeping the viewing angle
+    pub fn frame(&mut self, bounds: &Aabb, cam_model: &CameraModel) {
+        self.target = bounds.center();
+        self.distance = cam_model
+            .fit_distance(bounds.size().length() / 2.0)
+            .max(MIN_DISTANCE);
+        self.apply();
+    }
+
+    pub fn frame_entity(&mut self, entity: &Entity, cam_model: &CameraModel) {
+        self.frame(&entity.world_bounds(), cam_model);
+    }
+
+    pub fn update(&mut self, input: &mut dyn Input, cam_model: &CameraModel) {
+        let actions = &self.actions;
+
+        // Pan is checked first, so it can share a button with orbit behind a modifier
+        if actions.action_held(input, "pan") {
+            // Keeps the target under the cursor
+            let units_per_pixel = cam_model.view_height(self.distance) / input.screen_size().y;
+            let drag = Float3::new(
+                -actions.axis_value(input, "look_x"),
+                actions.axis_value(input, "look_y"),
+                0.0,
+            );
+            self.target += look_rotation(self.yaw, self.pitch) * drag * units_per_pixel;
+        } else if actions.action_held(input, "orbit") {
+            let rad_per_pixel = ORBIT_SENSITIVITY / input.screen_size().x;
+            self.yaw -= actions.axis_value(input, "look_x") * rad_per_pixel;
+            self.pitch = (self.pitch - actions.axis_value(input, "look_y") * rad_per_pixel)
+                .clamp(-MAX_PITCH, MAX_PITCH);
+        }
+
+        // Scrolling up moves in, by the same factor per notch at any distance
+        let dolly = actions.axis_value(input, "dolly");
+        self.distance = (self.distance * DOLLY_STEP.powf(-dolly)).max(MIN_DISTANCE);
+
+        self.apply();
+    }
+
+    fn apply(&self) {
+        let rotation = look_rotation(self.yaw, self.pitch);
+        let world = Transform {
+            position: self.target - rotation * Float3::FORWARD * self.distance,
+            rotation,
+            ..Default::default()
+        };
+        PoseGraph::set_world_transform(&self.pose, world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::ENGINE;
+    use crate::input::{InputFrame, ScriptedInput};
+    use crate::mesh::Mesh;
+    use crate::primitives::{Float2, Quaternion};
+    use crate::shader::NormalShader;
+
+    use std::f32::consts::FRAC_PI_2;
+    use std::sync::Arc;
+
+    const WIDTH: usize = 400;
+    const HEIGHT: usize = 300;
+
+    fn setup() -> (SharedPGNode, SharedPGNode, OrbitController) {
+        let root = PoseGraph::root();
+        let cam = PoseGraph::new("cam", root.clone()).unwrap();
+        cam.write().apply_translation(Float3::new(0.0, 0.0, 5.0));
+        let controller = OrbitController::new(
+            cam.clone(),
+            OrbitController::default_bindings(),
+            Float3::ZERO,
+        );
+        (root, cam, controller)
+    }
+
+    fn input(frame: InputFrame) -> ScriptedInput {
+        let mut input = ScriptedInput::new([frame.with_screen_size(WIDTH as f32, HEIGHT as f32)]);
+        input.next_frame();
+        input
+    }
+
+    fn cam_model() -> CameraModel {
+        CameraModel::new(60.0, true, WIDTH, HEIGHT)
+    }
+
+    fn cam_position(cam: &SharedPGNode) -> Float3 {
+        PoseGraph::world_transform(cam).position
+    }
+
+    #[test]
+    fn test_orbit_keeps_distance() {
+        let (_root, cam, mut controller) = setup();
+        assert!(VectorOps::approx_eq(
+            cam_position(&cam),
+            Float3::Z * 5.0,
+            1e-5
+        ));
+
+        // Dragging left turns the view right, moving the camera a quarter turn around
+        let dx = -FRAC_PI_2 * WIDTH as f32 / ORBIT_SENSITIVITY;
+        let drag = InputFrame::default()
+            .with_mouse_held(MouseKey::Left)
+            .with_mouse(Float2::ZERO, Float2::new(dx, 0.0));
+        controller.update(&mut input(drag), &cam_model());
+
+        let world = PoseGraph::world_transform(&cam);
+        assert!(VectorOps::approx_eq(world.position, Float3::X * 5.0, 1e-4));
+        assert!(VectorOps::approx_eq(world.forward_vec(), -Float3::X, 1e-4));
+    }
+
+    #[test]
+    fn test_pan_and_dolly() {
+        let (_root, cam, mut controller) = setup();
+
+        // Shift + drag moves the target, the camera follows
+        let drag = InputFrame::default()
+            .with_mouse_held(MouseKey::Left)
+            .with_key_held(Key::LeftShift)
+            .with_mouse(Float2::ZERO, Float2::new(-30.0, 0.0));
+        controller.update(&mut input(drag), &cam_model());
+        assert!(controller.target().x > 0.0);
+        assert_eq!(controller.distance(), 5.0);
+        let moved = cam_position(&cam) - Float3::Z * 5.0;
+        assert!(VectorOps::approx_eq(moved, controller.target(), 1e-5));
+
+        let scroll = InputFrame::default().with_mouse_wheel(Float2::new(0.0, 2.0));
+        controller.update(&mut input(scroll), &cam_model());
+        assert!((controller.distance() - 5.0 / DOLLY_STEP / DOLLY_STEP).abs() < 1e-4);
+
+        // Never past the target
+        let scroll = InputFrame::default().with_mouse_wheel(Float2::new(0.0, 100.0));
+        controller.update(&mut input(scroll), &cam_model());
+        assert_eq!(controller.distance(), MIN_DISTANCE);
+    }
+
+    #[test]
+    fn test_parented_camera() {
+        let (root, _, _) = setup();
+        let rig = PoseGraph::new("rig", root.clone()).unwrap();
+        rig.write()
+            .apply_translation(Float3::new(3.0, 1.0, 0.0))
+            .apply_rotation(Quaternion::from_y_angle(1.0));
+        let cam = PoseGraph::new("cam", rig).unwrap();
+
+        let target = Float3::new(0.0, 0.0, -2.0);
+        let controller = OrbitController::new(cam.clone(), ActionMap::default(), target);
+        let world = PoseGraph::world_transform(&cam);
+        let to_target = (target - world.position).normalized();
+        assert!(VectorOps::approx_eq(world.forward_vec(), to_target, 1e-4));
+        assert!(((world.position - target).length() - controller.distance()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_frame_entity() {
+        let (root, cam, mut controller) = setup();
+        let pose = PoseGraph::new("cube", root).unwrap();
+        pose.write()
+            .apply_translation(Float3::new(10.0, 2.0, -4.0))
+            .apply_scale(Float3::ONE * 3.0);
+        let mesh = Mesh::from_obj_file("../resources/models/cube.obj", ENGINE).unwrap();
+        let entity = Entity::new(pose, Arc::new(mesh), Arc::new(NormalShader()));
+
+        let on_screen = |cam: &SharedPGNode| {
+            let model = cam_model();
+            let to_cam = PoseGraph::relative_transform(&entity.pose, cam);
+            entity.mesh.bounds().corners().iter().all(|&c| {
+                let p = model.point_to_screen(to_cam.apply(c));
+                (0.0..=WIDTH as f32).contains(&p.x) && (0.0..=HEIGHT as f32).contains(&p.y)
+            })
+        };
+
+        assert!(!on_screen(&cam));
+        controller.frame_entity(&entity, &cam_model());
+        assert!(on_screen(&cam));
+        assert_eq!(controller.target(), Float3::new(10.0, 2.0, -4.0));
+
+        // The free helper keeps the orientation
+        let other = PoseGraph::new("other", PoseGraph::find_root(&cam)).unwrap();
+        other.write().apply_rotation(Quaternion::from_x_angle(-0.5));
+        frame_entity(&other, &cam_model(), &entity);
+        assert!(on_screen(&other));
+        let forward = PoseGraph::world_transform(&other).forward_vec();
+        assert!(VectorOps::approx_eq(
+            forward,
+            Quaternion::from_x_angle(-0.5) * Float3::FORWARD,
+            1e-5
+        ));
+    }
+}
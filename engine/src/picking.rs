@@ -0,0 +1,129 @@
+use crate::entity::Entity;
+use crate::pose_graph::PoseGraph;
+use crate::primitives::{Float2, Float3, Ray, Tri, VectorOps};
+use crate::scene::SceneData;
+
+/// What a picking ray hit, in world space unless noted
+#[derive(Debug, Clone)]
+pub struct PickHit {
+    pub entity: String,
+    pub face: usize, // Index into the entity's mesh faces
+    pub barycentric: Tri<f32>,
+    pub uv: Float2,
+    pub position: Float3,
+    pub normal: Float3, // Interpolated from the vertex normals
+    pub distance: f32,  // From the ray origin
+}
+
+/// The closest entity under a pixel of the camera's viewport
+pub fn pick(data: &SceneData, pixel: Float2) -> Option<PickHit> {
+    let ray = data.cam_model.screen_to_ray(pixel);
+    let cam_to_world = PoseGraph::world_transform(&data.cam_pose);
+    raycast(data, &ray.transformed(&cam_to_world))
+}
+
+/// The closest entity hit by a world-space ray. Meshes are tested as they are drawn,
+/// with morph targets and skinning applied.
+pub fn raycast(data: &SceneData, ray: &Ray) -> Option<PickHit> {
+    data.entities
+        .iter()
+        .filter_map(|(name, entity)| raycast_entity(name, entity, ray))
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+fn raycast_entity(name: &str, entity: &Entity, ray: &Ray) -> Option<PickHit> {
+    let world = PoseGraph::world_transform(&entity.pose);
+    let faces = entity.deformed_faces(None);
+
+    // Distances along the local ray are the same as along the world ray
+    let hit = ray.transformed_inv(&world).intersect_faces(&faces)?;
+    let face = &faces[hit.face];
+    let weights = &hit.barycentric;
+    let normal = (&face.normals * weights).sum();
+
+    Some(PickHit {
+        entity: name.to_string(),
+        face: hit.face,
+        uv: (&face.uvs * weights).sum(),
+        position: ray.at(hit.t),
+        normal: (world.rotation * (normal / world.scale)).normalized(),
+        distance: hit.t * ray.direction.length(),
+        barycentric: hit.barycentric,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::CameraModel;
+    use crate::coords::ENGINE;
+    use crate::mesh::Mesh;
+    use crate::pose_graph::SharedPGNode;
+    use crate::shader::NormalShader;
+
+    use std::sync::Arc;
+
+    const WIDTH: usize = 320;
+    const HEIGHT: usize = 240;
+
+    // Two unit cubes straight ahead, the smaller one in front, and a camera at the origin
+    fn scene() -> SceneData {
+        let root = PoseGraph::root();
+        let cam = PoseGraph::new("cam", root.clone()).unwrap();
+        let mesh = Arc::new(Mesh::from_obj_file("../resources/models/cube.obj", ENGINE).unwrap());
+        let cube = |name: &str, position: Float3, scale: f32| -> (SharedPGNode, Entity) {
+            let pose = PoseGraph::new(name, root.clone()).unwrap();
+            pose.write()
+                .apply_translation(position)
+                .apply_scale(Float3::ONE * scale);
+            let entity = Entity::new(pose.clone(), mesh.clone(), Arc::new(NormalShader()));
+            (pose, entity)
+        };
+        let (near_pose, near) = cube("near", Float3::new(0.0, 0.0, -5.0), 0.5);
+        let (far_pose, far) = cube("far", Float3::new(0.0, 0.0, -10.0), 2.0);
+
+        SceneData {
+            entities: [("near".to_string(), near), ("far".to_string(), far)].into(),
+            cam_model: CameraModel::new(60.0, true, WIDTH, HEIGHT),
+            cam_pose: cam,
+            nodes: vec![root, near_pose, far_pose],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pick_closest() {
+        let data = scene();
+        let center = Float2::new(WIDTH as f32, HEIGHT as f32) / 2.0;
+        let hit = pick(&data, center).unwrap();
+        assert_eq!(hit.entity, "near");
+        assert!((hit.distance - 4.5).abs() < 1e-4);
+        assert!(VectorOps::approx_eq(
+            hit.position,
+            Float3::new(0.0, 0.0, -4.5),
+            1e-4
+        ));
+        assert!(VectorOps::approx_eq(hit.normal, Float3::Z, 1e-4));
+        assert!((hit.barycentric.sum() - 1.0).abs() < 1e-5);
+
+        // The middle of the cube's +Z face, between its UVs (0.375, 0.75) and (0.625, 1)
+        assert!(VectorOps::approx_eq(hit.uv, Float2::new(0.5, 0.875), 1e-5));
+
+        // Past the small cube's edge, the big one behind it is hit
+        let hit = pick(&data, center + Float2::new(30.0, 0.0)).unwrap();
+        assert_eq!(hit.entity, "far");
+        assert!(pick(&data, Float2::new(1.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn test_hit_matches_projection() {
+        let data = scene();
+        let pixel = Float2::new(170.0, 110.0);
+        let hit = pick(&data, pixel).unwrap();
+
+        // The hit point projects back onto the pixel it was picked from
+        let to_cam = PoseGraph::world_transform(&data.cam_pose).inverse();
+        let back = data.cam_model.point_to_screen(to_cam.apply(hit.position));
+        assert!(VectorOps::approx_eq(back, pixel, 1e-3));
+    }
+}
@@ -1,15 +1,105 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use parking_lot::{Mutex, RwLock};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
 
 use crate::primitives::{Float3, Quaternion, Transform};
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoseGraphError {
+    Cycle { node: String, parent: String }, // `parent` is the node itself or one of its descendants
+    DuplicateName { parent: String, name: String },
+    InvalidName(String), // Empty or containing the path separator
+}
+
+impl fmt::Display for PoseGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PoseGraphError::Cycle { node, parent } => {
+                write!(
+                    f,
+                    "Making '{parent}' the parent of '{node}' would create a cycle"
+                )
+            }
+            PoseGraphError::DuplicateName { parent, name } => {
+                write!(f, "'{parent}' already has a child named '{name}'")
+            }
+            PoseGraphError::InvalidName(name) => write!(f, "Invalid node name '{name}'"),
+        }
+    }
+}
+
+impl std::error::Error for PoseGraphError {}
+
 // We need this for more complex hierarchies of transforms.
-pub type SharedPGNode = Rc<RefCell<PoseGraph>>;
-#[derive(Debug, Clone, Default)]
+// Nodes can be shared across threads. Structural changes (parenting, detaching and
+// renaming) lock several nodes one after the other, so they are serialized by a
+// graph-wide lock to keep the checks and the relinking atomic.
+// A child is never locked while its parent's lock is held for writing, and a parent is
+// never locked while a child's lock is held, so readers and writers can't deadlock.
+pub type SharedPGNode = Arc<RwLock<PoseGraph>>;
+
+pub const PATH_SEPARATOR: char = '/';
+
+static NEXT_NODE_ID: AtomicU64 = AtomicU64::new(0);
+static INVALIDATIONS: AtomicU64 = AtomicU64::new(0);
+static STRUCTURE: Mutex<()> = Mutex::new(());
+
+/// Process-wide unique node identifier, used to look nodes up in a `PoseSnapshot`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(u64);
+
+impl Default for NodeId {
+    fn default() -> Self {
+        NodeId(NEXT_NODE_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+// Children hold their parent strongly through `parent`, while parents only keep weak
+// references to their children to avoid reference cycles. A node stays alive as long as
+// someone holds it or one of its descendants, e.g. `SceneData::nodes`.
+// The world transform is cached. A node is only cached if all its ancestors are, so
+// clearing the caches of a subtree can stop at the first node that isn't.
+// Names are unique among siblings, so every node can be found by its path from the root.
+#[derive(Debug, Default)]
 pub struct PoseGraph {
+    id: NodeId,
     parent: Option<SharedPGNode>,
-    pub transform: Transform,
-    pub name: String,
+    children: Vec<Weak<RwLock<PoseGraph>>>,
+    transform: Transform,
+    world: Arc<WorldCache>,
+    name: String,
+}
+
+// The cached world transform of a node, linked to the caches of its children. Changes
+// clear the caches of a whole subtree through these links, without locking the nodes.
+#[derive(Debug, Default)]
+struct WorldCache {
+    world: Mutex<Option<Transform>>,
+    children: Mutex<Vec<Weak<WorldCache>>>,
+}
+
+impl WorldCache {
+    fn clear(&self) {
+        if self.world.lock().take().is_none() {
+            return; // Already clear, so the subtree is too
+        }
+        let children = self.children.lock().clone();
+        for child in children.iter().filter_map(Weak::upgrade) {
+            child.clear();
+        }
+    }
+
+    // Stores a world transform computed from the graph as it was at `generation`,
+    // unless something was invalidated since.
+    fn store(&self, world: Transform, generation: u64) {
+        let mut cached = self.world.lock();
+        if INVALIDATIONS.load(Ordering::SeqCst) == generation {
+            *cached = Some(world);
+        }
+    }
 }
 
 impl PoseGraph {
@@ -18,71 +108,350 @@ impl PoseGraph {
             name: "root".to_string(),
             ..Default::default()
         };
-        Rc::new(RefCell::new(root))
+        Arc::new(RwLock::new(root))
     }
 
-    pub fn new(name: &str, parent: SharedPGNode) -> SharedPGNode {
-        let mut node = PoseGraph {
+    /// Creates a node under `parent`. Fails if the parent already has a child with
+    /// that name. The parent doesn't keep the node alive: once the returned handle and
+    /// every descendant are dropped, the node silently disappears from the graph.
+    pub fn new(name: &str, parent: SharedPGNode) -> Result<SharedPGNode, PoseGraphError> {
+        validate_name(name)?;
+        let node = Arc::new(RwLock::new(PoseGraph {
             name: name.to_string(),
             ..Default::default()
-        };
-        node.set_parent(parent);
-        Rc::new(RefCell::new(node))
+        }));
+        PoseGraph::set_parent(&node, &parent)?;
+        Ok(node)
+    }
+
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn rename(node: &SharedPGNode, name: &str) -> Result<(), PoseGraphError> {
+        validate_name(name)?;
+        let _structure = STRUCTURE.lock();
+        let parent = node.read().parent();
+        if let Some(parent) = parent {
+            check_name_free(&parent, name, node)?;
+        }
+        node.write().name = name.to_string();
+        Ok(())
+    }
+
+    pub fn child(&self, name: &str) -> Option<SharedPGNode> {
+        self.children().into_iter().find(|c| c.read().name == name)
+    }
+
+    /// Looks up a node by its path, e.g. "root/dagger1/dave". The first component is
+    /// the name of `start` itself. Nodes that nobody holds anymore are not found.
+    pub fn find(start: &SharedPGNode, path: &str) -> Option<SharedPGNode> {
+        let mut parts = path.split(PATH_SEPARATOR);
+        if parts.next()? != start.read().name {
+            return None;
+        }
+
+        let mut current = start.clone();
+        for part in parts {
+            let next = current.read().child(part)?;
+            current = next;
+        }
+        Some(current)
+    }
+
+    /// Path of the node from its root, e.g. "root/dagger1/dave"
+    pub fn path(node: &SharedPGNode) -> String {
+        let mut names = vec![];
+        let mut current = Some(node.clone());
+        while let Some(n) = current {
+            let n = n.read();
+            names.push(n.name.clone());
+            current = n.parent();
+        }
+        names.reverse();
+        names.join(&PATH_SEPARATOR.to_string())
+    }
+
+    /// Renders the subtree below `node` for debugging, one node per line.
+    pub fn tree_string(node: &SharedPGNode) -> String {
+        let mut out = node.read().name.clone();
+        out.push('\n');
+        write_children(node, "", &mut out);
+        out
     }
 
-    // Sets the parent. There is no explicit cycle avoidance here!
-    pub fn set_parent(&mut self, parent: SharedPGNode) -> &mut Self {
-        self.parent = Some(parent);
+    pub fn parent(&self) -> Option<SharedPGNode> {
+        self.parent.clone()
+    }
+
+    /// Children that are still alive. Children that nobody holds anymore have been
+    /// dropped and are skipped.
+    pub fn children(&self) -> Vec<SharedPGNode> {
+        self.children.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    /// Topmost ancestor of `node`, or `node` itself if it has no parent
+    pub fn find_root(node: &SharedPGNode) -> SharedPGNode {
+        let mut root = node.clone();
+        while let Some(parent) = root.clone().read().parent() {
+            root = parent;
+        }
+        root
+    }
+
+    /// Returns true if `ancestor` is `node` itself or one of its ancestors
+    pub fn is_ancestor(ancestor: &SharedPGNode, node: &SharedPGNode) -> bool {
+        let mut current = Some(node.clone());
+        while let Some(n) = current {
+            if Arc::ptr_eq(&n, ancestor) {
+                return true;
+            }
+            current = n.read().parent.clone();
+        }
+        false
+    }
+
+    /// Moves `node` under `parent`, keeping its local transform. Fails if `parent` is
+    /// `node` itself or one of its descendants, or if it has a child with the same name.
+    pub fn set_parent(node: &SharedPGNode, parent: &SharedPGNode) -> Result<(), PoseGraphError> {
+        let _structure = STRUCTURE.lock();
+        if PoseGraph::is_ancestor(node, parent) {
+            return Err(PoseGraphError::Cycle {
+                node: node.read().name.clone(),
+                parent: parent.read().name.clone(),
+            });
+        }
+        let name = node.read().name.clone();
+        check_name_free(parent, &name, node)?;
+
+        detach_locked(node);
+        let node_cache = Arc::downgrade(&node.read().world);
+        let mut parent_mut = parent.write();
+        parent_mut.children.retain(|c| c.strong_count() > 0);
+        parent_mut.children.push(Arc::downgrade(node));
+        let mut cache_children = parent_mut.world.children.lock();
+        cache_children.retain(|c| c.strong_count() > 0);
+        cache_children.push(node_cache);
+        drop(cache_children);
+        drop(parent_mut);
+
+        let mut node_mut = node.write();
+        node_mut.parent = Some(parent.clone());
+        node_mut.invalidate();
+        Ok(())
+    }
+
+    /// Moves `node` under `parent`. With `keep_world`, the local transform is adjusted
+    /// so the node stays in place in world space.
+    pub fn reparent(
+        node: &SharedPGNode,
+        parent: &SharedPGNode,
+        keep_world: bool,
+    ) -> Result<(), PoseGraphError> {
+        let world = PoseGraph::world_transform(node);
+        PoseGraph::set_parent(node, parent)?;
+
+        if keep_world {
+            PoseGraph::set_world_transform(node, world);
+        }
+        Ok(())
+    }
+
+    /// Removes `node` from its parent, making it the root of its own subtree.
+    pub fn detach(node: &SharedPGNode) {
+        let _structure = STRUCTURE.lock();
+        detach_locked(node);
+    }
+
+    // Drops the cached world transform of this node and its whole subtree. Queries
+    // that started before can't store their results anymore.
+    fn invalidate(&self) {
+        INVALIDATIONS.fetch_add(1, Ordering::SeqCst);
+        self.world.clear();
+    }
+
+    /// Local transform, relative to the parent
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) -> &mut Self {
+        self.transform = transform;
+        self.invalidate();
         self
     }
 
     pub fn apply_scale(&mut self, scale: Float3) -> &mut Self {
         self.transform.scale *= scale;
+        self.invalidate();
         self
     }
 
     pub fn apply_rotation(&mut self, rotation: Quaternion) -> &mut Self {
         self.transform.rotation *= rotation;
+        self.invalidate();
         self
     }
 
     pub fn slerp_rotation(&mut self, rotation: Quaternion, t: f32) -> &mut Self {
         self.transform.rotation = self.transform.rotation.slerp(rotation, t);
+        self.invalidate();
         self
     }
 
     pub fn apply_translation(&mut self, translation: Float3) -> &mut Self {
         self.transform.position += translation;
+        self.invalidate();
         self
     }
 
-    /// Compute world transform by traversing up the tree
-    fn to_world(&self) -> Transform {
-        let mut current = self.parent.clone();
-        let mut acc = self.transform;
-        let mut stack = vec![];
-
-        while let Some(node) = current {
-            let borrowed = node.borrow(); // Explicitly clone node contents
-            stack.push(borrowed.clone());
-            current = borrowed.parent.clone();
-        }
+    /// Returns the transform that maps a point in the node's local space to world space.
+    /// Cached, so only the first query after a change walks up the tree.
+    pub fn world_transform(node: &SharedPGNode) -> Transform {
+        let generation = INVALIDATIONS.load(Ordering::SeqCst);
+        let (parent, local, cache) = {
+            let node = node.read();
+            if let Some(world) = *node.world.world.lock() {
+                return world;
+            }
+            (node.parent(), node.transform, node.world.clone())
+        };
 
-        while let Some(node) = stack.pop() {
-            acc = node.transform.compose(&acc);
-        }
+        // Only one node is locked at a time. The parent is cached first, which keeps
+        // every cached node's ancestors cached too.
+        let world = match parent {
+            Some(parent) => PoseGraph::world_transform(&parent).compose(&local),
+            None => local,
+        };
+        cache.store(world, generation);
+        world
+    }
 
-        acc
+    /// Sets the local transform that puts `node` at `world` in world space
+    pub fn set_world_transform(node: &SharedPGNode, world: Transform) {
+        let parent = node.read().parent();
+        let local = match parent {
+            Some(parent) => PoseGraph::world_transform(&parent)
+                .inverse()
+                .compose(&world),
+            None => world,
+        };
+        node.write().set_transform(local);
     }
 
     /// Returns a transform that maps a point in `from`'s local space to `to`'s local space
     pub fn relative_transform(from: &SharedPGNode, to: &SharedPGNode) -> Transform {
-        let from_world = from.borrow().to_world();
-        let to_world_inv = to.borrow().to_world().inverse();
+        let from_world = PoseGraph::world_transform(from);
+        let to_world_inv = PoseGraph::world_transform(to).inverse();
         to_world_inv.compose(&from_world)
     }
 }
 
+// `detach`, for callers already holding the structure lock.
+fn detach_locked(node: &SharedPGNode) {
+    let Some(parent) = node.write().parent.take() else {
+        return;
+    };
+    let node_ptr = Arc::as_ptr(node);
+    let cache_ptr = Arc::as_ptr(&node.read().world);
+    let mut parent_mut = parent.write();
+    parent_mut
+        .children
+        .retain(|c| c.strong_count() > 0 && c.as_ptr() != node_ptr);
+    parent_mut
+        .world
+        .children
+        .lock()
+        .retain(|c| c.strong_count() > 0 && c.as_ptr() != cache_ptr);
+    drop(parent_mut);
+    node.read().invalidate();
+}
+
+fn validate_name(name: &str) -> Result<(), PoseGraphError> {
+    if name.is_empty() || name.contains(PATH_SEPARATOR) {
+        return Err(PoseGraphError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+// Fails if a child of `parent` other than `node` is already called `name`.
+fn check_name_free(
+    parent: &SharedPGNode,
+    name: &str,
+    node: &SharedPGNode,
+) -> Result<(), PoseGraphError> {
+    let parent = parent.read();
+    match parent.child(name) {
+        Some(existing) if !Arc::ptr_eq(&existing, node) => Err(PoseGraphError::DuplicateName {
+            parent: parent.name.clone(),
+            name: name.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn write_children(node: &SharedPGNode, indent: &str, out: &mut String) {
+    let children = node.read().children();
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let (branch, next_indent) = match last {
+            true => ("└── ", "    "),
+            false => ("├── ", "│   "),
+        };
+        out.push_str(&format!("{indent}{branch}{}\n", child.read().name));
+        write_children(child, &format!("{indent}{next_indent}"), out);
+    }
+}
+
+/// World transforms of a whole pose graph, captured at one point in time. Rendering
+/// can read a snapshot while the live graph is already updated for the next frame.
+#[derive(Debug, Clone, Default)]
+pub struct PoseSnapshot {
+    world: HashMap<NodeId, Transform>,
+}
+
+impl PoseSnapshot {
+    /// Captures the tree that `node` belongs to, starting from its root. Each node is
+    /// composed with the world transform its parent had in the snapshot.
+    pub fn capture(node: &SharedPGNode) -> Self {
+        let root = PoseGraph::find_root(node);
+        let mut world = HashMap::new();
+        let mut stack = vec![(root, Transform::default())];
+        while let Some((node, parent_world)) = stack.pop() {
+            let node = node.read();
+            let node_world = parent_world.compose(&node.transform);
+            world.insert(node.id, node_world);
+            stack.extend(node.children().into_iter().map(|c| (c, node_world)));
+        }
+
+        PoseSnapshot { world }
+    }
+
+    pub fn len(&self) -> usize {
+        self.world.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.world.is_empty()
+    }
+
+    /// Returns the transform that maps a point in the node's local space to world space
+    pub fn world_transform(&self, node: NodeId) -> Option<Transform> {
+        self.world.get(&node).copied()
+    }
+
+    /// Returns a transform that maps a point in `from`'s local space to `to`'s local space
+    pub fn relative_transform(&self, from: NodeId, to: NodeId) -> Option<Transform> {
+        let from_world = self.world_transform(from)?;
+        let to_world_inv = self.world_transform(to)?.inverse();
+        Some(to_world_inv.compose(&from_world))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,13 +463,13 @@ mod tests {
     fn test_round_trip() {
         let root = PoseGraph::root();
 
-        let t1 = PoseGraph::new("t1", root.clone());
-        t1.borrow_mut()
+        let t1 = PoseGraph::new("t1", root.clone()).unwrap();
+        t1.write()
             .apply_scale(Float3::new(1.0, 3.0, 0.5))
             .apply_rotation(Quaternion::from_x_angle(FRAC_PI_2));
 
-        let t2 = PoseGraph::new("t2", t1.clone());
-        t2.borrow_mut()
+        let t2 = PoseGraph::new("t2", t1.clone()).unwrap();
+        t2.write()
             .apply_translation(Float3::new(-2.0, 0.0, 3.2))
             .apply_rotation(Quaternion::from_z_angle(-FRAC_PI_2));
 
@@ -117,16 +486,14 @@ mod tests {
         let root = PoseGraph::root();
 
         // The parent is rotated 90 degrees around the Y axis.
-        let parent = PoseGraph::new("parent", root.clone());
+        let parent = PoseGraph::new("parent", root.clone()).unwrap();
         parent
-            .borrow_mut()
+            .write()
             .apply_rotation(Quaternion::from_y_angle(FRAC_PI_2));
 
         // The child is translated by (1, 0, 0) relative to the parent.
-        let child = PoseGraph::new("child", parent.clone());
-        child
-            .borrow_mut()
-            .apply_translation(Float3::new(1.0, 0.0, 0.0));
+        let child = PoseGraph::new("child", parent.clone()).unwrap();
+        child.write().apply_translation(Float3::new(1.0, 0.0, 0.0));
 
         let p_local = Float3::ZERO;
 
@@ -150,15 +517,14 @@ mod tests {
     fn test_mesh_to_cam() {
         let root = PoseGraph::root();
 
-        let mesh = PoseGraph::new("mesh", root.clone());
-        mesh.borrow_mut()
+        let mesh = PoseGraph::new("mesh", root.clone()).unwrap();
+        mesh.write()
             .apply_scale(Float3::new(2.0, 2.0, 2.0))
             .apply_translation(Float3::new(1.0, 0.0, 0.0))
             .apply_rotation(Quaternion::from_y_angle(FRAC_PI_2));
 
-        let cam = PoseGraph::new("cam", root.clone());
-        cam.borrow_mut()
-            .apply_translation(Float3::new(-1.0, 0.0, 0.0));
+        let cam = PoseGraph::new("cam", root.clone()).unwrap();
+        cam.write().apply_translation(Float3::new(-1.0, 0.0, 0.0));
 
         let p_mesh = Float3::new(0.1, 0.0, 0.0);
         let mesh_to_cam = PoseGraph::relative_transform(&mesh, &cam);
@@ -170,4 +536,281 @@ mod tests {
             1e-5
         ))
     }
+
+    #[test]
+    fn test_children_tracking() {
+        let root = PoseGraph::root();
+        let a = PoseGraph::new("a", root.clone()).unwrap();
+        let b = PoseGraph::new("b", root.clone()).unwrap();
+        {
+            let _c = PoseGraph::new("c", root.clone()).unwrap();
+            assert_eq!(root.read().children().len(), 3);
+        }
+
+        // Dropped nodes are no longer reported
+        let children = root.read().children();
+        assert_eq!(children.len(), 2);
+        assert!(Arc::ptr_eq(&children[0], &a) && Arc::ptr_eq(&children[1], &b));
+
+        PoseGraph::detach(&a);
+        assert!(a.read().parent().is_none());
+        assert_eq!(root.read().children().len(), 1);
+    }
+
+    #[test]
+    fn test_cycles_rejected() {
+        let root = PoseGraph::root();
+        let a = PoseGraph::new("a", root.clone()).unwrap();
+        let b = PoseGraph::new("b", a.clone()).unwrap();
+        let c = PoseGraph::new("c", b.clone()).unwrap();
+
+        assert!(PoseGraph::set_parent(&a, &a).is_err());
+        assert_eq!(
+            PoseGraph::set_parent(&a, &c),
+            Err(PoseGraphError::Cycle {
+                node: "a".to_string(),
+                parent: "c".to_string()
+            })
+        );
+
+        // The failed calls must leave the graph untouched
+        assert!(Arc::ptr_eq(&a.read().parent().unwrap(), &root));
+        assert!(PoseGraph::set_parent(&c, &a).is_ok());
+        assert_eq!(a.read().children().len(), 2);
+        assert!(b.read().children().is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_set_parent_no_cycle() {
+        let root = PoseGraph::root();
+        for _ in 0..200 {
+            let a = PoseGraph::new("a", root.clone()).unwrap();
+            let b = PoseGraph::new("b", root.clone()).unwrap();
+
+            // Each move is fine on its own, but together they would form a cycle
+            let (a_under_b, b_under_a) = std::thread::scope(|scope| {
+                let a_under_b = scope.spawn(|| PoseGraph::set_parent(&a, &b).is_ok());
+                let b_under_a = scope.spawn(|| PoseGraph::set_parent(&b, &a).is_ok());
+                (a_under_b.join().unwrap(), b_under_a.join().unwrap())
+            });
+            assert!(a_under_b != b_under_a);
+            assert!(Arc::ptr_eq(&PoseGraph::find_root(&a), &root));
+
+            PoseGraph::detach(&a);
+            PoseGraph::detach(&b);
+        }
+    }
+
+    #[test]
+    fn test_reparent_keep_world() {
+        let root = PoseGraph::root();
+        let a = PoseGraph::new("a", root.clone()).unwrap();
+        a.write()
+            .apply_translation(Float3::new(1.0, 2.0, 3.0))
+            .apply_rotation(Quaternion::from_y_angle(FRAC_PI_2));
+        let b = PoseGraph::new("b", root.clone()).unwrap();
+        b.write()
+            .apply_translation(Float3::new(-4.0, 0.0, 1.0))
+            .apply_rotation(Quaternion::from_x_angle(FRAC_PI_2));
+        let node = PoseGraph::new("node", a.clone()).unwrap();
+        node.write().apply_translation(Float3::new(0.5, 0.0, 0.0));
+
+        let p_local = Float3::new(0.3, -1.0, 2.0);
+        let p_world = PoseGraph::world_transform(&node).apply(p_local);
+
+        PoseGraph::reparent(&node, &b, true).unwrap();
+        let p_moved = PoseGraph::world_transform(&node).apply(p_local);
+        assert!(VectorOps::approx_eq(p_world, p_moved, 1e-5));
+
+        // Without keep_world the local transform is kept instead
+        let local = node.read().transform();
+        PoseGraph::reparent(&node, &root, false).unwrap();
+        let world = PoseGraph::world_transform(&node);
+        assert!(VectorOps::approx_eq(
+            world.apply(p_local),
+            local.apply(p_local),
+            1e-6
+        ));
+    }
+
+    #[test]
+    fn test_cached_world_invalidated() {
+        let root = PoseGraph::root();
+        let a = PoseGraph::new("a", root.clone()).unwrap();
+        let b = PoseGraph::new("b", a.clone()).unwrap();
+        let c = PoseGraph::new("c", b.clone()).unwrap();
+        b.write().apply_translation(Float3::X);
+        c.write().apply_translation(Float3::Y);
+
+        let p_world = PoseGraph::world_transform(&c).apply(Float3::ZERO);
+        assert!(VectorOps::approx_eq(
+            p_world,
+            Float3::new(1.0, 1.0, 0.0),
+            1e-6
+        ));
+        let cached = |n: &SharedPGNode| n.read().world.world.lock().is_some();
+        assert!(cached(&a) && cached(&b) && cached(&c));
+
+        // Changing an ancestor must clear every cached descendant
+        a.write()
+            .apply_rotation(Quaternion::from_z_angle(FRAC_PI_2));
+        assert!(!cached(&a) && !cached(&b) && !cached(&c));
+        assert!(cached(&root));
+        let p_world = PoseGraph::world_transform(&c).apply(Float3::ZERO);
+        assert!(VectorOps::approx_eq(
+            p_world,
+            Float3::new(-1.0, 1.0, 0.0),
+            1e-5
+        ));
+
+        assert!(cached(&c));
+
+        // As must moving a subtree
+        PoseGraph::set_parent(&b, &root).unwrap();
+        assert!(!cached(&b) && !cached(&c));
+        assert!(cached(&a));
+        let p_world = PoseGraph::world_transform(&c).apply(Float3::ZERO);
+        assert!(VectorOps::approx_eq(
+            p_world,
+            Float3::new(1.0, 1.0, 0.0),
+            1e-6
+        ));
+
+        // Changing a node leaves its ancestors cached
+        c.write().apply_translation(Float3::Z);
+        assert!(!cached(&c) && cached(&b));
+    }
+
+    #[test]
+    fn test_parent_changed_while_child_locked() {
+        let root = PoseGraph::root();
+        let parent = PoseGraph::new("parent", root).unwrap();
+        let child = PoseGraph::new("child", parent.clone()).unwrap();
+        PoseGraph::world_transform(&child);
+
+        // Changing a parent doesn't touch the child's lock
+        let mut child_mut = child.write();
+        parent.write().apply_translation(Float3::X);
+        child_mut.apply_translation(Float3::Y);
+        drop(child_mut);
+        let position = PoseGraph::world_transform(&child).position;
+        assert!(VectorOps::approx_eq(
+            position,
+            Float3::new(1.0, 1.0, 0.0),
+            1e-6
+        ));
+    }
+
+    #[test]
+    fn test_concurrent_reads_and_writes() {
+        let root = PoseGraph::root();
+        let parent = PoseGraph::new("parent", root.clone()).unwrap();
+        let child = PoseGraph::new("child", parent.clone()).unwrap();
+
+        // Writers on both ends of the path, and a reader walking it
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for _ in 0..2000 {
+                    parent.write().apply_translation(Float3::X);
+                }
+            });
+            scope.spawn(|| {
+                for _ in 0..2000 {
+                    child.write().apply_translation(Float3::Y);
+                }
+            });
+            scope.spawn(|| {
+                for _ in 0..2000 {
+                    PoseGraph::world_transform(&child);
+                    PoseSnapshot::capture(&child);
+                }
+            });
+        });
+
+        let position = PoseGraph::world_transform(&child).position;
+        assert!(VectorOps::approx_eq(
+            position,
+            Float3::new(2000.0, 2000.0, 0.0),
+            1e-6
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_isolated_from_updates() {
+        let root = PoseGraph::root();
+        let a = PoseGraph::new("a", root.clone()).unwrap();
+        let b = PoseGraph::new("b", a.clone()).unwrap();
+        a.write().apply_translation(Float3::X);
+        b.write().apply_translation(Float3::Y);
+
+        let snapshot = PoseSnapshot::capture(&b);
+        assert_eq!(snapshot.len(), 3);
+
+        // Update the live graph from another thread while the snapshot is held
+        let a_shared = a.clone();
+        std::thread::spawn(move || {
+            a_shared.write().apply_translation(Float3::Z * 5.0);
+        })
+        .join()
+        .unwrap();
+
+        let b_id = b.read().id();
+        let snap_pos = snapshot.world_transform(b_id).unwrap().position;
+        let live_pos = PoseGraph::world_transform(&b).position;
+        assert!(VectorOps::approx_eq(
+            snap_pos,
+            Float3::new(1.0, 1.0, 0.0),
+            1e-6
+        ));
+        assert!(VectorOps::approx_eq(
+            live_pos,
+            Float3::new(1.0, 1.0, 5.0),
+            1e-6
+        ));
+
+        let rel = snapshot.relative_transform(b_id, root.read().id()).unwrap();
+        assert!(VectorOps::approx_eq(rel.position, snap_pos, 1e-6));
+    }
+
+    #[test]
+    fn test_unique_names_and_paths() {
+        let root = PoseGraph::root();
+        let dagger = PoseGraph::new("dagger1", root.clone()).unwrap();
+        let dave = PoseGraph::new("dave", dagger.clone()).unwrap();
+        let other = PoseGraph::new("dagger2", root.clone()).unwrap();
+
+        assert_eq!(
+            PoseGraph::new("dagger1", root.clone()).unwrap_err(),
+            PoseGraphError::DuplicateName {
+                parent: "root".to_string(),
+                name: "dagger1".to_string()
+            }
+        );
+        assert!(PoseGraph::new("a/b", root.clone()).is_err());
+        assert!(PoseGraph::rename(&other, "dagger1").is_err());
+        assert!(PoseGraph::rename(&dagger, "dagger1").is_ok());
+
+        // Same name is fine under a different parent, but not when moved next to it
+        let dave2 = PoseGraph::new("dave", other.clone()).unwrap();
+        assert!(PoseGraph::set_parent(&dave2, &dagger).is_err());
+
+        assert_eq!(PoseGraph::path(&dave), "root/dagger1/dave");
+        let found = PoseGraph::find(&root, "root/dagger1/dave").unwrap();
+        assert!(Arc::ptr_eq(&found, &dave));
+        assert!(PoseGraph::find(&root, "root/dagger2/dave").is_some());
+        assert!(PoseGraph::find(&root, "root/missing").is_none());
+        assert!(PoseGraph::find(&dagger, "root/dagger1").is_none());
+    }
+
+    #[test]
+    fn test_tree_string() {
+        let root = PoseGraph::root();
+        let a = PoseGraph::new("a", root.clone()).unwrap();
+        let _b = PoseGraph::new("b", a.clone()).unwrap();
+        let _c = PoseGraph::new("c", a.clone()).unwrap();
+        let _d = PoseGraph::new("d", root.clone()).unwrap();
+
+        let expected = "root\n├── a\n│   ├── b\n│   └── c\n└── d\n";
+        assert_eq!(PoseGraph::tree_string(&root), expected);
+    }
 }
@@ -0,0 +1,145 @@
+use crate::primitives::{Float3, Ray, Transform, VectorOps};
+
+// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Float3,
+    pub max: Float3,
+}
+
+impl Aabb {
+    // Inverted box, growing it with any point yields that point.
+    pub const EMPTY: Self = Aabb::new(
+        Float3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        Float3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+    );
+
+    pub const fn new(min: Float3, max: Float3) -> Self {
+        Aabb { min, max }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Float3>) -> Self {
+        points.into_iter().fold(Self::EMPTY, Self::grow)
+    }
+
+    pub fn grow(self, p: Float3) -> Self {
+        Aabb::new(self.min.min(p), self.max.max(p))
+    }
+
+    pub fn center(&self) -> Float3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Float3 {
+        self.max - self.min
+    }
+
+    pub fn corners(&self) -> [Float3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Float3::new(a.x, a.y, a.z),
+            Float3::new(b.x, a.y, a.z),
+            Float3::new(b.x, b.y, a.z),
+            Float3::new(a.x, b.y, a.z),
+            Float3::new(a.x, a.y, b.z),
+            Float3::new(b.x, a.y, b.z),
+            Float3::new(b.x, b.y, b.z),
+            Float3::new(a.x, b.y, b.z),
+        ]
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Aabb::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    // Zero for empty boxes
+    pub fn surface_area(&self) -> f32 {
+        let d = self.size().max(Float3::ZERO);
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        (0..3).all(|i| self.min[i] <= other.max[i] && other.min[i] <= self.max[i])
+    }
+
+    // Squared distance from `p` to the box, zero inside
+    pub fn distance_sq(&self, p: Float3) -> f32 {
+        (p.max(self.min).min(self.max) - p).sqr_magnitude()
+    }
+
+    // Slab test. Returns where the ray enters the box, if it does so before `max_t`.
+    // Rays starting inside enter at zero.
+    pub fn intersect_ray(&self, ray: &Ray, max_t: f32) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, max_t);
+        for i in 0..3 {
+            let inv_dir = 1.0 / ray.direction[i];
+            let t0 = (self.min[i] - ray.origin[i]) * inv_dir;
+            let t1 = (self.max[i] - ray.origin[i]) * inv_dir;
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+
+    // Bounds of the transformed box (not tight under rotation)
+    pub fn transformed(&self, t: &Transform) -> Self {
+        Self::from_points(self.corners().map(|c| t.apply(c)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::Quaternion;
+
+    use std::f32::consts::FRAC_PI_2; // 90 degrees in radians
+
+    #[test]
+    fn test_from_points() {
+        let aabb = Aabb::from_points([
+            Float3::new(1.0, -2.0, 0.5),
+            Float3::new(-1.0, 3.0, 0.0),
+            Float3::new(0.0, 0.0, 2.0),
+        ]);
+
+        assert_eq!(aabb.min, Float3::new(-1.0, -2.0, 0.0));
+        assert_eq!(aabb.max, Float3::new(1.0, 3.0, 2.0));
+        assert_eq!(aabb.center(), Float3::new(0.0, 0.5, 1.0));
+    }
+
+    #[test]
+    fn test_ray_and_overlap() {
+        let aabb = Aabb::new(Float3::ZERO, Float3::ONE);
+        let ray = Ray::new(Float3::new(0.5, 0.5, 3.0), Float3::FORWARD);
+        assert_eq!(aabb.intersect_ray(&ray, f32::INFINITY), Some(2.0));
+        assert_eq!(aabb.intersect_ray(&ray, 1.5), None);
+        let inside = Ray::new(Float3::ONE * 0.5, Float3::X);
+        assert_eq!(aabb.intersect_ray(&inside, f32::INFINITY), Some(0.0));
+        let past = Ray::new(Float3::new(2.0, 0.5, 3.0), Float3::FORWARD);
+        assert_eq!(aabb.intersect_ray(&past, f32::INFINITY), None);
+
+        assert!(aabb.overlaps(&Aabb::new(Float3::ONE, Float3::ONE * 2.0)));
+        assert!(!aabb.overlaps(&Aabb::new(Float3::ONE * 1.5, Float3::ONE * 2.0)));
+        assert_eq!(aabb.distance_sq(Float3::new(0.5, 3.0, 2.0)), 5.0);
+        assert_eq!(aabb.distance_sq(Float3::ONE * 0.5), 0.0);
+        assert_eq!(aabb.surface_area(), 6.0);
+        assert_eq!(Aabb::EMPTY.surface_area(), 0.0);
+    }
+
+    #[test]
+    fn test_transformed() {
+        let aabb = Aabb::new(Float3::ZERO, Float3::new(2.0, 1.0, 1.0));
+        let t = Transform {
+            position: Float3::new(0.0, 0.0, 5.0),
+            rotation: Quaternion::from_z_angle(FRAC_PI_2),
+            ..Default::default()
+        };
+
+        let moved = aabb.transformed(&t);
+        assert!((moved.min.x + 1.0).abs() < 1e-5 && (moved.max.y - 2.0).abs() < 1e-5);
+        assert!((moved.min.z - 5.0).abs() < 1e-5 && (moved.max.z - 6.0).abs() < 1e-5);
+    }
+}
@@ -1,6 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 use crate::primitives::VectorOps;
 
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Float2 {
     pub x: f32,
     pub y: f32,
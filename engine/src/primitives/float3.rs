@@ -1,6 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 use crate::primitives::VectorOps;
 
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Float3 {
     pub x: f32,
     pub y: f32,
@@ -140,6 +142,14 @@ impl Float3 {
         (r, g, b, 255)
     }
 
+    pub const fn min(self, rhs: Self) -> Self {
+        Float3::new(self.x.min(rhs.x), self.y.min(rhs.y), self.z.min(rhs.z))
+    }
+
+    pub const fn max(self, rhs: Self) -> Self {
+        Float3::new(self.x.max(rhs.x), self.y.max(rhs.y), self.z.max(rhs.z))
+    }
+
     pub const fn cross(self, rhs: Self) -> Self {
         Float3::new(
             self.y * rhs.z - self.z * rhs.y,
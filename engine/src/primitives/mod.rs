@@ -1,14 +1,18 @@
+pub use aabb::Aabb;
 pub use face::{FaceData2D, FaceData3D};
 pub use float2::Float2;
 pub use float3::Float3;
 pub use quaternion::Quaternion;
+pub use ray::{Ray, RayHit};
 pub use transform::Transform;
 pub use triangle::Tri;
 
+mod aabb;
 mod face;
 mod float2;
 mod float3;
 mod quaternion;
+mod ray;
 mod transform;
 mod triangle;
 
@@ -1,7 +1,9 @@
+use serde::{Deserialize, Serialize};
+
 use crate::primitives::{Float3, VectorOps};
 
 // Rotation quaternions, assumed to always be of unit length.
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Quaternion {
     w: f32,
     i: f32,
@@ -145,6 +147,72 @@ impl Quaternion {
             self.k * w1 + target.k * w2,
         )
     }
+
+    // Same rotation, opposite hemisphere
+    fn negated(self) -> Self {
+        Self::new(-self.w, -self.i, -self.j, -self.k)
+    }
+
+    // Logarithm of a unit quaternion, a pure quaternion (w = 0)
+    fn log(self) -> Self {
+        let v = Float3::from(self);
+        let sin_theta = v.length();
+        if sin_theta < 1e-6 {
+            return Self::new(0.0, 0.0, 0.0, 0.0);
+        }
+        let theta = sin_theta.atan2(self.w);
+        (v * (theta / sin_theta)).into()
+    }
+
+    // Exponential of a pure quaternion, the inverse of `log`
+    fn exp(self) -> Self {
+        let v = Float3::from(self);
+        let theta = v.length();
+        if theta < 1e-6 {
+            return Self::IDENTITY;
+        }
+        let (s, c) = theta.sin_cos();
+        let axis = v * (s / theta);
+        Self::new(c, axis.x, axis.y, axis.z)
+    }
+
+    // Inner control point of `q` for squad, on the side of the segment towards `toward`.
+    // The tangent at `q` is the difference between its neighbours, scaled by `scale`.
+    fn squad_control(away: Self, q: Self, toward: Self, scale: f32) -> Self {
+        let q_inv = q.inverse();
+        let a = Float3::from((q_inv * away).log());
+        let b = Float3::from((q_inv * toward).log());
+        let tangent = (b - a) * scale;
+        (q * Quaternion::from((tangent - b) * 0.5).exp()).normalized()
+    }
+
+    /// Smooth cubic interpolation between `q1` and `q2`, using their neighbours `q0` and
+    /// `q3` to keep the angular velocity continuous across keyframes.
+    pub fn squad(q0: Self, q1: Self, q2: Self, q3: Self, t: f32) -> Self {
+        Self::squad_scaled(q0, q1, q2, q3, (0.5, 0.5), t)
+    }
+
+    /// Same as `squad`, for unevenly spaced keys. `scale` is the length of the segment
+    /// over the span between the neighbours of `q1` and of `q2`, (0.5, 0.5) when even.
+    pub fn squad_scaled(q0: Self, q1: Self, q2: Self, q3: Self, scale: (f32, f32), t: f32) -> Self {
+        // Keep every neighbour in the hemisphere of its predecessor
+        let align = |prev: Self, q: Self| {
+            if prev.pairwise_mul(q) < 0.0 {
+                q.negated()
+            } else {
+                q
+            }
+        };
+        let q0 = align(q1, q0);
+        let q2 = align(q1, q2);
+        let q3 = align(q2, q3);
+
+        let s1 = Self::squad_control(q0, q1, q2, scale.0);
+        let s2 = Self::squad_control(q3, q2, q1, scale.1);
+        let outer = q1.slerp(q2, t);
+        let inner = s1.slerp(s2, t);
+        outer.slerp(inner, 2.0 * t * (1.0 - t))
+    }
 }
 
 #[cfg(test)]
@@ -263,4 +331,30 @@ mod tests {
         let interp = q1.slerp(q2, 0.3);
         assert!((interp.length() - 1.0).abs() < 1e-5);
     }
+
+    #[test]
+    fn test_squad_matches_slerp_for_uniform_rotation() {
+        let q = |deg: f32| Quaternion::from_z_angle(deg.to_radians());
+        let p = Float3::new(1.0, 0.0, 0.0);
+
+        for t in [0.0, 0.25, 0.5, 0.9, 1.0] {
+            let squad = Quaternion::squad(q(0.0), q(30.0), q(60.0), q(90.0), t) * p;
+            let slerp = q(30.0).slerp(q(60.0), t) * p;
+            assert!(VectorOps::approx_eq(squad, slerp, 1e-4));
+        }
+    }
+
+    #[test]
+    fn test_squad_scaled_matches_slerp_for_uniform_rate() {
+        // Keys at times 0, 1, 3 and 4, turning at 30 degrees per unit of time
+        let q = |deg: f32| Quaternion::from_z_angle(deg.to_radians());
+        let p = Float3::new(1.0, 0.0, 0.0);
+
+        for t in [0.0, 0.25, 0.5, 0.9, 1.0] {
+            let scale = (2.0 / 3.0, 2.0 / 3.0);
+            let squad = Quaternion::squad_scaled(q(0.0), q(30.0), q(90.0), q(120.0), scale, t);
+            let slerp = q(30.0).slerp(q(90.0), t);
+            assert!(VectorOps::approx_eq(squad * p, slerp * p, 1e-4));
+        }
+    }
 }
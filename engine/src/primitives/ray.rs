@@ -0,0 +1,148 @@
+use crate::primitives::{FaceData3D, Float3, Transform, Tri, VectorOps};
+
+// Hits closer than this are ignored, so rays leaving a surface don't hit it again
+const RAY_EPSILON: f32 = 1e-5;
+
+// The direction doesn't have to be normalized, distances are in multiples of it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Float3,
+    pub direction: Float3,
+}
+
+#[derive(Debug, Clone)]
+pub struct RayHit {
+    pub face: usize,
+    pub t: f32,                // Along the ray, see `Ray::at`
+    pub barycentric: Tri<f32>, // Weights of the triangle's vertices
+}
+
+impl Ray {
+    pub const fn new(origin: Float3, direction: Float3) -> Self {
+        Ray { origin, direction }
+    }
+
+    pub fn at(&self, t: f32) -> Float3 {
+        self.origin + self.direction * t
+    }
+
+    // The same ray in the space `t` maps to. Distances along it stay the same, since
+    // the direction is not renormalized.
+    pub fn transformed(&self, t: &Transform) -> Self {
+        let origin = t.apply(self.origin);
+        Ray::new(origin, t.apply(self.origin + self.direction) - origin)
+    }
+
+    // Inverse of `transformed`
+    pub fn transformed_inv(&self, t: &Transform) -> Self {
+        let origin = t.apply_inv(self.origin);
+        Ray::new(origin, t.apply_inv(self.origin + self.direction) - origin)
+    }
+
+    // Möller-Trumbore, hitting both sides. Returns the distance and barycentric weights.
+    pub fn intersect_tri(&self, tri: &Tri<Float3>) -> Option<(f32, Tri<f32>)> {
+        let [a, b, c] = tri.vertices;
+        let (e1, e2) = (b - a, c - a);
+        let p = self.direction.cross(e2);
+        let det = e1.dot(p);
+        // Relative to the lengths, so small or far away triangles aren't all parallel
+        let scale = e1.length() * e2.length() * self.direction.length();
+        if det.abs() <= f32::EPSILON * scale {
+            return None; // Parallel to the triangle
+        }
+
+        let inv_det = 1.0 / det;
+        let s = self.origin - a;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(e1);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = e2.dot(q) * inv_det;
+        (t > RAY_EPSILON).then(|| (t, Tri::new(1.0 - u - v, u, v)))
+    }
+
+    // Closest hit by testing every face
+    pub fn intersect_faces(&self, faces: &[FaceData3D]) -> Option<RayHit> {
+        faces
+            .iter()
+            .enumerate()
+            .filter_map(|(face, f)| {
+                let (t, barycentric) = self.intersect_tri(&f.vertices)?;
+                Some(RayHit {
+                    face,
+                    t,
+                    barycentric,
+                })
+            })
+            .min_by(|a, b| a.t.total_cmp(&b.t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::Quaternion;
+
+    fn tri() -> Tri<Float3> {
+        Tri::new(
+            Float3::new(0.0, 0.0, -2.0),
+            Float3::new(1.0, 0.0, -2.0),
+            Float3::new(0.0, 1.0, -2.0),
+        )
+    }
+
+    #[test]
+    fn test_intersect_tri() {
+        let ray = Ray::new(Float3::new(0.25, 0.5, 0.0), Float3::FORWARD);
+        let (t, bary) = ray.intersect_tri(&tri()).unwrap();
+        assert!((t - 2.0).abs() < 1e-6);
+        assert_eq!(bary.vertices, [0.25, 0.25, 0.5]);
+        assert!(VectorOps::approx_eq(
+            ray.at(t),
+            Float3::new(0.25, 0.5, -2.0),
+            1e-6
+        ));
+
+        // Outside the edges, behind the origin, parallel
+        let miss = |origin, direction| Ray::new(origin, direction).intersect_tri(&tri()).is_none();
+        assert!(miss(Float3::new(0.75, 0.5, 0.0), Float3::FORWARD));
+        assert!(miss(Float3::new(0.25, 0.5, 0.0), -Float3::FORWARD));
+        assert!(miss(Float3::ZERO, Float3::X));
+
+        // Tiny triangles are still hit
+        let small = Tri::new(
+            tri().vertices[0] * 1e-4,
+            tri().vertices[1] * 1e-4,
+            tri().vertices[2] * 1e-4,
+        );
+        let ray = Ray::new(Float3::new(0.25e-4, 0.5e-4, 0.0), Float3::FORWARD);
+        assert!(ray.intersect_tri(&small).is_some());
+    }
+
+    #[test]
+    fn test_transformed_keeps_distances() {
+        let t = Transform {
+            position: Float3::new(1.0, 2.0, 3.0),
+            rotation: Quaternion::from_y_angle(0.7),
+            scale: Float3::new(2.0, 1.0, 0.5),
+        };
+        let ray = Ray::new(Float3::new(0.5, -1.0, 2.0), Float3::new(0.3, 0.2, -1.0));
+        let local = ray.transformed_inv(&t);
+        assert!(VectorOps::approx_eq(
+            t.apply(local.at(1.5)),
+            ray.at(1.5),
+            1e-5
+        ));
+        assert!(VectorOps::approx_eq(
+            local.transformed(&t).direction,
+            ray.direction,
+            1e-5
+        ));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::primitives::Float2;
+use crate::primitives::{Aabb, Float2, Float3, VectorOps};
 
 #[derive(Debug, Clone)]
 pub struct Tri<T> {
@@ -62,12 +62,13 @@ impl<T: std::ops::Add<T, Output = T> + Copy> Tri<T> {
 }
 
 impl Tri<Float2> {
-    pub fn bbox<const WIDTH: usize, const HEIGHT: usize>(&self) -> (usize, usize, usize, usize) {
+    // Pixel bounds, clamped to a `width` x `height` screen
+    pub fn bbox(&self, width: usize, height: usize) -> (usize, usize, usize, usize) {
         let [a, b, c] = self.vertices;
-        let min_x = (a.x.min(b.x).min(c.x) as usize).clamp(0, WIDTH - 1);
-        let min_y = (a.y.min(b.y).min(c.y) as usize).clamp(0, HEIGHT - 1);
-        let max_x = (a.x.max(b.x).max(c.x) as usize).clamp(0, WIDTH - 1);
-        let max_y = (a.y.max(b.y).max(c.y) as usize).clamp(0, HEIGHT - 1);
+        let min_x = (a.x.min(b.x).min(c.x) as usize).clamp(0, width - 1);
+        let min_y = (a.y.min(b.y).min(c.y) as usize).clamp(0, height - 1);
+        let max_x = (a.x.max(b.x).max(c.x) as usize).clamp(0, width - 1);
+        let max_y = (a.y.max(b.y).max(c.y) as usize).clamp(0, height - 1);
 
         (min_x, min_y, max_x, max_y)
     }
@@ -100,3 +101,121 @@ impl Tri<Float2> {
         ))
     }
 }
+
+impl Tri<Float3> {
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(self.vertices)
+    }
+
+    // Unnormalized, facing the side the vertices wind counter-clockwise around
+    pub fn normal(&self) -> Float3 {
+        let [a, b, c] = self.vertices;
+        (b - a).cross(c - a)
+    }
+
+    // Closest point on the triangle, by the Voronoi region of `p` (Ericson, 5.1.5)
+    pub fn closest_point(&self, p: Float3) -> Float3 {
+        let [a, b, c] = self.vertices;
+        let (ab, ac) = (b - a, c - a);
+
+        let ap = p - a;
+        let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+        if d1 <= 0.0 && d2 <= 0.0 {
+            return a;
+        }
+
+        let bp = p - b;
+        let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+        if d3 >= 0.0 && d4 <= d3 {
+            return b;
+        }
+
+        let vc = d1 * d4 - d3 * d2;
+        if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            return a + ab * (d1 / (d1 - d3));
+        }
+
+        let cp = p - c;
+        let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+        if d6 >= 0.0 && d5 <= d6 {
+            return c;
+        }
+
+        let vb = d5 * d2 - d1 * d6;
+        if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            return a + ac * (d2 / (d2 - d6));
+        }
+
+        let va = d3 * d6 - d5 * d4;
+        if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+            return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+        }
+
+        // Inside the face
+        let denom = 1.0 / (va + vb + vc);
+        a + ab * (vb * denom) + ac * (vc * denom)
+    }
+
+    // Separating axis test against the box's faces, the triangle's normal and the
+    // cross products of their edges
+    pub fn overlaps_aabb(&self, aabb: &Aabb) -> bool {
+        let center = aabb.center();
+        let half = aabb.size() * 0.5;
+        let v = self.vertices.map(|v| v - center);
+        let edges = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+
+        let separated = |axis: Float3| {
+            let p = v.map(|v| v.dot(axis));
+            let r = half.x * axis.x.abs() + half.y * axis.y.abs() + half.z * axis.z.abs();
+            p[0].min(p[1]).min(p[2]) > r || p[0].max(p[1]).max(p[2]) < -r
+        };
+
+        let box_axes = [Float3::X, Float3::Y, Float3::Z];
+        if box_axes.into_iter().any(separated) || separated(edges[0].cross(edges[1])) {
+            return false;
+        }
+        !box_axes
+            .iter()
+            .flat_map(|axis| edges.map(|edge| axis.cross(edge)))
+            .any(separated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tri() -> Tri<Float3> {
+        Tri::new(Float3::ZERO, Float3::X * 2.0, Float3::Y * 2.0)
+    }
+
+    #[test]
+    fn test_closest_point() {
+        let t = tri();
+        let eq = |a, b| VectorOps::approx_eq(a, b, 1e-6);
+        assert!(eq(
+            t.closest_point(Float3::new(0.5, 0.5, 3.0)),
+            Float3::new(0.5, 0.5, 0.0)
+        ));
+        assert!(eq(
+            t.closest_point(Float3::new(-1.0, -1.0, 0.0)),
+            Float3::ZERO
+        ));
+        assert!(eq(t.closest_point(Float3::new(1.0, -1.0, 1.0)), Float3::X));
+        assert!(eq(
+            t.closest_point(Float3::new(2.0, 2.0, 0.0)),
+            Float3::new(1.0, 1.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn test_overlaps_aabb() {
+        let t = tri();
+        let unit = |min: Float3| Aabb::new(min, min + Float3::ONE);
+        assert!(t.overlaps_aabb(&unit(Float3::new(0.5, 0.5, -0.5))));
+        assert!(!t.overlaps_aabb(&unit(Float3::new(0.5, 0.5, 0.5))));
+        // Inside the bounds, but past the diagonal edge
+        assert!(!t.overlaps_aabb(&unit(Float3::new(1.6, 1.6, -0.5))));
+        assert!(t.bounds().max == Float3::new(2.0, 2.0, 0.0));
+    }
+}
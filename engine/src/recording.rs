@@ -0,0 +1,88 @@
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::input::{InputFrame, ScriptedInput};
+
+// Everything a scene needs to reproduce one frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    pub delta: f32,
+    pub render_size: (usize, usize), // Recorded as well, since dynamic resolution depends on timing
+    pub input: InputFrame,
+}
+
+/// Per-frame input and frame times captured by `Scene::run`, stored as RON
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputRecording {
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl InputRecording {
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(ron::from_str(text)?)
+    }
+
+    pub fn to_ron(&self) -> Result<String, Box<dyn Error>> {
+        // One frame per line keeps long recordings readable
+        let config = PrettyConfig::default().depth_limit(2);
+        Ok(ron::ser::to_string_pretty(self, config)?)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+
+    pub fn push(&mut self, delta: f32, render_size: (usize, usize), input: InputFrame) {
+        self.frames.push(RecordedFrame {
+            delta,
+            render_size,
+            input,
+        });
+    }
+
+    /// Plays back the recorded input, one frame per `ScriptedInput::next_frame`
+    pub fn input(&self) -> ScriptedInput {
+        ScriptedInput::new(self.frames.iter().map(|f| f.input.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{Input, Key, MouseKey};
+    use crate::primitives::Float2;
+
+    #[test]
+    fn test_round_trip() {
+        let mut recording = InputRecording::default();
+        recording.push(
+            0.016,
+            (480, 270),
+            InputFrame::default()
+                .with_key_pressed(Key::F3)
+                .with_mouse_held(MouseKey::Left)
+                .with_mouse(Float2::new(10.5, 20.0), Float2::new(-3.0, 0.25))
+                .with_screen_size(960.0, 540.0),
+        );
+        recording.push(0.017, (480, 270), InputFrame::default());
+
+        let text = recording.to_ron().unwrap();
+        assert_eq!(InputRecording::parse(&text).unwrap(), recording);
+
+        let mut input = recording.input();
+        assert!(input.next_frame());
+        assert!(input.is_key_down_this_frame(Key::F3));
+        assert!(input.next_frame());
+        assert!(!input.is_key_held(Key::F3));
+        assert!(!input.next_frame());
+    }
+}
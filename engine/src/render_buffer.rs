@@ -1,29 +1,65 @@
 use parking_lot::Mutex;
+use png::{BitDepth, ColorType, Encoder};
 
-use crate::primitives::Float3;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
 
+use crate::primitives::{Float2, Float3, VectorOps};
+use crate::render_scale::UpscaleFilter;
+
+// Relative depth tolerance, so lines drawn over their own faces are not occluded.
+const LINE_DEPTH_BIAS: f32 = 1e-3;
+
+// Row-major color and depth buffer
 #[derive(Debug)]
-pub struct RenderBuffer<const WIDTH: usize, const HEIGHT: usize> {
+pub struct RenderBuffer {
+    width: usize,
+    height: usize,
     pub pixels: Vec<Mutex<(Float3, f32)>>,
 }
 
-impl<const WIDTH: usize, const HEIGHT: usize> Default for RenderBuffer<WIDTH, HEIGHT> {
-    fn default() -> Self {
+impl Clone for RenderBuffer {
+    fn clone(&self) -> Self {
+        Self {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(|p| Mutex::new(*p.lock())).collect(),
+        }
+    }
+}
+
+impl RenderBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
         Self {
-            pixels: (0..WIDTH * HEIGHT)
+            width,
+            height,
+            pixels: (0..width * height)
                 .map(|_| Mutex::new((Float3::ZERO, f32::INFINITY)))
                 .collect(),
         }
     }
-}
 
-impl<const WIDTH: usize, const HEIGHT: usize> RenderBuffer<WIDTH, HEIGHT> {
-    pub fn test_frame(t: f32) -> Self {
-        let buffer: RenderBuffer<WIDTH, HEIGHT> = RenderBuffer::default();
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Reallocates the buffer for a new resolution. The contents are cleared.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        *self = Self::new(width, height);
+    }
+
+    pub fn test_frame(width: usize, height: usize, t: f32) -> Self {
+        let buffer = RenderBuffer::new(width, height);
         let color = Float3::new(((t * 50.0) % 255.0) / 255.0, 0.0, 0.0);
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
-                let idx = y * WIDTH + x;
+        for y in 0..height {
+            for x in 0..width {
+                let idx = y * width + x;
                 let mut pixel = buffer.pixels[idx].lock();
                 pixel.0 = color;
             }
@@ -41,12 +77,193 @@ impl<const WIDTH: usize, const HEIGHT: usize> RenderBuffer<WIDTH, HEIGHT> {
         }
     }
 
+    /// Writes the colors to an RGBA PNG, e.g. for comparing renders
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut rgba = vec![0u8; 4 * self.width * self.height];
+        self.to_rgba_buffer(&mut rgba);
+
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&rgba)?;
+        Ok(())
+    }
+
+    /// Per-channel absolute color difference to `other`, which must have the same size,
+    /// e.g. for comparing two renderers. Depths are left cleared.
+    pub fn diff(&self, other: &RenderBuffer) -> Result<RenderBuffer, Box<dyn Error>> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return Err(format!(
+                "Buffer sizes differ: {}x{} and {}x{}",
+                self.width, self.height, other.width, other.height
+            )
+            .into());
+        }
+
+        let diff = RenderBuffer::new(self.width, self.height);
+        for (out, (a, b)) in diff
+            .pixels
+            .iter()
+            .zip(self.pixels.iter().zip(&other.pixels))
+        {
+            let (a, b) = (a.lock().0, b.lock().0);
+            out.lock().0 = (a - b).max(b - a);
+        }
+        Ok(diff)
+    }
+
+    /// Writes the colors resampled to `width` x `height` pixels
+    pub fn to_rgba_buffer_scaled(
+        &self,
+        buffer: &mut [u8],
+        width: usize,
+        height: usize,
+        filter: UpscaleFilter,
+    ) {
+        if (width, height) == (self.width, self.height) {
+            return self.to_rgba_buffer(buffer);
+        }
+
+        let step = Float2::new(
+            self.width as f32 / width as f32,
+            self.height as f32 / height as f32,
+        );
+        for (i, pixel) in buffer.chunks_exact_mut(4).enumerate() {
+            // Center of the output pixel, in buffer pixels
+            let p = Float2::new((i % width) as f32 + 0.5, (i / width) as f32 + 0.5) * step;
+            let color = match filter {
+                UpscaleFilter::Nearest => self.color_at(p.x as usize, p.y as usize),
+                UpscaleFilter::Bilinear => self.bilinear(p - Float2::ONE * 0.5),
+            };
+
+            let (r, g, b, a) = color.to_rgba_bytes();
+            pixel[0] = r;
+            pixel[1] = g;
+            pixel[2] = b;
+            pixel[3] = a;
+        }
+    }
+
+    // Color of the pixel, clamped to the buffer edges
+    fn color_at(&self, x: usize, y: usize) -> Float3 {
+        let (x, y) = (x.min(self.width - 1), y.min(self.height - 1));
+        self.pixels[y * self.width + x].lock().0
+    }
+
+    // Interpolates between the four pixels around `p`, where pixel centers are at
+    // whole coordinates
+    fn bilinear(&self, p: Float2) -> Float3 {
+        let p = Float2::new(p.x.max(0.0), p.y.max(0.0));
+        let (x, y) = (p.x as usize, p.y as usize);
+        let (tx, ty) = (p.x.fract(), p.y.fract());
+
+        let top = self.color_at(x, y).lerp(self.color_at(x + 1, y), tx);
+        let bottom = self
+            .color_at(x, y + 1)
+            .lerp(self.color_at(x + 1, y + 1), tx);
+        top.lerp(bottom, ty)
+    }
+
     pub fn clear(&mut self, bg: Float3) {
         for i in self.pixels.iter_mut() {
             let mut pixel = i.lock();
             *pixel = (bg, f32::INFINITY);
         }
     }
+
+    // Depth-tested write used by line and point primitives.
+    fn plot(&self, x: usize, y: usize, depth: f32, color: Float3) {
+        let mut pixel = self.pixels[y * self.width + x].lock();
+        if depth * (1.0 - LINE_DEPTH_BIAS) < pixel.1 {
+            *pixel = (color, depth.min(pixel.1));
+        }
+    }
+
+    // Liang-Barsky clipping of a segment against the buffer bounds.
+    // Returns the parameter range of the segment that lies on screen.
+    fn clip_line(&self, a: Float2, b: Float2) -> Option<(f32, f32)> {
+        let d = b - a;
+        let max = Float2::new((self.width - 1) as f32, (self.height - 1) as f32);
+        let (mut t0, mut t1) = (0.0f32, 1.0f32);
+
+        for (p, q) in [
+            (-d.x, a.x),
+            (d.x, max.x - a.x),
+            (-d.y, a.y),
+            (d.y, max.y - a.y),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else if p < 0.0 {
+                t0 = t0.max(q / p);
+            } else {
+                t1 = t1.min(q / p);
+            }
+        }
+
+        (t0 <= t1).then_some((t0, t1))
+    }
+
+    // Walks the on-screen part of the segment, calling `f` with each pixel
+    // and its parameter along the full segment.
+    fn for_line_pixels(&self, a: Float2, b: Float2, mut f: impl FnMut(usize, usize, f32)) {
+        let Some((t0, t1)) = self.clip_line(a, b) else {
+            return;
+        };
+
+        let span = (b - a) * (t1 - t0);
+        let steps = span.x.abs().max(span.y.abs()).ceil().max(1.0) as usize;
+
+        for i in 0..=steps {
+            let t = t0 + (t1 - t0) * (i as f32 / steps as f32);
+            let p = a + (b - a) * t;
+            f(p.x.round() as usize, p.y.round() as usize, t);
+        }
+    }
+
+    /// Sets the color of a pixel, leaving its depth untouched. Out of bounds pixels are ignored.
+    pub fn set_pixel(&self, x: usize, y: usize, color: Float3) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x].lock().0 = color;
+        }
+    }
+
+    /// Draws a depth-tested line between two screen-space points.
+    /// Depth is interpolated perspective-correctly, like triangle fragments.
+    pub fn draw_line(&self, a: Float2, b: Float2, depths: (f32, f32), color: Float3) {
+        let (inv_a, inv_b) = (1.0 / depths.0, 1.0 / depths.1);
+        self.for_line_pixels(a, b, |x, y, t| {
+            let depth = 1.0 / (inv_a + (inv_b - inv_a) * t);
+            self.plot(x, y, depth, color);
+        });
+    }
+
+    /// Draws a line on top of everything, ignoring the depth buffer.
+    pub fn draw_overlay_line(&self, a: Float2, b: Float2, color: Float3) {
+        self.for_line_pixels(a, b, |x, y, _| self.set_pixel(x, y, color));
+    }
+
+    /// Draws a depth-tested square point of `size` pixels centered on `p`.
+    pub fn draw_point(&self, p: Float2, depth: f32, size: usize, color: Float3) {
+        let half = (size.max(1) - 1) as f32 / 2.0;
+        let min = (p - Float2::ONE * half).floor();
+        if min.x >= self.width as f32 || min.y >= self.height as f32 {
+            return;
+        }
+
+        let (start_x, start_y) = (min.x.max(0.0) as usize, min.y.max(0.0) as usize);
+        let end_x = ((min.x + size as f32) as usize).min(self.width);
+        let end_y = ((min.y + size as f32) as usize).min(self.height);
+
+        for y in start_y..end_y {
+            for x in start_x..end_x {
+                self.plot(x, y, depth, color);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -55,9 +272,117 @@ mod tests {
 
     #[test]
     fn test_buffer_creation() {
-        let buffer = RenderBuffer::<640, 480>::default();
+        let buffer = RenderBuffer::new(640, 480);
         assert_eq!(buffer.pixels.len(), 640 * 480);
         assert_eq!(buffer.pixels[0].lock().0, Float3::ZERO);
         assert_eq!(buffer.pixels[0].lock().1, f32::INFINITY);
     }
+
+    #[test]
+    fn test_resize() {
+        let mut buffer = RenderBuffer::new(16, 8);
+        buffer.set_pixel(15, 7, Float3::ONE);
+        buffer.resize(4, 20);
+        assert_eq!((buffer.width(), buffer.height()), (4, 20));
+        assert_eq!(buffer.pixels.len(), 80);
+        assert!(buffer.pixels.iter().all(|p| p.lock().0 == Float3::ZERO));
+
+        // Bounds follow the new size
+        buffer.set_pixel(3, 19, Float3::ONE);
+        buffer.set_pixel(15, 7, Float3::ONE);
+        assert_eq!(buffer.pixels[79].lock().0, Float3::ONE);
+        assert_eq!(
+            buffer
+                .pixels
+                .iter()
+                .filter(|p| p.lock().0 == Float3::ONE)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let (a, b) = (RenderBuffer::new(4, 2), RenderBuffer::new(4, 2));
+        a.set_pixel(1, 1, Float3::new(0.25, 1.0, 0.0));
+        b.set_pixel(1, 1, Float3::new(1.0, 0.5, 0.0));
+        let diff = a.diff(&b).unwrap();
+        assert_eq!(diff.pixels[5].lock().0, Float3::new(0.75, 0.5, 0.0));
+        assert_eq!(diff.pixels[0].lock().0, Float3::ZERO);
+        assert!(a.diff(&RenderBuffer::new(2, 4)).is_err());
+    }
+
+    #[test]
+    fn test_upscale() {
+        // Black and white halves, upscaled 2x
+        let buffer = RenderBuffer::new(2, 1);
+        buffer.set_pixel(1, 0, Float3::ONE);
+        let mut out = vec![0u8; 4 * 4 * 2];
+        let red = |out: &[u8]| out.chunks(4).map(|p| p[0]).collect::<Vec<_>>();
+
+        buffer.to_rgba_buffer_scaled(&mut out, 4, 2, UpscaleFilter::Nearest);
+        assert_eq!(red(&out), [0, 0, 255, 255, 0, 0, 255, 255]);
+
+        // Bilinear blends across the edge, the outer pixels clamp to the border
+        buffer.to_rgba_buffer_scaled(&mut out, 4, 2, UpscaleFilter::Bilinear);
+        assert_eq!(red(&out)[..4], [0, 63, 191, 255]);
+    }
+
+    #[test]
+    fn test_draw_line_clipped() {
+        let buffer = RenderBuffer::new(16, 8);
+        let color = Float3::new(1.0, 0.0, 0.0);
+
+        // Horizontal line running well off both sides of the buffer.
+        buffer.draw_line(
+            Float2::new(-50.0, 3.0),
+            Float2::new(80.0, 3.0),
+            (1.0, 1.0),
+            color,
+        );
+
+        for x in 0..16 {
+            assert_eq!(buffer.pixels[3 * 16 + x].lock().0, color);
+        }
+        assert_eq!(buffer.pixels[2 * 16].lock().0, Float3::ZERO);
+    }
+
+    #[test]
+    fn test_draw_line_depth_test() {
+        let buffer = RenderBuffer::new(16, 8);
+        let (near, far) = (Float3::new(1.0, 0.0, 0.0), Float3::new(0.0, 1.0, 0.0));
+
+        buffer.draw_line(
+            Float2::new(0.0, 0.0),
+            Float2::new(15.0, 0.0),
+            (1.0, 1.0),
+            near,
+        );
+        buffer.draw_line(
+            Float2::new(0.0, 0.0),
+            Float2::new(15.0, 0.0),
+            (2.0, 2.0),
+            far,
+        );
+        assert_eq!(buffer.pixels[5].lock().0, near);
+
+        // Perspective-correct depth: the midpoint of a 1..3 line is at depth 1.5
+        buffer.draw_line(
+            Float2::new(0.0, 4.0),
+            Float2::new(10.0, 4.0),
+            (1.0, 3.0),
+            far,
+        );
+        assert!((buffer.pixels[4 * 16 + 5].lock().1 - 1.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_draw_point() {
+        let buffer = RenderBuffer::new(16, 8);
+        let color = Float3::ONE;
+
+        buffer.draw_point(Float2::new(0.0, 0.0), 1.0, 3, color);
+        let drawn = buffer.pixels.iter().filter(|p| p.lock().0 == color).count();
+        assert_eq!(drawn, 4); // Clipped to the bottom-right quarter of the square
+    }
 }
@@ -0,0 +1,171 @@
+pub const MIN_SCALE: f32 = 0.25;
+pub const MAX_SCALE: f32 = 2.0;
+
+// Dynamic scales are rounded to this, so the buffers are not reallocated every frame
+const SCALE_STEP: f32 = 1.0 / 16.0;
+// Frame times within this fraction of the target leave the scale alone
+const DEADBAND: f32 = 0.1;
+// Largest relative scale change per adjustment
+const MAX_ADJUST: f32 = 1.25;
+// Weight of the newest frame in the smoothed frame time
+const SMOOTHING: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UpscaleFilter {
+    #[default]
+    Nearest,
+    Bilinear,
+}
+
+/// Resolution of the render buffer relative to the window. Scales above one render
+/// more pixels than shown, which are then filtered down.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderScale {
+    scale: f32,
+    pub filter: UpscaleFilter,
+    target_frame_time: Option<f32>, // In seconds, adjusts the scale when set
+    avg_frame_time: Option<f32>,
+    suspended: bool, // Keeps a dynamic scale where it is
+}
+
+impl Default for RenderScale {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl RenderScale {
+    pub fn new(scale: f32) -> Self {
+        Self {
+            scale: scale.clamp(MIN_SCALE, MAX_SCALE),
+            filter: UpscaleFilter::default(),
+            target_frame_time: None,
+            avg_frame_time: None,
+            suspended: false,
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Sets a fixed scale, clamped to `MIN_SCALE..=MAX_SCALE`
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.clamp(MIN_SCALE, MAX_SCALE);
+        self.avg_frame_time = None;
+    }
+
+    pub fn target_frame_time(&self) -> Option<f32> {
+        self.target_frame_time
+    }
+
+    /// Enables dynamic resolution, aiming for `seconds` per frame. None keeps the
+    /// current scale fixed.
+    pub fn set_target_frame_time(&mut self, seconds: Option<f32>) {
+        self.target_frame_time = seconds;
+        self.avg_frame_time = None;
+    }
+
+    /// Stops adjusting a dynamic scale while set, e.g. while frames accumulate at one
+    /// resolution. Frame times fed in the meantime are ignored.
+    pub fn set_suspended(&mut self, suspended: bool) {
+        self.suspended = suspended;
+        self.avg_frame_time = None;
+    }
+
+    /// Render buffer size for a window, at least one pixel each way
+    pub fn render_size(&self, width: usize, height: usize) -> (usize, usize) {
+        let scaled = |size: usize| ((size as f32 * self.scale).round() as usize).max(1);
+        (scaled(width), scaled(height))
+    }
+
+    /// Feeds the time it took to produce the last frame. In dynamic mode, the scale
+    /// follows the square root of the frame time ratio, since cost scales with area.
+    pub fn update(&mut self, frame_time: f32) {
+        let Some(target) = self.target_frame_time.filter(|_| !self.suspended) else {
+            return;
+        };
+
+        let avg = match self.avg_frame_time {
+            Some(avg) => avg + (frame_time - avg) * SMOOTHING,
+            None => frame_time,
+        };
+        self.avg_frame_time = Some(avg);
+
+        let ratio = target / avg.max(f32::EPSILON);
+        if (ratio - 1.0).abs() <= DEADBAND {
+            return;
+        }
+
+        let adjust = ratio.sqrt().clamp(1.0 / MAX_ADJUST, MAX_ADJUST);
+        let scale = (self.scale * adjust / SCALE_STEP).round() * SCALE_STEP;
+        let scale = scale.clamp(MIN_SCALE, MAX_SCALE);
+        if scale != self.scale {
+            // Frame times measured at the old scale don't apply anymore
+            self.scale = scale;
+            self.avg_frame_time = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_size() {
+        assert_eq!(RenderScale::new(0.5).render_size(960, 540), (480, 270));
+        assert_eq!(RenderScale::new(2.0).render_size(960, 540), (1920, 1080));
+        assert_eq!(RenderScale::new(0.01).render_size(2, 2), (1, 1));
+        assert_eq!(RenderScale::new(8.0).scale(), MAX_SCALE);
+    }
+
+    #[test]
+    fn test_fixed_scale_ignores_frame_times() {
+        let mut scale = RenderScale::new(1.0);
+        scale.update(1.0);
+        assert_eq!(scale.scale(), 1.0);
+    }
+
+    #[test]
+    fn test_dynamic_scale() {
+        let mut scale = RenderScale::new(1.0);
+        scale.set_target_frame_time(Some(0.016));
+
+        // Too slow: the scale drops until the minimum
+        scale.update(0.064);
+        assert!(scale.scale() < 1.0);
+        for _ in 0..50 {
+            scale.update(0.064);
+        }
+        assert_eq!(scale.scale(), MIN_SCALE);
+
+        // Close enough to the target: no change
+        scale.set_scale(0.5);
+        scale.update(0.017);
+        assert_eq!(scale.scale(), 0.5);
+
+        // Too fast: the scale grows again, in whole steps
+        for _ in 0..10 {
+            scale.update(0.004);
+        }
+        assert!(scale.scale() > 0.5);
+        assert_eq!(scale.scale() % SCALE_STEP, 0.0);
+    }
+
+    #[test]
+    fn test_suspended_scale() {
+        let mut scale = RenderScale::new(1.0);
+        scale.set_target_frame_time(Some(0.016));
+
+        scale.set_suspended(true);
+        for _ in 0..10 {
+            scale.update(1.0);
+        }
+        assert_eq!(scale.scale(), 1.0);
+
+        scale.set_suspended(false);
+        scale.update(1.0);
+        assert!(scale.scale() < 1.0);
+    }
+}
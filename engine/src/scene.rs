@@ -1,54 +1,274 @@
 use raylib::prelude::*;
 
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Instant;
 
 use crate::camera::CameraModel;
+use crate::debug_draw::DebugDraw;
 use crate::entity::Entity;
-use crate::input::Input;
+use crate::input::{Input, RaylibInput, ScriptedInput};
 use crate::pose_graph::SharedPGNode;
 use crate::primitives::Float3;
+use crate::recording::InputRecording;
 use crate::render_buffer::RenderBuffer;
+use crate::render_scale::RenderScale;
 use crate::shader::ShaderGlobals;
 
+const CLEAR_COLOR: Float3 = Float3::new(0.55, 0.55, 0.55);
+
 #[derive(Debug, Default)]
-pub struct SceneData<const WIDTH: usize, const HEIGHT: usize> {
+pub struct SceneData {
     pub entities: HashMap<String, Entity>,
-    pub cam_model: CameraModel<WIDTH, HEIGHT>,
+    pub cam_model: CameraModel,
     pub cam_pose: SharedPGNode,
+    pub sun_pose: Option<SharedPGNode>, // Light direction along its forward vector
+    pub nodes: Vec<SharedPGNode>,       // Keeps nodes alive that no entity refers to
     pub globals: ShaderGlobals,
+    pub debug: DebugDraw,
+}
+
+impl SceneData {
+    // Composites the queued debug shapes over the rendered frame.
+    pub fn draw_debug(&mut self, buffer: &RenderBuffer) {
+        self.debug.flush(buffer, &self.cam_model, &self.cam_pose);
+    }
 }
 
-pub trait Scene<const WIDTH: usize, const HEIGHT: usize> {
-    fn update_state(&mut self, time_delta: f32, input: &mut Input);
+// Texture the render buffer is uploaded to each frame
+fn frame_texture(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    width: usize,
+    height: usize,
+) -> Texture2D {
+    let image = Image::gen_image_color(width as i32, height as i32, Color::BLACK);
+    rl.load_texture_from_image(thread, &image).unwrap()
+}
+
+pub trait Scene {
+    fn update_state(&mut self, time_delta: f32, input: &mut dyn Input);
+
+    fn render(&mut self, buffer: &mut RenderBuffer);
 
-    fn render(&mut self, buffer: &mut RenderBuffer<WIDTH, HEIGHT>);
+    // Called after `render`, e.g. to composite debug overlays.
+    fn post_render(&mut self, _buffer: &mut RenderBuffer) {}
 
-    fn run(&mut self) {
+    // Called with the render buffer size in pixels when the window opens, and whenever
+    // the window is resized or the render scale changes.
+    fn resize(&mut self, width: usize, height: usize);
+
+    // Scenes with a render scale are rendered at a different resolution than the window.
+    fn render_scale(&mut self) -> Option<&mut RenderScale> {
+        None
+    }
+
+    // A full frame: update, render and post-render.
+    fn step(&mut self, time_delta: f32, input: &mut dyn Input, buffer: &mut RenderBuffer) {
+        self.update_state(time_delta, input);
+        self.render(buffer);
+        self.post_render(buffer);
+    }
+
+    fn run(&mut self, width: usize, height: usize) {
+        self.run_with(width, height, RunMode::Interactive);
+    }
+
+    fn run_with(&mut self, width: usize, height: usize, mode: RunMode) {
         let (mut rl, thread) = raylib::init()
-            .size(WIDTH as i32, HEIGHT as i32)
+            .size(width as i32, height as i32)
             .title("Rusterizer")
+            .resizable()
             .build();
 
-        let image = Image::gen_image_color(WIDTH as i32, HEIGHT as i32, Color::BLACK);
-        let mut texture = rl.load_texture_from_image(&thread, &image).unwrap();
-        let mut render_buffer = RenderBuffer::<WIDTH, HEIGHT>::default();
-        let mut frame_buffer = vec![0u8; 4 * WIDTH * HEIGHT];
+        let (mut width, mut height) = (width, height);
+        let mut texture = frame_texture(&mut rl, &thread, width, height);
+        let mut frame_buffer = vec![0u8; 4 * width * height];
+        let mut render_buffer = RenderBuffer::new(0, 0);
+
+        let mut recording = InputRecording::default();
+        let (record_path, mut replay) = match mode {
+            RunMode::Interactive => (None, None),
+            RunMode::Record(path) => (Some(path), None),
+            RunMode::Replay(recording) => (None, Some(recording.frames.into_iter())),
+        };
 
         while !rl.window_should_close() {
-            let delta = rl.get_frame_time();
-            let mut input = Input::new(&mut rl);
-            self.update_state(delta, &mut input);
-            self.render(&mut render_buffer);
+            // Minimized windows report a size of zero, keep the old buffers until restored
+            let size = (rl.get_screen_width(), rl.get_screen_height());
+            if rl.is_window_resized() && size.0 > 0 && size.1 > 0 {
+                (width, height) = (size.0 as usize, size.1 as usize);
+                texture = frame_texture(&mut rl, &thread, width, height);
+                frame_buffer = vec![0u8; 4 * width * height];
+            }
+
+            let replayed = match &mut replay {
+                Some(frames) => match frames.next() {
+                    Some(frame) => Some(frame),
+                    None => break,
+                },
+                None => None,
+            };
+
+            let scale = self.render_scale().map(|s| *s).unwrap_or_default();
+            let render_size = match &replayed {
+                Some(frame) => frame.render_size,
+                None => scale.render_size(width, height),
+            };
+            fit_buffer(self, &mut render_buffer, render_size);
+
+            let start = Instant::now();
+            match replayed {
+                Some(frame) => {
+                    let mut input = ScriptedInput::new([frame.input]);
+                    input.next_frame();
+                    self.step(frame.delta, &mut input, &mut render_buffer);
+                }
+                None => {
+                    let delta = rl.get_frame_time();
+                    let mut input = RaylibInput::new(&mut rl);
+                    if record_path.is_some() {
+                        recording.push(delta, render_size, input.capture());
+                    }
+                    self.step(delta, &mut input, &mut render_buffer);
+
+                    // Only the CPU work counts, presenting may wait for vsync
+                    if let Some(scale) = self.render_scale() {
+                        scale.update(start.elapsed().as_secs_f32());
+                    }
+                }
+            }
 
-            render_buffer.to_rgba_buffer(&mut frame_buffer);
+            render_buffer.to_rgba_buffer_scaled(&mut frame_buffer, width, height, scale.filter);
             texture.update_texture(&frame_buffer).unwrap();
 
             let mut d = rl.begin_drawing(&thread);
-            let rect = Rectangle::new(0.0, 0.0, WIDTH as f32, HEIGHT as f32);
+            let rect = Rectangle::new(0.0, 0.0, width as f32, height as f32);
 
             d.draw_texture_pro(&texture, rect, rect, Vector2::zero(), 0.0, Color::WHITE);
-            d.draw_fps(10, 10);
-            render_buffer.clear(Float3::new(0.55, 0.55, 0.55));
+            render_buffer.clear(CLEAR_COLOR);
+        }
+
+        if let Some(path) = record_path {
+            match recording.save(&path) {
+                Ok(()) => println!(
+                    "Saved {} frames to {}",
+                    recording.frames.len(),
+                    path.display()
+                ),
+                Err(e) => println!("Failed to save the recording: {e}"),
+            }
+        }
+    }
+
+    /// Replays a recording without opening a window, calling `on_frame` with the index
+    /// and render buffer of each frame.
+    fn replay_headless(
+        &mut self,
+        recording: &InputRecording,
+        on_frame: &mut dyn FnMut(usize, &RenderBuffer),
+    ) {
+        let mut buffer = RenderBuffer::new(0, 0);
+        let mut input = recording.input();
+
+        for (i, frame) in recording.frames.iter().enumerate() {
+            input.next_frame();
+            fit_buffer(self, &mut buffer, frame.render_size);
+            self.step(frame.delta, &mut input, &mut buffer);
+            on_frame(i, &buffer);
+            buffer.clear(CLEAR_COLOR);
         }
     }
 }
+
+#[derive(Debug, Clone, Default)]
+pub enum RunMode {
+    #[default]
+    Interactive,
+    Record(PathBuf), // Saved when the window is closed
+    Replay(InputRecording),
+}
+
+// Reallocates the render buffer if the size changed, and tells the scene
+fn fit_buffer<S: Scene + ?Sized>(scene: &mut S, buffer: &mut RenderBuffer, size: (usize, usize)) {
+    if size != (buffer.width(), buffer.height()) {
+        buffer.resize(size.0, size.1);
+        buffer.clear(CLEAR_COLOR);
+        scene.resize(size.0, size.1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{InputFrame, Key};
+    use crate::primitives::Float2;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_scene_data_is_send_sync() {
+        assert_send_sync::<SharedPGNode>();
+        assert_send_sync::<Entity>();
+        assert_send_sync::<SceneData>();
+    }
+
+    // Moves a dot with the arrow keys, at a speed that depends on the frame time
+    #[derive(Default)]
+    struct DotScene {
+        pos: Float2,
+        size: (usize, usize),
+    }
+
+    impl Scene for DotScene {
+        fn update_state(&mut self, time_delta: f32, input: &mut dyn Input) {
+            if input.is_key_held(Key::Right) {
+                self.pos.x += 100.0 * time_delta;
+            }
+            if input.is_key_down_this_frame(Key::Down) {
+                self.pos.y += 1.0;
+            }
+        }
+
+        fn render(&mut self, buffer: &mut RenderBuffer) {
+            buffer.set_pixel(self.pos.x as usize, self.pos.y as usize, Float3::ONE);
+        }
+
+        fn resize(&mut self, width: usize, height: usize) {
+            self.size = (width, height);
+        }
+    }
+
+    fn replay_frames(recording: &InputRecording) -> Vec<Vec<u8>> {
+        let mut frames = vec![];
+        DotScene::default().replay_headless(recording, &mut |_, buffer| {
+            let mut rgba = vec![0; 4 * buffer.pixels.len()];
+            buffer.to_rgba_buffer(&mut rgba);
+            frames.push(rgba);
+        });
+        frames
+    }
+
+    #[test]
+    fn test_headless_replay() {
+        let mut recording = InputRecording::default();
+        let right = InputFrame::default().with_key_held(Key::Right);
+        recording.push(0.05, (16, 8), right.clone());
+        recording.push(0.1, (16, 8), right.with_key_pressed(Key::Down));
+        recording.push(0.02, (8, 8), InputFrame::default());
+
+        let mut scene = DotScene::default();
+        let mut sizes = vec![];
+        scene.replay_headless(&recording, &mut |i, buffer| {
+            sizes.push((i, buffer.width(), buffer.height()));
+        });
+        assert_eq!(scene.pos, Float2::new(15.0, 1.0));
+        assert_eq!(scene.size, (8, 8));
+        assert_eq!(sizes, [(0, 16, 8), (1, 16, 8), (2, 8, 8)]);
+
+        // Replays render the same frames every time
+        let frames = replay_frames(&recording);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames, replay_frames(&recording));
+    }
+}
@@ -0,0 +1,482 @@
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use crate::camera::CameraModel;
+use crate::coords::{CoordinateSystem, ENGINE};
+use crate::entity::{Entity, PolygonMode};
+use crate::material::Material;
+use crate::mesh::{Mesh, MeshSource};
+use crate::pose_graph::{PATH_SEPARATOR, PoseGraph, SharedPGNode};
+use crate::primitives::{Float3, Quaternion, Transform};
+use crate::scene::SceneData;
+use crate::shader::ShaderDesc;
+use crate::skinning::Skeleton;
+
+// Scene files describe the pose graph, entities, camera and sun of a scene in RON.
+// Mesh and texture paths are relative to the scene file's directory, and are resolved
+// when loading the file, or relative to the working directory for parsed text.
+// Skeleton joints are recreated from the mesh skins, in their bind pose. Morph targets
+// and animations are set up in code.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeDesc {
+    pub path: String, // e.g. "root/dagger1", where "root/" has to be declared first
+    #[serde(default)]
+    pub position: Float3,
+    #[serde(default = "identity")]
+    pub rotation: Quaternion,
+    #[serde(default = "unit_scale")]
+    pub scale: Float3,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeshDesc {
+    pub path: PathBuf,
+    #[serde(default = "engine_coords")]
+    pub coords: CoordinateSystem,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntityDesc {
+    pub name: String,
+    pub node: String,
+    pub mesh: MeshDesc,
+    pub shader: ShaderDesc,
+    #[serde(default)]
+    pub material: Option<Material>, // The default material if missing
+    #[serde(default)]
+    pub polygon_mode: Option<PolygonMode>,
+    #[serde(default)]
+    pub skinned: bool, // Creates the joints of the mesh's skin below `node`
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraDesc {
+    pub node: String,
+    #[serde(default = "default_fov")]
+    pub fov_deg: f32, // Perspective cameras only
+    pub perspective: bool,
+    #[serde(default = "default_view_height")]
+    pub scale: f32, // Orthographic cameras only, the height of the view in world units
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneDesc {
+    pub nodes: Vec<NodeDesc>, // Parents before their children
+    pub entities: Vec<EntityDesc>,
+    pub camera: CameraDesc,
+    #[serde(default)]
+    pub sun: Option<String>,
+}
+
+fn identity() -> Quaternion {
+    Quaternion::IDENTITY
+}
+
+fn unit_scale() -> Float3 {
+    Float3::ONE
+}
+
+fn engine_coords() -> CoordinateSystem {
+    ENGINE
+}
+
+fn default_fov() -> f32 {
+    60.0
+}
+
+fn default_view_height() -> f32 {
+    10.0
+}
+
+// `path` relative to `dir`, where both are relative to the working directory. Paths
+// that can't be expressed that way, e.g. when `dir` starts above the common part, are
+// made absolute instead.
+fn relative_to(path: &Path, dir: &Path) -> std::io::Result<PathBuf> {
+    if path.is_absolute() != dir.is_absolute() {
+        return std::path::absolute(path);
+    }
+    let common = path
+        .components()
+        .zip(dir.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let ups = dir.components().skip(common);
+    if ups.clone().any(|c| c == Component::ParentDir) {
+        return std::path::absolute(path);
+    }
+    Ok(ups
+        .map(|_| Component::ParentDir)
+        .chain(path.components().skip(common))
+        .collect())
+}
+
+impl SceneDesc {
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(ron::from_str(text)?)
+    }
+
+    pub fn to_ron(&self) -> Result<String, Box<dyn Error>> {
+        Ok(ron::ser::to_string_pretty(self, PrettyConfig::default())?)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let mut desc = Self::parse(&fs::read_to_string(&path)?)?;
+        let dir = path.as_ref().parent().unwrap_or(Path::new(""));
+        for file in desc.file_paths_mut() {
+            *file = dir.join(&*file);
+        }
+        Ok(desc)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut desc = self.clone();
+        let dir = path.as_ref().parent().unwrap_or(Path::new(""));
+        for file in desc.file_paths_mut() {
+            *file = relative_to(file, dir)?;
+        }
+        fs::write(path, desc.to_ron()?)?;
+        Ok(())
+    }
+
+    // The mesh and texture files
+    fn file_paths_mut(&mut self) -> impl Iterator<Item = &mut PathBuf> {
+        self.entities.iter_mut().flat_map(|e| {
+            let texture = match &mut e.shader {
+                ShaderDesc::Texture { texture } | ShaderDesc::LitTexture { texture } => {
+                    Some(texture)
+                }
+                ShaderDesc::Depth | ShaderDesc::Normal => None,
+            };
+            [Some(&mut e.mesh.path), texture].into_iter().flatten()
+        })
+    }
+
+    /// Describes a scene built from files. Fails for meshes or shaders created in code.
+    pub fn from_scene(data: &SceneData) -> Result<Self, Box<dyn Error>> {
+        // Joints are recreated from the mesh skins when loading
+        let joints: HashSet<_> = data
+            .entities
+            .values()
+            .flat_map(|e| e.skeleton.iter().flat_map(|s| s.joints.iter()))
+            .map(|j| j.read().id())
+            .collect();
+
+        let mut nodes = vec![];
+        let mut stack = vec![PoseGraph::find_root(&data.cam_pose)];
+        while let Some(node) = stack.pop() {
+            let path = PoseGraph::path(&node);
+            let node = node.read();
+            if joints.contains(&node.id()) {
+                continue;
+            }
+
+            let transform = node.transform();
+            nodes.push(NodeDesc {
+                path,
+                position: transform.position,
+                rotation: transform.rotation,
+                scale: transform.scale,
+            });
+            stack.extend(node.children().into_iter().rev());
+        }
+
+        let mut names: Vec<_> = data.entities.keys().collect();
+        names.sort();
+        let entities = names
+            .into_iter()
+            .map(|name| {
+                let entity = &data.entities[name];
+                let source = entity.mesh.source.as_ref().ok_or_else(|| {
+                    format!("The mesh of entity '{name}' was not loaded from a file")
+                })?;
+                let shader = entity.shader.desc().ok_or_else(|| {
+                    format!("The shader of entity '{name}' can't be stored in a scene file")
+                })?;
+
+                Ok(EntityDesc {
+                    name: name.clone(),
+                    node: PoseGraph::path(&entity.pose),
+                    mesh: MeshDesc {
+                        path: source.path.clone(),
+                        coords: source.coords,
+                    },
+                    shader,
+                    material: (entity.material != Material::default()).then_some(entity.material),
+                    polygon_mode: entity.polygon_mode,
+                    skinned: entity.skeleton.is_some(),
+                })
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+
+        Ok(SceneDesc {
+            nodes,
+            entities,
+            camera: CameraDesc {
+                node: PoseGraph::path(&data.cam_pose),
+                fov_deg: match data.cam_model.perspective {
+                    true => data.cam_model.fov_deg(),
+                    false => default_fov(),
+                },
+                perspective: data.cam_model.perspective,
+                scale: match data.cam_model.perspective {
+                    true => default_view_height(),
+                    false => data.cam_model.view_height(1.0),
+                },
+            },
+            sun: data.sun_pose.as_ref().map(PoseGraph::path),
+        })
+    }
+
+    /// Loads the meshes and textures and assembles the scene, with the camera projecting
+    /// to `width` x `height` pixels
+    pub fn build(&self, width: usize, height: usize) -> Result<SceneData, Box<dyn Error>> {
+        let nodes = self.build_pose_graph()?;
+        let root = nodes[0].clone();
+        let find = |path: &str| {
+            PoseGraph::find(&root, path).ok_or_else(|| format!("Unknown node '{path}'"))
+        };
+
+        let camera = &self.camera;
+        let mut data = SceneData {
+            cam_model: match camera.perspective {
+                true => CameraModel::new(camera.fov_deg, true, width, height),
+                false => CameraModel::orthographic(camera.scale, width, height),
+            },
+            cam_pose: find(&self.camera.node)?,
+            sun_pose: self.sun.as_deref().map(find).transpose()?,
+            nodes,
+            ..Default::default()
+        };
+
+        // Entities using the same file share the mesh
+        let mut meshes: HashMap<MeshSource, Arc<Mesh>> = HashMap::new();
+        for desc in &self.entities {
+            let source = MeshSource {
+                path: desc.mesh.path.clone(),
+                coords: desc.mesh.coords,
+            };
+            let mesh = match meshes.get(&source) {
+                Some(mesh) => mesh.clone(),
+                None => {
+                    let mesh = Arc::new(Mesh::from_obj_file(&source.path, source.coords)?);
+                    meshes.insert(source, mesh.clone());
+                    mesh
+                }
+            };
+
+            let pose = find(&desc.node)?;
+            let mut entity = Entity::new(pose.clone(), mesh.clone(), desc.shader.build()?);
+            entity.material = desc.material.unwrap_or_default();
+            entity.polygon_mode = desc.polygon_mode;
+
+            if desc.skinned {
+                let skin = mesh.skin.as_ref().ok_or_else(|| {
+                    format!(
+                        "Entity '{}' is skinned, but its mesh has no skin",
+                        desc.name
+                    )
+                })?;
+                entity =
+                    entity.with_skeleton(Arc::new(Skeleton::instantiate(&skin.joints, &pose)?));
+            }
+
+            if data.entities.insert(desc.name.clone(), entity).is_some() {
+                return Err(format!("Duplicate entity '{}'", desc.name).into());
+            }
+        }
+
+        Ok(data)
+    }
+
+    // All declared nodes, starting with the root. Parents only hold weak references to
+    // their children, so the scene has to keep them.
+    fn build_pose_graph(&self) -> Result<Vec<SharedPGNode>, Box<dyn Error>> {
+        let mut nodes: Vec<SharedPGNode> = vec![];
+
+        for desc in &self.nodes {
+            let node = match (nodes.first(), desc.path.rsplit_once(PATH_SEPARATOR)) {
+                (None, None) => {
+                    let node = PoseGraph::root();
+                    PoseGraph::rename(&node, &desc.path)?;
+                    node
+                }
+                (Some(root), Some((parent, name))) => {
+                    let parent = PoseGraph::find(root, parent).ok_or_else(|| {
+                        format!("Node '{}' is declared before its parent", desc.path)
+                    })?;
+                    PoseGraph::new(name, parent)?
+                }
+                (Some(_), None) => return Err(format!("Second root node '{}'", desc.path).into()),
+                (None, Some(_)) => return Err("The first node has to be the root".into()),
+            };
+
+            node.write().set_transform(Transform {
+                position: desc.position,
+                rotation: desc.rotation.normalized(),
+                scale: desc.scale,
+            });
+            nodes.push(node);
+        }
+
+        match nodes.is_empty() {
+            true => Err("The scene has no nodes".into()),
+            false => Ok(nodes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::VectorOps;
+
+    const SCENE: &str = r#"(
+        nodes: [
+            (path: "world"),
+            (path: "world/box", position: (x: 1.0, y: 2.0, z: -5.0)),
+            (path: "world/box/spinner", rotation: (w: 0.0, i: 0.0, j: 1.0, k: 0.0)),
+            (path: "world/cam"),
+            (path: "world/sun", scale: (x: 2.0, y: 2.0, z: 2.0)),
+        ],
+        entities: [
+            (
+                name: "box",
+                node: "world/box",
+                mesh: (path: "../resources/models/cube.obj"),
+                shader: Normal,
+                polygon_mode: Some(Wireframe),
+            ),
+            (
+                name: "box2",
+                node: "world/box/spinner",
+                mesh: (path: "../resources/models/cube.obj"),
+                shader: Depth,
+                material: Some((emission: (x: 2.0, y: 2.0, z: 2.0))),
+            ),
+            (
+                name: "tube",
+                node: "world",
+                mesh: (path: "../resources/models/bent_cylinder.obj", coords: RightHandedYUp),
+                shader: Normal,
+                skinned: true,
+            ),
+        ],
+        camera: (node: "world/cam", fov_deg: 60.0, perspective: true),
+        sun: Some("world/sun"),
+    )"#;
+
+    #[test]
+    fn test_build_scene() {
+        let data = SceneDesc::parse(SCENE).unwrap().build(8, 8).unwrap();
+
+        assert_eq!(data.entities.len(), 3);
+        assert!(Arc::ptr_eq(
+            &data.entities["box"].mesh,
+            &data.entities["box2"].mesh
+        ));
+        assert_eq!(
+            data.entities["box"].polygon_mode,
+            Some(PolygonMode::Wireframe)
+        );
+        assert!(data.entities["tube"].skeleton.is_some());
+        assert!(data.entities["box2"].material.is_emissive());
+        assert_eq!(data.entities["box"].material, Material::default());
+        assert!((data.cam_model.fov_deg() - 60.0).abs() < 1e-4);
+        assert_eq!(
+            PoseGraph::path(data.sun_pose.as_ref().unwrap()),
+            "world/sun"
+        );
+
+        let spinner = PoseGraph::world_transform(&data.entities["box2"].pose);
+        assert!(VectorOps::approx_eq(
+            spinner.position,
+            Float3::new(1.0, 2.0, -5.0),
+            1e-6
+        ));
+        let forward = spinner.forward_vec();
+        assert!(VectorOps::approx_eq(
+            forward,
+            Float3::new(0.0, 0.0, 1.0),
+            1e-5
+        ));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let desc = SceneDesc::parse(SCENE).unwrap();
+        let data = desc.build(8, 8).unwrap();
+        let saved = SceneDesc::from_scene(&data).unwrap();
+
+        // The joints of the skinned tube are not saved, they come from its mesh
+        assert_eq!(saved.nodes.len(), desc.nodes.len());
+        assert!(saved.nodes.iter().all(|n| !n.path.contains("lower")));
+
+        let reloaded = SceneDesc::parse(&saved.to_ron().unwrap()).unwrap();
+        assert_eq!(reloaded, saved);
+        assert_eq!(reloaded.nodes, desc.nodes);
+        assert_eq!(reloaded.entities, desc.entities);
+    }
+
+    #[test]
+    fn test_invalid_scenes() {
+        let mut desc = SceneDesc::parse(SCENE).unwrap();
+        desc.camera.node = "world/missing".to_string();
+        assert!(desc.build(8, 8).is_err());
+
+        let mut desc = SceneDesc::parse(SCENE).unwrap();
+        desc.nodes.swap(1, 2);
+        assert!(desc.build(8, 8).is_err());
+
+        assert!(SceneDesc::parse("(nodes: [])").is_err());
+    }
+
+    #[test]
+    fn test_parse_test_scene() {
+        let desc = SceneDesc::from_file("../resources/scenes/test_scene.ron").unwrap();
+        assert_eq!(desc.nodes[0].path, "root");
+        assert!(desc.entities.iter().any(|e| e.skinned));
+        assert_eq!(desc.sun.as_deref(), Some("root/sun"));
+
+        // Paths are relative to the scene file, wherever it is loaded from
+        assert!(desc.build(8, 8).is_ok());
+    }
+
+    #[test]
+    fn test_relative_paths() {
+        let rel = |path: &str, dir: &str| relative_to(Path::new(path), Path::new(dir)).unwrap();
+        assert_eq!(
+            rel("a/scenes/../models/m.obj", "a/scenes"),
+            Path::new("../models/m.obj")
+        );
+        assert_eq!(
+            rel("a/models/m.obj", "a/scenes"),
+            Path::new("../models/m.obj")
+        );
+        assert_eq!(rel("m.obj", ""), Path::new("m.obj"));
+        assert_eq!(rel("/abs/m.obj", "a"), Path::new("/abs/m.obj"));
+
+        // Not expressible relative to `dir`, but still pointing at the same file
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(rel("m.obj", "../a"), cwd.join("m.obj"));
+        assert_eq!(rel("m.obj", "/a"), cwd.join("m.obj"));
+    }
+
+    #[test]
+    fn test_orthographic_camera() {
+        let mut desc = SceneDesc::parse(SCENE).unwrap();
+        desc.camera.perspective = false;
+        desc.camera.scale = 4.0;
+        let data = desc.build(8, 8).unwrap();
+        assert_eq!(data.cam_model.view_height(1.0), 4.0);
+
+        let saved = SceneDesc::from_scene(&data).unwrap();
+        assert_eq!(saved.camera, desc.camera);
+    }
+}
@@ -1,4 +1,4 @@
-use super::{PixelShader, ShaderGlobals};
+use super::{PixelShader, ShaderDesc, ShaderGlobals};
 use crate::primitives::{Float2, Float3};
 
 #[derive(Debug)]
@@ -15,4 +15,8 @@ impl PixelShader for DepthShader {
     ) -> Float3 {
         Float3::ONE * (depth / 5.0)
     }
+
+    fn desc(&self) -> Option<ShaderDesc> {
+        Some(ShaderDesc::Depth)
+    }
 }
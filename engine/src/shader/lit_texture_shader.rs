@@ -1,4 +1,4 @@
-use super::{PixelShader, ShaderGlobals};
+use super::{PixelShader, ShaderDesc, ShaderGlobals};
 use crate::primitives::{Float2, Float3, VectorOps};
 use crate::texture::Texture;
 
@@ -20,6 +20,11 @@ impl PixelShader for LitTextureShader {
         let scaled_intensity = 0.4 + 0.6 * intensity.clamp(0.0, 1.0);
         self.texture.sample(uv) * scaled_intensity
     }
+
+    fn desc(&self) -> Option<ShaderDesc> {
+        let texture = self.texture.source.clone()?;
+        Some(ShaderDesc::LitTexture { texture })
+    }
 }
 
 impl LitTextureShader {
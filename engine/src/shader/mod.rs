@@ -8,7 +8,14 @@ mod lit_texture_shader;
 mod normal_shader;
 mod texture_shader;
 
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+
 use crate::primitives::{Float2, Float3};
+use crate::texture::Texture;
 
 // Global scene information which can be used by the shader.
 #[derive(Debug, Default, Clone)]
@@ -26,4 +33,33 @@ pub trait PixelShader: std::fmt::Debug + Sync + Send {
         depth: f32,
         globals: &ShaderGlobals,
     ) -> Float3;
+
+    // Description for scene files, None if the shader can't be stored in one.
+    fn desc(&self) -> Option<ShaderDesc> {
+        None
+    }
+}
+
+// Shader choice and parameters as stored in scene files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ShaderDesc {
+    Depth,
+    Normal,
+    Texture { texture: PathBuf },
+    LitTexture { texture: PathBuf },
+}
+
+impl ShaderDesc {
+    pub fn build(&self) -> Result<Arc<dyn PixelShader>, Box<dyn Error>> {
+        Ok(match self {
+            ShaderDesc::Depth => Arc::new(DepthShader()),
+            ShaderDesc::Normal => Arc::new(NormalShader()),
+            ShaderDesc::Texture { texture } => {
+                Arc::new(TextureShader::new(Texture::from_file(texture)?))
+            }
+            ShaderDesc::LitTexture { texture } => {
+                Arc::new(LitTextureShader::new(Texture::from_file(texture)?))
+            }
+        })
+    }
 }
@@ -1,4 +1,4 @@
-use super::{PixelShader, ShaderGlobals};
+use super::{PixelShader, ShaderDesc, ShaderGlobals};
 use crate::primitives::{Float2, Float3};
 
 #[derive(Debug)]
@@ -15,4 +15,8 @@ impl PixelShader for NormalShader {
     ) -> Float3 {
         normal
     }
+
+    fn desc(&self) -> Option<ShaderDesc> {
+        Some(ShaderDesc::Normal)
+    }
 }
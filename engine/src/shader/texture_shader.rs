@@ -1,4 +1,4 @@
-use super::{PixelShader, ShaderGlobals};
+use super::{PixelShader, ShaderDesc, ShaderGlobals};
 use crate::primitives::{Float2, Float3};
 use crate::texture::Texture;
 
@@ -18,6 +18,11 @@ impl PixelShader for TextureShader {
     ) -> Float3 {
         self.texture.sample(uv)
     }
+
+    fn desc(&self) -> Option<ShaderDesc> {
+        let texture = self.texture.source.clone()?;
+        Some(ShaderDesc::Texture { texture })
+    }
 }
 
 impl TextureShader {
@@ -0,0 +1,243 @@
+use crate::pose_graph::{NodeId, PoseGraph, PoseGraphError, PoseSnapshot, SharedPGNode};
+use crate::primitives::{FaceData3D, Float3, Transform, Tri, VectorOps};
+
+pub const MAX_INFLUENCES: usize = 4;
+
+// Joint influences of a single vertex. Unused slots have a weight of zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VertexWeights {
+    pub joints: [u16; MAX_INFLUENCES],
+    pub weights: [f32; MAX_INFLUENCES],
+}
+
+impl VertexWeights {
+    /// Keeps the strongest influences and normalizes their weights
+    pub fn from_influences(influences: &[(u16, f32)]) -> Self {
+        let mut sorted = influences.to_vec();
+        sorted.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mut out = VertexWeights::default();
+        for (i, (joint, weight)) in sorted.into_iter().take(MAX_INFLUENCES).enumerate() {
+            out.joints[i] = joint;
+            out.weights[i] = weight;
+        }
+
+        let total: f32 = out.weights.iter().sum();
+        if total > 0.0 {
+            out.weights.iter_mut().for_each(|w| *w /= total);
+        }
+        out
+    }
+}
+
+// A joint as stored in a model file
+#[derive(Debug, Clone)]
+pub struct JointDesc {
+    pub name: String,
+    pub parent: Option<usize>, // Index of the parent joint, always lower than the joint's own
+    pub position: Float3,      // Bind position, relative to the parent
+}
+
+// Skinning data of a mesh, with `weights` running parallel to `Mesh::data`
+#[derive(Debug, Clone, Default)]
+pub struct Skin {
+    pub joints: Vec<JointDesc>,
+    pub weights: Vec<Tri<VertexWeights>>,
+}
+
+/// Joints driving a skinned mesh, as pose graph nodes. The inverse bind transforms map
+/// the mesh space to each joint's local space in the bind pose.
+#[derive(Debug, Clone)]
+pub struct Skeleton {
+    pub joints: Vec<SharedPGNode>,
+    pub inverse_bind: Vec<Transform>,
+}
+
+impl Skeleton {
+    /// Uses the current joint poses as the bind pose. `mesh_pose` is the pose of the
+    /// entity the skinned mesh is drawn with.
+    pub fn from_bind_pose(joints: Vec<SharedPGNode>, mesh_pose: &SharedPGNode) -> Self {
+        let inverse_bind = joints
+            .iter()
+            .map(|j| PoseGraph::relative_transform(j, mesh_pose).inverse())
+            .collect();
+        Skeleton {
+            joints,
+            inverse_bind,
+        }
+    }
+
+    /// Creates pose graph nodes for the joints of a model file, below `mesh_pose`
+    pub fn instantiate(
+        joints: &[JointDesc],
+        mesh_pose: &SharedPGNode,
+    ) -> Result<Self, PoseGraphError> {
+        let mut nodes: Vec<SharedPGNode> = Vec::with_capacity(joints.len());
+        for joint in joints {
+            let parent = joint.parent.map_or(mesh_pose, |i| &nodes[i]).clone();
+            let node = PoseGraph::new(&joint.name, parent)?;
+            node.write().apply_translation(joint.position);
+            nodes.push(node);
+        }
+        Ok(Skeleton::from_bind_pose(nodes, mesh_pose))
+    }
+
+    /// Transforms from the bind pose to the current pose, both in mesh space
+    pub fn skin_transforms(&self, mesh_pose: &SharedPGNode) -> Vec<Transform> {
+        self.joints
+            .iter()
+            .zip(&self.inverse_bind)
+            .map(|(joint, inv_bind)| {
+                PoseGraph::relative_transform(joint, mesh_pose).compose(inv_bind)
+            })
+            .collect()
+    }
+
+    /// Same as `skin_transforms`, but read from a snapshot. None if a joint is missing.
+    pub fn skin_transforms_in(&self, poses: &PoseSnapshot, mesh: NodeId) -> Option<Vec<Transform>> {
+        self.joints
+            .iter()
+            .zip(&self.inverse_bind)
+            .map(|(joint, inv_bind)| {
+                let joint_to_mesh = poses.relative_transform(joint.read().id(), mesh)?;
+                Some(joint_to_mesh.compose(inv_bind))
+            })
+            .collect()
+    }
+}
+
+// Linear blend skinning of a single vertex. Vertices without weights keep their bind pose.
+fn skin_vertex(
+    position: Float3,
+    normal: Float3,
+    weights: &VertexWeights,
+    transforms: &[Transform],
+) -> (Float3, Float3) {
+    let mut skinned = (Float3::ZERO, Float3::ZERO);
+    let mut total = 0.0;
+
+    for (&joint, &weight) in weights.joints.iter().zip(&weights.weights) {
+        if weight <= 0.0 {
+            continue;
+        }
+        let transform = &transforms[joint as usize];
+        skinned.0 += transform.apply(position) * weight;
+        skinned.1 += (transform.rotation * normal) * weight;
+        total += weight;
+    }
+
+    match total > 0.0 {
+        true => (skinned.0 / total, skinned.1.normalized()),
+        false => (position, normal),
+    }
+}
+
+/// The faces deformed by the skin transforms
+pub fn skin_faces(faces: &[FaceData3D], skin: &Skin, transforms: &[Transform]) -> Vec<FaceData3D> {
+    faces
+        .iter()
+        .zip(&skin.weights)
+        .map(|(face, weights)| {
+            let corners = [0, 1, 2]
+                .map(|i| skin_vertex(face.vertices[i], face.normals[i], &weights[i], transforms));
+            FaceData3D {
+                vertices: Tri::new(corners[0].0, corners[1].0, corners[2].0),
+                normals: Tri::new(corners[0].1, corners[1].1, corners[2].1),
+                uvs: face.uvs.clone(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::ENGINE;
+    use crate::mesh::Mesh;
+    use crate::primitives::Quaternion;
+
+    use std::f32::consts::FRAC_PI_2;
+
+    fn load_cylinder() -> (SharedPGNode, Mesh, Skeleton) {
+        let root = PoseGraph::root();
+        let mesh = Mesh::from_obj_file("../resources/models/bent_cylinder.obj", ENGINE).unwrap();
+        let skin = mesh.skin.as_ref().unwrap();
+        let skeleton = Skeleton::instantiate(&skin.joints, &root).unwrap();
+        (root, mesh, skeleton)
+    }
+
+    // Skinned position of the first corner found at `p` in the bind pose
+    fn skinned_at(mesh: &Mesh, faces: &[FaceData3D], p: Float3) -> Float3 {
+        mesh.data
+            .iter()
+            .zip(faces)
+            .find_map(|(bind, skinned)| {
+                (0..3)
+                    .find(|&i| VectorOps::approx_eq(bind.vertices[i], p, 1e-5))
+                    .map(|i| skinned.vertices[i])
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_weights_normalized() {
+        let w = VertexWeights::from_influences(&[(0, 1.0), (3, 3.0), (1, 0.0)]);
+        assert_eq!(w.joints[..2], [3, 0]);
+        assert_eq!(w.weights, [0.75, 0.25, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_load_skinned_obj() {
+        let (root, mesh, skeleton) = load_cylinder();
+        let skin = mesh.skin.as_ref().unwrap();
+        assert_eq!(skin.weights.len(), mesh.data.len());
+        assert_eq!(skeleton.joints.len(), 2);
+        assert!(PoseGraph::find(&root, "root/lower/upper").is_some());
+
+        // The bind pose leaves the mesh untouched
+        let faces = mesh.deformed(None, Some(&skeleton.skin_transforms(&root)));
+        for (bind, skinned) in mesh.data.iter().zip(faces.iter()) {
+            for i in 0..3 {
+                assert!(VectorOps::approx_eq(
+                    bind.vertices[i],
+                    skinned.vertices[i],
+                    1e-5
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn test_two_bone_bend() {
+        let (root, mesh, skeleton) = load_cylinder();
+
+        // Bend the upper bone 90 degrees around Z, at the elbow at (0, 1, 0)
+        skeleton.joints[1]
+            .write()
+            .apply_rotation(Quaternion::from_z_angle(FRAC_PI_2));
+        let faces = mesh.deformed(None, Some(&skeleton.skin_transforms(&root)));
+
+        // The lower half only follows the lower bone
+        let p = skinned_at(&mesh, &faces, Float3::new(0.3, 0.5, 0.0));
+        assert!(VectorOps::approx_eq(p, Float3::new(0.3, 0.5, 0.0), 1e-5));
+
+        // The top cap rotates with the upper bone
+        let p = skinned_at(&mesh, &faces, Float3::new(0.0, 2.0, 0.0));
+        assert!(VectorOps::approx_eq(p, Float3::new(-1.0, 1.0, 0.0), 1e-5));
+
+        // At the elbow both bones have the same weight, so the vertex is pulled inwards
+        let bind = Float3::new(0.3, 1.0, 0.0);
+        let rotated = Float3::new(0.0, 1.3, 0.0);
+        let p = skinned_at(&mesh, &faces, bind);
+        assert!(VectorOps::approx_eq(p, (bind + rotated) * 0.5, 1e-5));
+
+        // The snapshot path agrees with the live one
+        let poses = PoseSnapshot::capture(&root);
+        let from_snapshot = skeleton
+            .skin_transforms_in(&poses, root.read().id())
+            .unwrap();
+        let faces_snapshot = mesh.deformed(None, Some(&from_snapshot));
+        let p = skinned_at(&mesh, &faces_snapshot, Float3::new(0.0, 2.0, 0.0));
+        assert!(VectorOps::approx_eq(p, Float3::new(-1.0, 1.0, 0.0), 1e-5));
+    }
+}
@@ -0,0 +1,261 @@
+use crate::primitives::{Float2, Float3};
+use crate::render_buffer::RenderBuffer;
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+const FIRST_GLYPH: char = ' ';
+const FALLBACK_GLYPH: char = '?';
+
+// 5x7 glyphs for printable ASCII, one byte per row, leftmost pixel in bit 4.
+const GLYPHS: [[u8; GLYPH_HEIGHT]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // '!'
+    [0x0a, 0x0a, 0x0a, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a], // '#'
+    [0x04, 0x0f, 0x14, 0x0e, 0x05, 0x1e, 0x04], // '$'
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // '%'
+    [0x0c, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0d], // '&'
+    [0x0c, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // '('
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // ')'
+    [0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00], // '*'
+    [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08], // ','
+    [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c], // '.'
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // '/'
+    [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e], // '0'
+    [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e], // '1'
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f], // '2'
+    [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e], // '3'
+    [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02], // '4'
+    [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e], // '5'
+    [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e], // '6'
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // '7'
+    [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e], // '8'
+    [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c], // '9'
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00], // ':'
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x04, 0x08], // ';'
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // '<'
+    [0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00], // '='
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // '>'
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // '?'
+    [0x0e, 0x11, 0x01, 0x0d, 0x15, 0x15, 0x0e], // '@'
+    [0x0e, 0x11, 0x11, 0x11, 0x1f, 0x11, 0x11], // 'A'
+    [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e], // 'B'
+    [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e], // 'C'
+    [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c], // 'D'
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f], // 'E'
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10], // 'F'
+    [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f], // 'G'
+    [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // 'H'
+    [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // 'I'
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c], // 'J'
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // 'K'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f], // 'L'
+    [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11], // 'M'
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // 'N'
+    [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // 'O'
+    [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10], // 'P'
+    [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d], // 'Q'
+    [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11], // 'R'
+    [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e], // 'S'
+    [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // 'T'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // 'U'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04], // 'V'
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a], // 'W'
+    [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11], // 'X'
+    [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04], // 'Y'
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f], // 'Z'
+    [0x0e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0e], // '['
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // '\\'
+    [0x0e, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0e], // ']'
+    [0x04, 0x0a, 0x11, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f], // '_'
+    [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x0e, 0x01, 0x0f, 0x11, 0x0f], // 'a'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1e], // 'b'
+    [0x00, 0x00, 0x0e, 0x10, 0x10, 0x11, 0x0e], // 'c'
+    [0x01, 0x01, 0x0d, 0x13, 0x11, 0x11, 0x0f], // 'd'
+    [0x00, 0x00, 0x0e, 0x11, 0x1f, 0x10, 0x0e], // 'e'
+    [0x06, 0x09, 0x08, 0x1c, 0x08, 0x08, 0x08], // 'f'
+    [0x00, 0x0f, 0x11, 0x11, 0x0f, 0x01, 0x0e], // 'g'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11], // 'h'
+    [0x04, 0x00, 0x0c, 0x04, 0x04, 0x04, 0x0e], // 'i'
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0c], // 'j'
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12], // 'k'
+    [0x0c, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // 'l'
+    [0x00, 0x00, 0x1a, 0x15, 0x15, 0x11, 0x11], // 'm'
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11], // 'n'
+    [0x00, 0x00, 0x0e, 0x11, 0x11, 0x11, 0x0e], // 'o'
+    [0x00, 0x00, 0x1e, 0x11, 0x1e, 0x10, 0x10], // 'p'
+    [0x00, 0x00, 0x0d, 0x13, 0x0f, 0x01, 0x01], // 'q'
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10], // 'r'
+    [0x00, 0x00, 0x0e, 0x10, 0x0e, 0x01, 0x1e], // 's'
+    [0x08, 0x08, 0x1c, 0x08, 0x08, 0x09, 0x06], // 't'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0d], // 'u'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0a, 0x04], // 'v'
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0a], // 'w'
+    [0x00, 0x00, 0x11, 0x0a, 0x04, 0x0a, 0x11], // 'x'
+    [0x00, 0x00, 0x11, 0x11, 0x0f, 0x01, 0x0e], // 'y'
+    [0x00, 0x00, 0x1f, 0x02, 0x04, 0x08, 0x1f], // 'z'
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02], // '{'
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // '|'
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08], // '}'
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00], // '~'
+];
+
+/// Built-in fixed-width bitmap font, drawn straight into a RenderBuffer.
+/// Each glyph pixel covers `scale` x `scale` buffer pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct BitmapFont {
+    pub scale: usize,
+}
+
+impl Default for BitmapFont {
+    fn default() -> Self {
+        Self { scale: 2 }
+    }
+}
+
+impl BitmapFont {
+    pub const fn new(scale: usize) -> Self {
+        Self { scale }
+    }
+
+    // Horizontal and vertical advance of a single character, including spacing
+    pub const fn char_size(&self) -> (usize, usize) {
+        (
+            (GLYPH_WIDTH + 1) * self.scale,
+            (GLYPH_HEIGHT + 2) * self.scale,
+        )
+    }
+
+    pub fn text_size(&self, text: &str) -> (usize, usize) {
+        let (char_w, char_h) = self.char_size();
+        let columns = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+        (columns * char_w, text.lines().count() * char_h)
+    }
+
+    fn glyph(c: char) -> &'static [u8; GLYPH_HEIGHT] {
+        let idx = |c: char| (c as usize).wrapping_sub(FIRST_GLYPH as usize);
+        GLYPHS.get(idx(c)).unwrap_or(&GLYPHS[idx(FALLBACK_GLYPH)])
+    }
+
+    /// Draws text with its top-left corner at `pos`, ignoring the depth buffer.
+    /// Newlines start a new line, unsupported characters are drawn as '?'.
+    pub fn draw_text(&self, buffer: &RenderBuffer, pos: Float2, text: &str, color: Float3) {
+        let (char_w, char_h) = self.char_size();
+        let (origin_x, origin_y) = (pos.x.round() as i64, pos.y.round() as i64);
+
+        for (line_idx, line) in text.lines().enumerate() {
+            for (char_idx, c) in line.chars().enumerate() {
+                let glyph_x = origin_x + (char_idx * char_w) as i64;
+                let glyph_y = origin_y + (line_idx * char_h) as i64;
+
+                for (row, bits) in Self::glyph(c).iter().enumerate() {
+                    for col in
+                        (0..GLYPH_WIDTH).filter(|col| bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0)
+                    {
+                        self.fill_cell(buffer, glyph_x, glyph_y, col, row, color);
+                    }
+                }
+            }
+        }
+    }
+
+    // Fills the scaled block of a single glyph pixel
+    fn fill_cell(
+        &self,
+        buffer: &RenderBuffer,
+        glyph_x: i64,
+        glyph_y: i64,
+        col: usize,
+        row: usize,
+        color: Float3,
+    ) {
+        let x0 = glyph_x + (col * self.scale) as i64;
+        let y0 = glyph_y + (row * self.scale) as i64;
+
+        for y in (y0..y0 + self.scale as i64).filter(|y| *y >= 0) {
+            for x in (x0..x0 + self.scale as i64).filter(|x| *x >= 0) {
+                buffer.set_pixel(x as usize, y as usize, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 64;
+    const HEIGHT: usize = 32;
+
+    fn lit_pixels(buffer: &RenderBuffer) -> Vec<(usize, usize)> {
+        (0..WIDTH * HEIGHT)
+            .filter(|i| buffer.pixels[*i].lock().0 == Float3::ONE)
+            .map(|i| (i % WIDTH, i / WIDTH))
+            .collect()
+    }
+
+    #[test]
+    fn test_glyph_table() {
+        assert_eq!(BitmapFont::glyph(' ').iter().sum::<u8>(), 0);
+        for c in '!'..='~' {
+            assert!(BitmapFont::glyph(c).iter().any(|row| *row != 0), "{c}");
+            assert!(
+                BitmapFont::glyph(c)
+                    .iter()
+                    .all(|row| *row < 1 << GLYPH_WIDTH)
+            );
+        }
+        assert_eq!(BitmapFont::glyph('\u{e9}'), BitmapFont::glyph('?'));
+    }
+
+    #[test]
+    fn test_draw_char() {
+        let buffer = RenderBuffer::new(WIDTH, HEIGHT);
+        BitmapFont::new(1).draw_text(&buffer, Float2::new(2.0, 3.0), "T", Float3::ONE);
+
+        // Top bar of the T, then its stem
+        let lit = lit_pixels(&buffer);
+        assert_eq!(lit.len(), 5 + 6);
+        assert!((2..7).all(|x| lit.contains(&(x, 3))));
+        assert!((4..10).all(|y| lit.contains(&(4, y))));
+    }
+
+    #[test]
+    fn test_scale_and_clipping() {
+        let buffer = RenderBuffer::new(WIDTH, HEIGHT);
+        let font = BitmapFont::new(2);
+
+        font.draw_text(&buffer, Float2::new(0.0, 0.0), "|", Float3::ONE);
+        assert_eq!(lit_pixels(&buffer).len(), 7 * 4);
+
+        // Partially off-screen text must not panic or wrap around
+        let before = lit_pixels(&buffer);
+        font.draw_text(
+            &buffer,
+            Float2::new(-4.0, 28.0),
+            "||||||||||||",
+            Float3::ONE,
+        );
+        let lit = lit_pixels(&buffer);
+        assert!(lit.len() > before.len());
+        assert!(lit.iter().all(|p| before.contains(p) || p.1 >= 28));
+
+        // Entirely off-screen text leaves the buffer as it was
+        for pos in [(-100.0, 0.0), (0.0, -100.0), (70.0, 0.0), (0.0, 40.0)] {
+            font.draw_text(&buffer, Float2::new(pos.0, pos.1), "|||", Float3::ONE);
+        }
+        assert_eq!(lit_pixels(&buffer), lit);
+    }
+
+    #[test]
+    fn test_text_size() {
+        let font = BitmapFont::new(2);
+        assert_eq!(font.text_size("abc"), (3 * 12, 18));
+        assert_eq!(font.text_size("abc\nfoobar"), (6 * 12, 2 * 18));
+    }
+}
@@ -2,7 +2,7 @@ use png::Decoder;
 
 use std::fs::File;
 use std::io::{BufReader, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::primitives::{Float2, Float3};
 
@@ -12,17 +12,20 @@ pub struct Texture {
     pub height: usize,
     scale: Float2,
     data: Vec<Float3>,
+    pub source: Option<PathBuf>, // File the texture was loaded from
 }
 
 impl Texture {
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
         let extension = path.as_ref().extension().and_then(|s| s.to_str());
 
-        match extension {
-            Some("png") => Self::from_png_file(path),
-            Some("bytes") => Self::from_bytes_file(path),
+        let mut texture = match extension {
+            Some("png") => Self::from_png_file(&path),
+            Some("bytes") => Self::from_bytes_file(&path),
             _ => Err("Unsupported extension".into()),
-        }
+        }?;
+        texture.source = Some(path.as_ref().to_path_buf());
+        Ok(texture)
     }
 
     fn from_png_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
@@ -61,6 +64,7 @@ impl Texture {
             height,
             scale: Float2::new((width - 1) as f32, (height - 1) as f32),
             data,
+            source: None,
         })
     }
 
@@ -86,6 +90,7 @@ impl Texture {
             height,
             scale: Float2::new((width - 1) as f32, (height - 1) as f32),
             data,
+            source: None,
         })
     }
 
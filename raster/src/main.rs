@@ -1,11 +1,126 @@
+mod path_tracer;
 mod raster;
+mod ray_tracer;
+mod stats;
 mod test_scene;
+#[cfg(test)]
+mod test_util;
 
-use engine::scene::Scene;
+use engine::primitives::Float3;
+use engine::recording::InputRecording;
+use engine::render_buffer::RenderBuffer;
+use engine::scene::{RunMode, Scene};
 
-use test_scene::TestScene;
+use std::error::Error;
 
-fn main() {
-    let mut scene = TestScene::<960, 540>::default();
-    scene.run();
+use path_tracer::{PathSettings, PathTracer};
+use test_scene::{Backend, TestScene};
+
+const WIDTH: usize = 960;
+const HEIGHT: usize = 540;
+const USAGE: &str = "Usage: raster [--record <file> | --replay <file> \
+    | --replay-headless <file> <out.png> | --trace-headless <file> <out.png> \
+    | --diff <file> <out.png> | --path-trace <file> <samples> <out.png|out.pfm>]";
+const PROGRESS_STEPS: u32 = 10; // Progress messages per path traced image
+
+// Replays a recording without a window, returning the scene and its last frame
+fn replay(path: &str, backend: Backend) -> Result<(TestScene, RenderBuffer), Box<dyn Error>> {
+    let mut scene = TestScene::default();
+    // Frame timings in the HUD differ between runs
+    scene.set_hud_visible(false);
+    scene.set_backend(backend);
+
+    let recording = InputRecording::from_file(path)?;
+    let mut result = None;
+    let last = recording.frames.len().saturating_sub(1);
+    scene.replay_headless(&recording, &mut |i, buffer| {
+        if i == last {
+            result = Some(buffer.clone());
+        }
+    });
+    let frame = result.ok_or_else(|| format!("No frames in {path}"))?;
+    Ok((scene, frame))
+}
+
+fn replay_last_frame(path: &str, backend: Backend) -> Result<RenderBuffer, Box<dyn Error>> {
+    Ok(replay(path, backend)?.1)
+}
+
+// Ground truth for the last frame of a recording. PFM files keep the full HDR radiance.
+fn path_trace(path: &str, samples: u32, out: &str) -> Result<(), Box<dyn Error>> {
+    if samples == 0 {
+        return Err("The path tracer needs at least one sample per pixel".into());
+    }
+    let (scene, mut frame) = replay(path, Backend::Raster)?;
+    let mut tracer = PathTracer::new(PathSettings::default());
+    let step = samples.div_ceil(PROGRESS_STEPS);
+    while tracer.samples() < samples {
+        let start = std::time::Instant::now();
+        let count = step.min(samples - tracer.samples());
+        tracer.add_samples(scene.data(), count);
+        println!(
+            "{}/{} samples ({:.2} s)",
+            tracer.samples(),
+            samples,
+            start.elapsed().as_secs_f32()
+        );
+    }
+
+    if out.ends_with(".pfm") {
+        return tracer.save_pfm(out);
+    }
+    frame.clear(Float3::ZERO);
+    tracer.resolve(&frame);
+    frame.save_png(out)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let mut scene = TestScene::default();
+
+    match args[..] {
+        [] => scene.run(WIDTH, HEIGHT),
+        ["--record", path] => scene.run_with(WIDTH, HEIGHT, RunMode::Record(path.into())),
+        ["--replay", path] => {
+            let recording = InputRecording::from_file(path)?;
+            scene.run_with(WIDTH, HEIGHT, RunMode::Replay(recording));
+        }
+        ["--replay-headless", path, out] => {
+            replay_last_frame(path, Backend::Raster)?.save_png(out)?;
+        }
+        ["--trace-headless", path, out] => {
+            replay_last_frame(path, Backend::RayTrace)?.save_png(out)?;
+        }
+        ["--diff", path, out] => {
+            // Where the rasterizer differs from the ray traced reference
+            let raster = replay_last_frame(path, Backend::Raster)?;
+            let traced = replay_last_frame(path, Backend::RayTrace)?;
+            let diff = raster.diff(&traced)?;
+            diff.save_png(out)?;
+
+            let errors: Vec<f32> = diff
+                .pixels
+                .iter()
+                .map(|p| {
+                    let c = p.lock().0;
+                    c.x.max(c.y).max(c.z)
+                })
+                .collect();
+            let differing = errors.iter().filter(|&&e| e > 1.0 / 255.0).count();
+            println!(
+                "mean error: {:.5}, max error: {:.5}, {} of {} pixels differ",
+                errors.iter().sum::<f32>() / errors.len().max(1) as f32,
+                errors.iter().copied().fold(0.0, f32::max),
+                differing,
+                errors.len()
+            );
+        }
+        ["--path-trace", path, samples, out] => {
+            let samples = samples.parse().map_err(|_| USAGE)?;
+            path_trace(path, samples, out)?;
+        }
+        _ => return Err(USAGE.into()),
+    }
+    Ok(())
 }
@@ -0,0 +1,537 @@
+use rayon::prelude::*;
+
+use std::error::Error;
+use std::f32::consts::{PI, TAU};
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use engine::bvh::Bvh;
+use engine::camera::CameraModel;
+use engine::material::Material;
+use engine::pose_graph::{PoseGraph, PoseSnapshot, SharedPGNode};
+use engine::primitives::{FaceData3D, Float2, Float3, Quaternion, Ray, Tri, VectorOps};
+use engine::render_buffer::RenderBuffer;
+use engine::scene::SceneData;
+
+use crate::stats::{EntityStats, RenderStats};
+
+const SURFACE_OFFSET: f32 = 1e-3; // Along the normal, so new rays don't hit their own surface
+const ROULETTE_DEPTH: u32 = 3; // Bounces before paths may be terminated at random
+const LUMINANCE: Float3 = Float3::new(0.2126, 0.7152, 0.0722);
+
+#[derive(Debug, Clone, Copy)]
+pub struct PathSettings {
+    pub max_bounces: u32,
+    pub sky: Float3,   // Radiance of rays leaving the scene
+    pub sun: Float3,   // Irradiance from the scene's sun, on a surface facing it
+    pub exposure: f32, // Scales the radiance before it's clamped to the display range
+}
+
+impl Default for PathSettings {
+    fn default() -> Self {
+        PathSettings {
+            max_bounces: 8,
+            sky: Float3::ONE * 0.55, // The rasterizer's clear color
+            sun: Float3::new(2.0, 1.9, 1.7),
+            exposure: 1.0,
+        }
+    }
+}
+
+// Small PCG generator. Seeded by pixel and sample, so each pass can be reproduced.
+struct Rng(u64);
+
+impl Rng {
+    fn new(pixel: usize, sample: u32) -> Self {
+        // SplitMix64 spreads neighboring seeds apart
+        let mut z = ((pixel as u64) << 32 | sample as u64).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        Rng(z ^ (z >> 31))
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let state = self.0;
+        self.0 = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        xorshifted.rotate_right((state >> 59) as u32)
+    }
+
+    // In [0, 1)
+    fn next(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn in_unit_sphere(&mut self) -> Float3 {
+        loop {
+            let p = Float3::new(self.next(), self.next(), self.next()) * 2.0 - Float3::ONE;
+            if p.sqr_magnitude() <= 1.0 {
+                return p;
+            }
+        }
+    }
+
+    // Cosine-weighted around `n`, so the cosine and pdf of a diffuse bounce cancel
+    fn cosine_direction(&mut self, n: Float3) -> Float3 {
+        let (u, v) = (self.next(), self.next());
+        let (r, phi) = (u.sqrt(), TAU * v);
+
+        // Orthonormal basis around n (Duff et al. 2017)
+        let sign = 1.0_f32.copysign(n.z);
+        let a = -1.0 / (sign + n.z);
+        let b = n.x * n.y * a;
+        let t = Float3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x);
+        let s = Float3::new(b, sign + n.y * n.y * a, -n.y);
+        t * (r * phi.cos()) + s * (r * phi.sin()) + n * (1.0 - u).sqrt()
+    }
+}
+
+fn reflect(d: Float3, n: Float3) -> Float3 {
+    d - n * (2.0 * d.dot(n))
+}
+
+// Where a light sample landed, and how likely it was per unit area
+struct LightSample {
+    point: Float3,
+    normal: Float3,
+    emission: Float3,
+    pdf: f32,
+}
+
+// Every entity's deformed triangles in world space, with their materials, and the
+// emissive triangles as area lights
+#[derive(Debug)]
+struct WorldScene {
+    faces: Vec<FaceData3D>,   // With normalized vertex normals
+    face_entity: Vec<usize>,  // Index into `materials`
+    materials: Vec<Material>, // Per entity
+    bvh: Bvh,
+    lights: Vec<usize>,  // Emissive faces
+    light_cdf: Vec<f32>, // Running sum of their emitted power
+    to_sun: Option<Float3>,
+}
+
+impl WorldScene {
+    fn new(data: &SceneData, stats: &mut RenderStats) -> Self {
+        // Sorted, so lights are sampled in the same order on every run
+        let mut names: Vec<_> = data.entities.keys().collect();
+        names.sort();
+        let poses = PoseSnapshot::capture(&data.cam_pose);
+        let world_of = |node: &SharedPGNode| {
+            let id = node.read().id();
+            poses
+                .world_transform(id)
+                .unwrap_or_else(|| PoseGraph::world_transform(node))
+        };
+
+        let mut faces = vec![];
+        let mut face_entity = vec![];
+        let mut materials = vec![];
+        for name in names {
+            let start = Instant::now();
+            let entity = &data.entities[name];
+            let world = world_of(&entity.pose);
+            let deformed = entity.deformed_faces(Some(&poses));
+
+            let to_world = |n: Float3| (world.rotation * (n / world.scale)).normalized();
+            faces.extend(deformed.iter().map(|f| FaceData3D {
+                vertices: world.apply_tri(&f.vertices),
+                normals: Tri::new(
+                    to_world(f.normals[0]),
+                    to_world(f.normals[1]),
+                    to_world(f.normals[2]),
+                ),
+                uvs: f.uvs.clone(),
+            }));
+            face_entity.resize(faces.len(), materials.len());
+            materials.push(entity.material);
+
+            stats.entities.push(EntityStats {
+                name: name.clone(),
+                transform_time: start.elapsed(),
+                ..Default::default()
+            });
+        }
+
+        let mut lights = vec![];
+        let mut light_cdf = vec![];
+        let mut total = 0.0;
+        for (i, face) in faces.iter().enumerate() {
+            let emission = materials[face_entity[i]].emission;
+            let power = emission.dot(LUMINANCE) * face.vertices.normal().length() / 2.0;
+            if power > 0.0 {
+                total += power;
+                lights.push(i);
+                light_cdf.push(total);
+            }
+        }
+
+        let to_sun = data
+            .sun_pose
+            .as_ref()
+            .map(|sun| -world_of(sun).forward_vec().normalized());
+
+        WorldScene {
+            bvh: Bvh::new(&faces),
+            faces,
+            face_entity,
+            materials,
+            lights,
+            light_cdf,
+            to_sun,
+        }
+    }
+
+    fn material(&self, face: usize) -> &Material {
+        &self.materials[self.face_entity[face]]
+    }
+
+    // A point on an emissive triangle, picked by emitted power, then uniformly by area
+    fn sample_light(&self, rng: &mut Rng) -> Option<LightSample> {
+        let total = *self.light_cdf.last()?;
+        let pick = rng.next() * total;
+        let i = self
+            .light_cdf
+            .partition_point(|&c| c <= pick)
+            .min(self.lights.len() - 1);
+        let face = &self.faces[self.lights[i]];
+
+        let power = self.light_cdf[i] - i.checked_sub(1).map_or(0.0, |j| self.light_cdf[j]);
+        let normal = face.vertices.normal();
+        let area = normal.length() / 2.0;
+        let (su, v) = (rng.next().sqrt(), rng.next());
+        let weights = Tri::new(1.0 - su, su * (1.0 - v), su * v);
+
+        Some(LightSample {
+            point: (&face.vertices * &weights).sum(),
+            normal: normal.normalized(),
+            emission: self.material(self.lights[i]).emission,
+            pdf: power / total / area,
+        })
+    }
+
+    // Light arriving directly from the sun and one area light sample, reflected by a
+    // white diffuse surface at `p`
+    fn direct_light(&self, p: Float3, n: Float3, settings: &PathSettings, rng: &mut Rng) -> Float3 {
+        let mut light = Float3::ZERO;
+
+        if let Some(to_sun) = self.to_sun {
+            let cos = n.dot(to_sun);
+            if cos > 0.0 && !self.bvh.occluded(&Ray::new(p, to_sun), f32::INFINITY) {
+                light += settings.sun * (cos / PI);
+            }
+        }
+
+        if let Some(sample) = self.sample_light(rng) {
+            let offset = sample.point - p;
+            let dist_sq = offset.sqr_magnitude();
+            let dir = offset / dist_sq.sqrt();
+            let cos = n.dot(dir);
+            let cos_light = sample.normal.dot(dir).abs(); // Lights emit from both sides
+            let shadow = Ray::new(p, offset);
+            if cos > 0.0 && cos_light > 0.0 && !self.bvh.occluded(&shadow, 1.0 - SURFACE_OFFSET) {
+                light += sample.emission * (cos * cos_light / (dist_sq * sample.pdf * PI));
+            }
+        }
+        light
+    }
+
+    // Radiance arriving along `ray`. Emission is only counted where the previous bounce
+    // couldn't sample lights directly, so no light is counted twice.
+    fn radiance(&self, mut ray: Ray, settings: &PathSettings, rng: &mut Rng) -> Float3 {
+        let mut radiance = Float3::ZERO;
+        let mut throughput = Float3::ONE;
+        let mut count_emission = true;
+
+        for bounce in 0..=settings.max_bounces {
+            let Some(hit) = self.bvh.intersect_ray(&ray, f32::INFINITY) else {
+                radiance += throughput * settings.sky;
+                break;
+            };
+            let face = &self.faces[hit.face];
+            let material = self.material(hit.face);
+            if count_emission {
+                radiance += throughput * material.emission;
+            }
+            if bounce == settings.max_bounces {
+                break;
+            }
+
+            // Surfaces are two-sided, facing the incoming ray
+            let dir = ray.direction.normalized();
+            let mut geometric = face.vertices.normal().normalized();
+            if geometric.dot(dir) > 0.0 {
+                geometric = -geometric;
+            }
+            let mut normal = (&face.normals * &hit.barycentric).sum().normalized();
+            if normal.dot(geometric) < 0.0 {
+                normal = -normal;
+            }
+            let p = ray.at(hit.t) + geometric * SURFACE_OFFSET;
+
+            let next = if rng.next() < material.specular {
+                count_emission = true;
+                reflect(dir, normal) + rng.in_unit_sphere() * material.roughness
+            } else {
+                radiance +=
+                    throughput * material.albedo * self.direct_light(p, normal, settings, rng);
+                count_emission = false;
+                throughput *= material.albedo;
+                rng.cosine_direction(normal)
+            };
+            if next.dot(geometric) <= 0.0 {
+                break; // Scattered into the surface
+            }
+
+            // Ends dim paths early, boosting the ones that go on to stay unbiased
+            if bounce >= ROULETTE_DEPTH {
+                let survive = throughput.x.max(throughput.y).max(throughput.z).min(0.95);
+                if rng.next() >= survive {
+                    break;
+                }
+                throughput /= survive;
+            }
+            ray = Ray::new(p, next);
+        }
+        radiance
+    }
+}
+
+/// Progressive Monte Carlo path tracer with global illumination. Averages samples into
+/// an HDR buffer, lit by the sky, the scene's sun and emissive meshes. Materials come
+/// from the entities, shaders are not used.
+#[derive(Debug, Default)]
+pub struct PathTracer {
+    pub settings: PathSettings,
+    width: usize,
+    height: usize,
+    accum: Vec<Float3>, // Sum of the samples per pixel
+    samples: u32,
+    view: Option<(Float3, Quaternion, CameraModel)>, // Camera the samples were taken from
+    scene: Option<WorldScene>,                       // Gathered on the first call after a reset
+}
+
+impl PathTracer {
+    pub fn new(settings: PathSettings) -> Self {
+        PathTracer {
+            settings,
+            ..Default::default()
+        }
+    }
+
+    /// Samples per pixel so far
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Discards the samples and the gathered scene, e.g. after the scene changed
+    pub fn reset(&mut self) {
+        self.scene = None;
+        self.restart();
+    }
+
+    // Discards the samples, keeping the scene
+    fn restart(&mut self) {
+        self.accum.fill(Float3::ZERO);
+        self.samples = 0;
+    }
+
+    /// Traces `count` more samples per pixel. Starts over if the camera, its projection
+    /// or the viewport changed since the last call. Other changes to the scene need a
+    /// `reset`. Entity times are only reported by the call that gathers the scene.
+    pub fn add_samples(&mut self, data: &SceneData, count: u32) -> RenderStats {
+        let cam = PoseGraph::world_transform(&data.cam_pose);
+        let (width, height) = (data.cam_model.width(), data.cam_model.height());
+        if (width, height) != (self.width, self.height) {
+            (self.width, self.height) = (width, height);
+            self.accum = vec![Float3::ZERO; width * height];
+            self.samples = 0;
+        }
+        let view = (cam.position, cam.rotation, data.cam_model);
+        if self.view != Some(view) {
+            self.view = Some(view);
+            self.restart();
+        }
+
+        let mut stats = RenderStats::default();
+        let scene = self
+            .scene
+            .get_or_insert_with(|| WorldScene::new(data, &mut stats));
+        stats.triangles_submitted = scene.faces.len();
+
+        let start = Instant::now();
+        let (first, settings) = (self.samples, &self.settings);
+        self.accum
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(idx, sum)| {
+                let pixel = Float2::new((idx % width) as f32, (idx / width) as f32);
+                for sample in first..first + count {
+                    // Jittered over the pixel's area, centered where the rasterizer samples
+                    let mut rng = Rng::new(idx, sample);
+                    let jitter = Float2::new(rng.next(), rng.next()) - Float2::ONE * 0.5;
+                    let ray = data
+                        .cam_model
+                        .screen_to_ray(pixel + jitter)
+                        .transformed(&cam);
+                    *sum += scene.radiance(ray, settings, &mut rng);
+                }
+            });
+        self.samples += count;
+
+        stats.trace_time = start.elapsed();
+        stats.pixels_shaded = self.accum.len() * count as usize;
+        stats
+    }
+
+    /// Average radiance per pixel, row-major
+    pub fn radiance(&self) -> Vec<Float3> {
+        let scale = 1.0 / self.samples.max(1) as f32;
+        self.accum.iter().map(|&sum| sum * scale).collect()
+    }
+
+    /// Writes the exposed colors into `buffer`, which has the camera's viewport size.
+    /// Depths are left as they are.
+    pub fn resolve(&self, buffer: &RenderBuffer) {
+        let exposure = self.settings.exposure;
+        buffer
+            .pixels
+            .par_iter()
+            .zip(self.radiance())
+            .for_each(|(pixel, radiance)| pixel.lock().0 = radiance * exposure);
+    }
+
+    /// Saves the average radiance unclamped, as a little-endian PFM image
+    pub fn save_pfm<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut bytes = format!("PF\n{} {}\n-1.0\n", self.width, self.height).into_bytes();
+        let radiance = self.radiance();
+        // PFM rows go from the bottom up
+        for row in radiance.chunks(self.width.max(1)).rev() {
+            for c in row {
+                for channel in [c.x, c.y, c.z] {
+                    bytes.extend(channel.to_le_bytes());
+                }
+            }
+        }
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::cube_scene;
+    use engine::primitives::Transform;
+
+    const WIDTH: usize = 40;
+    const HEIGHT: usize = 30;
+
+    // Cubes given as (position, scale, material), with a camera at the origin
+    fn scene(cubes: &[(Float3, Float3, Material)]) -> SceneData {
+        let names: Vec<_> = (0..cubes.len()).map(|i| format!("cube{i}")).collect();
+        let transforms: Vec<_> = names
+            .iter()
+            .zip(cubes)
+            .map(|(name, &(position, scale, _))| {
+                let transform = Transform {
+                    position,
+                    scale,
+                    ..Default::default()
+                };
+                (name.as_str(), transform)
+            })
+            .collect();
+        let mut data = cube_scene(&transforms, WIDTH, HEIGHT);
+        for (name, &(_, _, material)) in names.iter().zip(cubes) {
+            data.entities.get_mut(name).unwrap().material = material;
+        }
+        data
+    }
+
+    fn at(radiance: &[Float3], x: usize, y: usize) -> Float3 {
+        radiance[y * WIDTH + x]
+    }
+
+    #[test]
+    fn test_white_furnace() {
+        // A convex object under a uniform sky reflects exactly its albedo times the sky
+        let albedo = Float3::new(0.5, 0.25, 0.75);
+        let data = scene(&[(
+            Float3::FORWARD * 5.0,
+            Float3::ONE,
+            Material::diffuse(albedo),
+        )]);
+        let mut tracer = PathTracer::new(PathSettings {
+            sky: Float3::ONE,
+            ..Default::default()
+        });
+        let stats = tracer.add_samples(&data, 4);
+        assert_eq!(stats.pixels_shaded, WIDTH * HEIGHT * 4);
+        assert_eq!(stats.entities.len(), 1);
+
+        let radiance = tracer.radiance();
+        assert!(VectorOps::approx_eq(
+            at(&radiance, WIDTH / 2, HEIGHT / 2),
+            albedo,
+            1e-4
+        ));
+        assert!(VectorOps::approx_eq(at(&radiance, 0, 0), Float3::ONE, 1e-6));
+    }
+
+    #[test]
+    fn test_area_light() {
+        // A lamp hanging over a floor, in the dark
+        let emission = Float3::new(4.0, 4.0, 3.0);
+        let mut data = scene(&[
+            (
+                Float3::new(0.0, -2.0, -8.0),
+                Float3::new(10.0, 0.1, 10.0),
+                Material::default(),
+            ),
+            (
+                Float3::new(-1.0, 0.5, -6.0),
+                Float3::ONE * 0.3,
+                Material::emissive(emission),
+            ),
+        ]);
+        let mut tracer = PathTracer::new(PathSettings {
+            sky: Float3::ZERO,
+            ..Default::default()
+        });
+        tracer.add_samples(&data, 16);
+        let radiance = tracer.radiance();
+
+        // Seen directly, then lighting the floor below it more than further away
+        let cam_model = &data.cam_model;
+        let lamp = cam_model.point_to_screen(Float3::new(-1.0, 0.5, -6.0));
+        let lamp = at(&radiance, lamp.x as usize, lamp.y as usize);
+        assert!(VectorOps::approx_eq(lamp, emission, 1e-4));
+        let below = cam_model.point_to_screen(Float3::new(-1.0, -1.9, -6.0));
+        let far = cam_model.point_to_screen(Float3::new(2.5, -1.9, -6.0));
+        let below = at(&radiance, below.x as usize, below.y as usize);
+        let far = at(&radiance, far.x as usize, far.y as usize);
+        assert!(far.x > 0.0 && below.x > 2.0 * far.x, "{below} vs {far}");
+
+        // Reproducible, and restarted when the camera moves, without gathering the
+        // scene again. Moving an entity needs a reset.
+        let mut again = PathTracer::new(tracer.settings);
+        again.add_samples(&data, 16);
+        assert_eq!(again.radiance(), radiance);
+        data.cam_pose.write().apply_translation(Float3::X);
+        let stats = again.add_samples(&data, 1);
+        assert_eq!(again.samples(), 1);
+        assert!(stats.entities.is_empty());
+
+        // As does a different projection of the same viewport
+        again.add_samples(&data, 1);
+        data.cam_model = CameraModel::new(40.0, true, cam_model.width(), cam_model.height());
+        again.add_samples(&data, 1);
+        assert_eq!(again.samples(), 1);
+        again.reset();
+        assert_eq!(again.add_samples(&data, 1).entities.len(), 2);
+    }
+}
@@ -1,39 +1,61 @@
 use rayon::prelude::*;
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
 
-use engine::camera::CameraModel;
-use engine::entity::Entity;
-use engine::pose_graph::{PoseGraph, SharedPGNode};
-use engine::primitives::{FaceData2D, Float2, Transform, Tri};
+use engine::camera::{CameraModel, NEAR_CLIP};
+use engine::entity::{Entity, PolygonMode};
+use engine::pose_graph::{PoseGraph, PoseSnapshot};
+use engine::primitives::{FaceData2D, Float2, Float3, Transform, Tri};
 use engine::render_buffer::RenderBuffer;
 use engine::scene::SceneData;
 
-const NEAR_CLIP: f32 = -0.01;
+use crate::stats::{EntityStats, FrameCounters, RenderStats};
 
-fn to_screen_space<const WIDTH: usize, const HEIGHT: usize>(
+const OVERDRAW_HEATMAP_MAX: u32 = 8;
+const WIRE_COLOR: Float3 = Float3::new(1.0, 0.6, 0.0);
+const POINT_SIZE: usize = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DebugView {
+    #[default]
+    Shaded,
+    Overdraw, // Number of shaded fragments per pixel, as a heatmap
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderSettings {
+    pub depth_prepass: bool,
+    pub debug_view: DebugView,
+    pub polygon_mode: PolygonMode, // Used for entities without their own mode
+}
+
+fn to_screen_space(
     entity: &Entity,
-    cam_model: CameraModel<WIDTH, HEIGHT>,
-    cam_pose: SharedPGNode,
+    cam_model: CameraModel,
+    vert_to_cam: Transform,
+    poses: &PoseSnapshot,
+    counters: &FrameCounters,
 ) -> Vec<FaceData2D> {
-    let vert_to_cam = PoseGraph::relative_transform(&entity.pose, &cam_pose);
+    let faces = entity.deformed_faces(Some(poses));
     let norm_to_cam = Transform {
         rotation: vert_to_cam.rotation,
         ..Default::default()
     };
 
-    entity
-        .mesh
-        .data
+    faces
         .par_iter()
         .filter_map(|v| {
             let vert_cam = vert_to_cam.apply_tri(&v.vertices);
             if vert_cam.vertices.iter().any(|vert| vert.z >= NEAR_CLIP) {
+                counters.near_clipped.add(1);
                 return None;
             }
 
             let vert_screen = cam_model.tri_to_screen(&vert_cam);
             if vert_screen.should_cull() {
+                counters.backface_culled.add(1);
                 return None;
             }
 
@@ -51,43 +73,277 @@ fn to_screen_space<const WIDTH: usize, const HEIGHT: usize>(
         .collect()
 }
 
-pub fn rasterize_scene<const WIDTH: usize, const HEIGHT: usize>(
-    data: &mut SceneData<WIDTH, HEIGHT>,
-    buffer: &mut RenderBuffer<WIDTH, HEIGHT>,
+// Calls `f` with the buffer index, position, barycentric weights and depth of every
+// pixel covered by the triangle.
+fn for_each_fragment(
+    d: &FaceData2D,
+    inv_depth: &Tri<f32>,
+    buffer: &RenderBuffer,
+    mut f: impl FnMut(usize, Float2, &Tri<f32>, f32),
 ) {
+    let width = buffer.width();
+    let (start_x, start_y, end_x, end_y) = d.vertices.bbox(width, buffer.height());
+
+    for y in start_y..=end_y {
+        for x in start_x..=end_x {
+            let p = Float2::new(x as f32, y as f32);
+
+            // Check if the point is inside the triangle
+            if let Some(weights) = d.vertices.to_barycentric(p) {
+                let depth = 1.0 / (&weights * inv_depth).sum();
+                f(y * width + x, p, &weights, depth);
+            }
+        }
+    }
+}
+
+// Blue for a single shaded fragment, through green, to red at OVERDRAW_HEATMAP_MAX.
+fn overdraw_color(count: u32) -> Float3 {
+    if count == 0 {
+        return Float3::ZERO;
+    }
+
+    let t = ((count - 1) as f32 / (OVERDRAW_HEATMAP_MAX - 1) as f32).min(1.0);
+    if t < 0.5 {
+        Float3::new(0.0, 2.0 * t, 1.0 - 2.0 * t)
+    } else {
+        Float3::new(2.0 * t - 1.0, 2.0 - 2.0 * t, 0.0)
+    }
+}
+
+// Draws the triangle edges or vertices, depth-tested against the filled geometry.
+fn draw_topology(d: &FaceData2D, mode: PolygonMode, buffer: &RenderBuffer) {
+    match mode {
+        PolygonMode::Wireframe | PolygonMode::FillWireframe => {
+            for i in 0..3 {
+                let j = (i + 1) % 3;
+                let depths = (d.depths[i], d.depths[j]);
+                buffer.draw_line(d.vertices[i], d.vertices[j], depths, WIRE_COLOR);
+            }
+        }
+        PolygonMode::Points => {
+            for i in 0..3 {
+                buffer.draw_point(d.vertices[i], d.depths[i], POINT_SIZE, WIRE_COLOR);
+            }
+        }
+        PolygonMode::Fill => {}
+    }
+}
+
+// An entity's screen-space triangles, ready for the raster passes
+struct ScreenBatch<'a> {
+    entity: &'a Entity,
+    mode: PolygonMode,
+    tris: Vec<FaceData2D>,
+    stats: EntityStats,
+}
+
+impl ScreenBatch<'_> {
+    fn filled(&self) -> bool {
+        matches!(self.mode, PolygonMode::Fill | PolygonMode::FillWireframe)
+    }
+}
+
+pub fn rasterize_scene(
+    data: &mut SceneData,
+    buffer: &mut RenderBuffer,
+    settings: &RenderSettings,
+) -> RenderStats {
     let globals = &data.globals;
+    let counters = FrameCounters::default();
+    let mut stats = RenderStats::default();
+
+    // Every entity is drawn with the poses from the start of the frame
+    let poses = PoseSnapshot::capture(&data.cam_pose);
+    let cam_id = data.cam_pose.read().id();
+
+    let mut batches: Vec<_> = data
+        .entities
+        .iter()
+        .map(|(name, entity)| {
+            let start = Instant::now();
+            let vert_to_cam = poses
+                .relative_transform(entity.pose.read().id(), cam_id)
+                .unwrap_or_else(|| PoseGraph::relative_transform(&entity.pose, &data.cam_pose));
+            let tris = to_screen_space(entity, data.cam_model, vert_to_cam, &poses, &counters);
+            stats.triangles_submitted += entity.mesh.data.len();
+            stats.rasterized += tris.len();
+
+            ScreenBatch {
+                entity,
+                mode: entity.polygon_mode.unwrap_or(settings.polygon_mode),
+                tris,
+                stats: EntityStats {
+                    name: name.clone(),
+                    transform_time: start.elapsed(),
+                    ..Default::default()
+                },
+            }
+        })
+        .collect();
 
-    for entity in data.entities.values() {
-        let screen_tris = to_screen_space(entity, data.cam_model, data.cam_pose.clone());
-        let shader = Arc::clone(&entity.shader);
+    // Depth-only pass, so that the shading pass only runs for the nearest fragment.
+    if settings.depth_prepass {
+        for batch in batches.iter_mut().filter(|b| b.filled()) {
+            let start = Instant::now();
+            batch.tris.par_iter().for_each(|d| {
+                let inv_depth = &Tri::new(1.0, 1.0, 1.0) / &d.depths;
+                for_each_fragment(d, &inv_depth, buffer, |idx, _, _, depth| {
+                    let mut pixel = buffer.pixels[idx].lock();
+                    pixel.1 = pixel.1.min(depth);
+                });
+            });
+            batch.stats.raster_time += start.elapsed();
+        }
+    }
+
+    let overdraw: Vec<AtomicU32> = match settings.debug_view {
+        DebugView::Overdraw => (0..buffer.pixels.len())
+            .map(|_| AtomicU32::new(0))
+            .collect(),
+        DebugView::Shaded => Vec::new(),
+    };
+
+    for batch in batches.iter_mut().filter(|b| b.filled()) {
+        let start = Instant::now();
+        let shader = Arc::clone(&batch.entity.shader);
         let shade_fn = move |p, uv, norm, depth| shader.pixel_color(p, uv, norm, depth, globals);
 
-        screen_tris.par_iter().for_each(|d| {
-            let (start_x, start_y, end_x, end_y) = d.vertices.bbox::<WIDTH, HEIGHT>();
+        batch.tris.par_iter().for_each(|d| {
             let inv_depth = &Tri::new(1.0, 1.0, 1.0) / &d.depths;
             let scaled_uv = &d.uvs * &inv_depth;
             let scaled_norms = &d.normals * &inv_depth;
+            let (mut shaded, mut failed) = (0, 0);
 
-            for y in start_y..=end_y {
-                for x in start_x..=end_x {
-                    let p = Float2::new(x as f32, y as f32);
-                    let weights = d.vertices.to_barycentric(p);
-
-                    // Check if the point is inside the triangle
-                    if let Some(weights) = weights {
-                        let depth = 1.0 / (&weights * &inv_depth).sum();
-                        let idx = y * WIDTH + x;
-
-                        let mut pixel = buffer.pixels[idx].lock();
-                        if depth < pixel.1 {
-                            // Only update if unoccluded
-                            let uv = ((&scaled_uv * &weights).sum()) * depth;
-                            let norm = ((&scaled_norms * &weights).sum()) * depth;
-                            *pixel = (shade_fn(p, uv, norm, depth), depth);
-                        }
+            for_each_fragment(d, &inv_depth, buffer, |idx, p, weights, depth| {
+                let mut pixel = buffer.pixels[idx].lock();
+                let visible = match settings.depth_prepass {
+                    true => depth <= pixel.1,
+                    false => depth < pixel.1,
+                };
+
+                // Only update if unoccluded
+                if visible {
+                    let uv = ((&scaled_uv * weights).sum()) * depth;
+                    let norm = ((&scaled_norms * weights).sum()) * depth;
+
+                    // After a pre-pass, nudge the depth so coplanar fragments are not shaded again.
+                    let stored_depth = match settings.depth_prepass {
+                        true => depth.next_down(),
+                        false => depth,
+                    };
+                    *pixel = (shade_fn(p, uv, norm, depth), stored_depth);
+                    shaded += 1;
+
+                    if let Some(count) = overdraw.get(idx) {
+                        count.fetch_add(1, Ordering::Relaxed);
                     }
+                } else {
+                    failed += 1;
                 }
-            }
+            });
+
+            counters.pixels_shaded.add(shaded);
+            counters.depth_test_failures.add(failed);
         });
+        batch.stats.raster_time += start.elapsed();
+    }
+
+    // Lines and points go last, so they are depth-tested against every filled entity.
+    for batch in batches.iter_mut().filter(|b| b.mode != PolygonMode::Fill) {
+        let start = Instant::now();
+        batch
+            .tris
+            .par_iter()
+            .for_each(|d| draw_topology(d, batch.mode, buffer));
+        batch.stats.raster_time += start.elapsed();
+    }
+
+    if settings.debug_view == DebugView::Overdraw {
+        buffer
+            .pixels
+            .par_iter()
+            .zip(overdraw.par_iter())
+            .for_each(|(pixel, count)| {
+                pixel.lock().0 = overdraw_color(count.load(Ordering::Relaxed));
+            });
+    }
+
+    counters.write_to(&mut stats);
+    stats.entities = batches.into_iter().map(|b| b.stats).collect();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{face, scene_with_faces};
+
+    const WIDTH: usize = 32;
+    const HEIGHT: usize = 24;
+
+    // A large far triangle, a smaller one in front, and a coplanar copy of the smaller
+    // one in another color, drawn in that order
+    fn scene() -> SceneData {
+        scene_with_faces(
+            vec![
+                face(-5.0, 4.0, Float3::new(1.0, 0.0, 0.0)),
+                face(-3.0, 1.0, Float3::new(0.0, 1.0, 0.0)),
+                face(-3.0, 1.0, Float3::new(0.0, 0.0, 1.0)),
+            ],
+            WIDTH,
+            HEIGHT,
+        )
+    }
+
+    fn render(depth_prepass: bool, debug_view: DebugView) -> (RenderBuffer, RenderStats) {
+        let mut data = scene();
+        let mut buffer = RenderBuffer::new(WIDTH, HEIGHT);
+        let settings = RenderSettings {
+            depth_prepass,
+            debug_view,
+            ..Default::default()
+        };
+
+        // A single worker keeps the triangles in order, so overdraw is deterministic
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let stats = pool.install(|| rasterize_scene(&mut data, &mut buffer, &settings));
+        (buffer, stats)
+    }
+
+    fn colors(buffer: &RenderBuffer) -> Vec<Float3> {
+        buffer.pixels.iter().map(|p| p.lock().0).collect()
+    }
+
+    #[test]
+    fn test_prepass_keeps_colors() {
+        let (plain, plain_stats) = render(false, DebugView::Shaded);
+        let (prepass, prepass_stats) = render(true, DebugView::Shaded);
+        assert_eq!(colors(&plain), colors(&prepass));
+
+        // The nearer triangle covers the center, its coplanar copy never wins
+        let center = plain.pixels[HEIGHT / 2 * WIDTH + WIDTH / 2].lock().0;
+        assert_eq!(center, Float3::new(0.0, 1.0, 0.0));
+        assert!(prepass_stats.pixels_shaded < plain_stats.pixels_shaded);
+    }
+
+    #[test]
+    fn test_overdraw_view() {
+        let center = HEIGHT / 2 * WIDTH + WIDTH / 2;
+        let (plain, _) = render(false, DebugView::Overdraw);
+        let (prepass, _) = render(true, DebugView::Overdraw);
+
+        // Far then near without the pre-pass, only the nearest with it
+        assert_eq!(plain.pixels[center].lock().0, overdraw_color(2));
+        assert!(
+            colors(&prepass)
+                .iter()
+                .all(|&c| c == Float3::ZERO || c == overdraw_color(1))
+        );
+        assert_eq!(prepass.pixels[center].lock().0, overdraw_color(1));
+        assert_eq!(prepass.pixels[0].lock().0, Float3::ZERO);
     }
 }
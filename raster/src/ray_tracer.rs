@@ -0,0 +1,199 @@
+use rayon::prelude::*;
+
+use std::borrow::Cow;
+use std::time::Instant;
+
+use engine::bvh::Bvh;
+use engine::entity::Entity;
+use engine::pose_graph::{PoseGraph, PoseSnapshot};
+use engine::primitives::{FaceData3D, Float2, Float3, RayHit, Transform};
+use engine::render_buffer::RenderBuffer;
+use engine::scene::SceneData;
+use engine::shader::ShaderGlobals;
+
+use crate::stats::{EntityStats, RenderStats};
+
+// An entity's deformed faces in its local space, with a BVH over them. Undeformed
+// meshes use the BVH cached on the mesh.
+struct TracedEntity<'a> {
+    entity: &'a Entity,
+    vert_to_cam: Transform,
+    faces: Cow<'a, [FaceData3D]>,
+    bvh: Cow<'a, Bvh>,
+}
+
+impl<'a> TracedEntity<'a> {
+    fn new(entity: &'a Entity, vert_to_cam: Transform, poses: &PoseSnapshot) -> Self {
+        let faces = entity.deformed_faces(Some(poses));
+        let bvh = match &faces {
+            Cow::Borrowed(_) => Cow::Borrowed(entity.mesh.bvh()),
+            Cow::Owned(faces) => Cow::Owned(Bvh::new(faces)),
+        };
+
+        TracedEntity {
+            entity,
+            vert_to_cam,
+            faces,
+            bvh,
+        }
+    }
+}
+
+// Reference renderer for the rasterizer. Casts one ray per pixel, through the same pixel
+// positions the rasterizer samples, and shades the closest hit with the entity's own
+// shader. Back faces are ignored, like the rasterizer culls them. Triangles crossing the
+// near plane are kept, where the rasterizer drops them.
+pub fn trace_scene(data: &SceneData, buffer: &mut RenderBuffer) -> RenderStats {
+    let globals = &data.globals;
+    let mut stats = RenderStats::default();
+    let poses = PoseSnapshot::capture(&data.cam_pose);
+    let cam_id = data.cam_pose.read().id();
+
+    let (entities, entity_stats): (Vec<_>, Vec<_>) = data
+        .entities
+        .iter()
+        .map(|(name, entity)| {
+            let start = Instant::now();
+            let vert_to_cam = poses
+                .relative_transform(entity.pose.read().id(), cam_id)
+                .unwrap_or_else(|| PoseGraph::relative_transform(&entity.pose, &data.cam_pose));
+            let traced = TracedEntity::new(entity, vert_to_cam, &poses);
+            let stats = EntityStats {
+                name: name.clone(),
+                transform_time: start.elapsed(),
+                ..Default::default()
+            };
+            (traced, stats)
+        })
+        .unzip();
+    stats.triangles_submitted = entities.iter().map(|e| e.faces.len()).sum();
+
+    let start = Instant::now();
+    let width = buffer.width();
+    let cam_model = &data.cam_model;
+    stats.pixels_shaded = buffer
+        .pixels
+        .par_iter()
+        .enumerate()
+        .filter(|(idx, pixel)| {
+            let p = Float2::new((idx % width) as f32, (idx / width) as f32);
+            let ray = cam_model.screen_to_ray(p);
+
+            // Distances along the ray are the same in every entity's space
+            let closest = entities
+                .iter()
+                .filter_map(|e| {
+                    let local = ray.transformed_inv(&e.vert_to_cam);
+                    Some((e, e.bvh.intersect_ray_front(&local, f32::INFINITY)?))
+                })
+                .min_by(|(_, a), (_, b)| a.t.total_cmp(&b.t));
+            let Some((e, hit)) = closest else {
+                return false;
+            };
+
+            let depth = -ray.at(hit.t).z;
+            let mut pixel = pixel.lock();
+            if depth >= pixel.1 {
+                return false;
+            }
+            *pixel = (shade(e, &hit, p, depth, globals), depth);
+            true
+        })
+        .count();
+
+    stats.trace_time = start.elapsed();
+    stats.entities = entity_stats;
+    stats
+}
+
+// Interpolates the hit face's attributes like the rasterizer does, with normals
+// rotated into camera space but not renormalized
+fn shade(
+    e: &TracedEntity,
+    hit: &RayHit,
+    pixel: Float2,
+    depth: f32,
+    globals: &ShaderGlobals,
+) -> Float3 {
+    let face = &e.faces[hit.face];
+    let weights = &hit.barycentric;
+    let uv = (&face.uvs * weights).sum();
+    let normal = e.vert_to_cam.rotation * (&face.normals * weights).sum();
+    e.entity
+        .shader
+        .pixel_color(pixel, uv, normal, depth, globals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::{RenderSettings, rasterize_scene};
+    use crate::test_util::cube_scene;
+    use engine::primitives::{Quaternion, VectorOps};
+
+    const WIDTH: usize = 160;
+    const HEIGHT: usize = 120;
+
+    // A turned cube in front of another one, so several faces and an occlusion edge show
+    fn scene() -> SceneData {
+        let cube = |position: Float3, angle: f32| Transform {
+            position,
+            rotation: Quaternion::from_y_angle(angle) * Quaternion::from_x_angle(0.4),
+            ..Default::default()
+        };
+        cube_scene(
+            &[
+                ("near", cube(Float3::new(-0.5, 0.0, -5.0), 0.6)),
+                ("far", cube(Float3::new(1.0, 0.5, -8.0), -0.3)),
+            ],
+            WIDTH,
+            HEIGHT,
+        )
+    }
+
+    #[test]
+    fn test_matches_rasterizer() {
+        let mut data = scene();
+        let mut raster = RenderBuffer::new(WIDTH, HEIGHT);
+        let mut traced = RenderBuffer::new(WIDTH, HEIGHT);
+        rasterize_scene(&mut data, &mut raster, &RenderSettings::default());
+        let stats = trace_scene(&data, &mut traced);
+        assert_eq!(stats.triangles_submitted, 24);
+        assert_eq!(stats.rasterized, 0);
+
+        // Entities only report their setup, the tracing is timed for the whole scene
+        let mut names: Vec<_> = stats.entities.iter().map(|e| e.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["far", "near"]);
+        assert!(stats.entities.iter().all(|e| !e.transform_time.is_zero()));
+        assert!(stats.raster_time().is_zero() && !stats.trace_time.is_zero());
+
+        // Away from edges, both see the same surface at the same depth with the same color
+        let (mut covered, mut mismatched) = (0, 0);
+        for (a, b) in raster.pixels.iter().zip(&traced.pixels) {
+            let (a, b) = (*a.lock(), *b.lock());
+            match (a.1.is_finite(), b.1.is_finite()) {
+                (true, true) => {
+                    covered += 1;
+                    let same =
+                        (a.1 - b.1).abs() < 1e-3 * a.1 && VectorOps::approx_eq(a.0, b.0, 1e-3);
+                    mismatched += usize::from(!same);
+                }
+                (false, false) => {}
+                _ => mismatched += 1,
+            }
+        }
+        assert_eq!(stats.pixels_shaded, covered + traced_only(&raster, &traced));
+        assert!(covered > 1000);
+        assert!(mismatched * 20 < covered, "{mismatched} of {covered}");
+    }
+
+    fn traced_only(raster: &RenderBuffer, traced: &RenderBuffer) -> usize {
+        raster
+            .pixels
+            .iter()
+            .zip(&traced.pixels)
+            .filter(|(a, b)| !a.lock().1.is_finite() && b.lock().1.is_finite())
+            .count()
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default)]
+pub struct EntityStats {
+    pub name: String,
+    pub transform_time: Duration, // Vertex transform, clipping and culling
+    pub raster_time: Duration,    // Every raster pass, including the depth pre-pass
+}
+
+// Counters for a single call to `rasterize_scene` or `trace_scene`.
+// Depth test failures are only counted in the shading pass, not in the pre-pass.
+// The tracers don't rasterize, clip or cull, and report their time for the whole scene.
+#[derive(Debug, Clone, Default)]
+pub struct RenderStats {
+    pub triangles_submitted: usize,
+    pub near_clipped: usize,
+    pub backface_culled: usize,
+    pub rasterized: usize,
+    pub pixels_shaded: usize,
+    pub depth_test_failures: usize,
+    pub trace_time: Duration,
+    pub entities: Vec<EntityStats>,
+}
+
+impl RenderStats {
+    pub fn transform_time(&self) -> Duration {
+        self.entities.iter().map(|e| e.transform_time).sum()
+    }
+
+    pub fn raster_time(&self) -> Duration {
+        self.entities.iter().map(|e| e.raster_time).sum()
+    }
+}
+
+// Can be added to from several rayon workers at once.
+#[derive(Debug, Default)]
+pub(crate) struct Counter(AtomicUsize);
+
+impl Counter {
+    pub fn add(&self, n: usize) {
+        if n > 0 {
+            self.0.fetch_add(n, Ordering::Relaxed);
+        }
+    }
+
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Shared between the rayon workers while rendering.
+#[derive(Debug, Default)]
+pub(crate) struct FrameCounters {
+    pub near_clipped: Counter,
+    pub backface_culled: Counter,
+    pub pixels_shaded: Counter,
+    pub depth_test_failures: Counter,
+}
+
+impl FrameCounters {
+    pub fn write_to(&self, stats: &mut RenderStats) {
+        stats.near_clipped = self.near_clipped.get();
+        stats.backface_culled = self.backface_culled.get();
+        stats.pixels_shaded = self.pixels_shaded.get();
+        stats.depth_test_failures = self.depth_test_failures.get();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::{RenderSettings, rasterize_scene};
+    use crate::test_util::{face, scene_with_faces};
+    use engine::primitives::{Float3, Tri};
+    use engine::render_buffer::RenderBuffer;
+
+    const WIDTH: usize = 32;
+    const HEIGHT: usize = 24;
+
+    #[test]
+    fn test_counter() {
+        use rayon::prelude::*;
+
+        let counter = Counter::default();
+        (0..100).into_par_iter().for_each(|i| counter.add(i % 3));
+        assert_eq!(counter.get(), 99);
+    }
+
+    #[test]
+    fn test_counters() {
+        let visible = face(-3.0, 1.0, Float3::Z);
+        let mut back = face(-3.0, 1.0, Float3::Z);
+        back.vertices = Tri::new(back.vertices[0], back.vertices[2], back.vertices[1]);
+        let mut clipped = face(-3.0, 1.0, Float3::Z);
+        clipped.vertices.vertices[2].z = 1.0; // Behind the camera
+        let mut data = scene_with_faces(vec![visible, back, clipped], WIDTH, HEIGHT);
+
+        let mut buffer = RenderBuffer::new(WIDTH, HEIGHT);
+        let settings = RenderSettings::default();
+        let stats = rasterize_scene(&mut data, &mut buffer, &settings);
+        assert_eq!(stats.triangles_submitted, 3);
+        assert_eq!(stats.rasterized, 1);
+        assert_eq!(stats.backface_culled, 1);
+        assert_eq!(stats.near_clipped, 1);
+        assert_eq!(
+            stats.triangles_submitted,
+            stats.rasterized + stats.backface_culled + stats.near_clipped
+        );
+
+        let covered = buffer
+            .pixels
+            .iter()
+            .filter(|p| p.lock().1.is_finite())
+            .count();
+        assert!(covered > 0);
+        assert_eq!(stats.pixels_shaded, covered);
+        assert_eq!(stats.depth_test_failures, 0);
+        assert_eq!(stats.entities.len(), 1);
+
+        // Drawn again at the same depths, every fragment fails
+        let stats = rasterize_scene(&mut data, &mut buffer, &settings);
+        assert_eq!(stats.pixels_shaded, 0);
+        assert_eq!(stats.depth_test_failures, covered);
+    }
+}
@@ -1,64 +1,150 @@
-use engine::input::{Input, Key, MouseKey};
+use engine::actions::ActionMap;
+use engine::camera::{look_angles, look_rotation};
+use engine::input::Input;
 use engine::pose_graph::SharedPGNode;
-use engine::primitives::{Float3, Quaternion, VectorOps};
+use engine::primitives::VectorOps;
 
 const SENSITIVITY: f32 = 2.0;
 const CAM_MOVE_SPEED: f32 = 15.0;
-const WASD_MOVE_SPEED: f32 = 5.0;
+const MOVE_SPEED: f32 = 5.0;
 const MAX_PITCH: f32 = f32::to_radians(85.0);
 const MIN_PITCH: f32 = f32::to_radians(-85.0);
 
+// Uses the "look" and "release_cursor" actions, and the "move_forward", "move_right",
+// "look_x" and "look_y" axes
 #[derive(Debug, Default)]
-pub struct CamController<const WIDTH: usize, const HEIGHT: usize> {
+pub struct CamController {
     pose: SharedPGNode,
+    actions: ActionMap,
     pitch_tgt: f32,
     yaw_tgt: f32,
 }
 
-impl<const WIDTH: usize, const HEIGHT: usize> CamController<WIDTH, HEIGHT> {
-    pub fn new(pose: SharedPGNode) -> Self {
+impl CamController {
+    pub fn new(pose: SharedPGNode, actions: ActionMap) -> Self {
         Self {
             pose,
+            actions,
             pitch_tgt: 0.0,
             yaw_tgt: 0.0,
         }
     }
 
-    pub fn update_camera(&mut self, time_delta: f32, input: &mut Input) {
+    /// Takes over the current orientation, e.g. after another controller moved the camera
+    pub fn sync_with_pose(&mut self) {
+        let (yaw, pitch) = look_angles(self.pose.read().transform().forward_vec());
+        self.yaw_tgt = yaw;
+        self.pitch_tgt = pitch.clamp(MIN_PITCH, MAX_PITCH);
+    }
+
+    pub fn update_camera(&mut self, time_delta: f32, input: &mut dyn Input) {
         // Mouse Look
-        if input.is_mouse_held(MouseKey::Left) {
-            let mouse_delta = (input.get_mouse_delta() * SENSITIVITY) / WIDTH as f32;
-            self.yaw_tgt -= mouse_delta.x;
-            self.pitch_tgt = (self.pitch_tgt - mouse_delta.y).clamp(MIN_PITCH, MAX_PITCH);
+        let actions = &self.actions;
+        if actions.action_held(input, "look") {
+            let rad_per_pixel = SENSITIVITY / input.screen_size().x;
+            self.yaw_tgt -= actions.axis_value(input, "look_x") * rad_per_pixel;
+            self.pitch_tgt = (self.pitch_tgt - actions.axis_value(input, "look_y") * rad_per_pixel)
+                .clamp(MIN_PITCH, MAX_PITCH);
             input.lock_cursor();
-        } else if input.is_key_down_this_frame(Key::Q) {
+        } else if actions.action_pressed(input, "release_cursor") {
             input.unlock_cursor();
         }
 
-        let mut pose = self.pose.borrow_mut();
-        let yaw_rot = Quaternion::from_y_angle(self.yaw_tgt);
-        let pitch_rot = Quaternion::from_x_angle(self.pitch_tgt);
-        pose.slerp_rotation(yaw_rot * pitch_rot, time_delta * CAM_MOVE_SPEED);
+        let mut pose = self.pose.write();
+        // Long frames snap straight to the target
+        let t = (time_delta * CAM_MOVE_SPEED).min(1.0);
+        pose.slerp_rotation(look_rotation(self.yaw_tgt, self.pitch_tgt), t);
 
-        // WASD Movement
-        let mut move_delta = Float3::ZERO;
-        let forward = pose.transform.forward_vec();
-        let right = pose.transform.right_vec();
+        // Movement
+        let forward = pose.transform().forward_vec();
+        let right = pose.transform().right_vec();
+        let mut move_delta = forward * actions.axis_value(input, "move_forward")
+            + right * actions.axis_value(input, "move_right");
 
-        if input.is_key_held(Key::W) {
-            move_delta += forward;
-        }
-        if input.is_key_held(Key::S) {
-            move_delta -= forward;
-        }
-        if input.is_key_held(Key::D) {
-            move_delta += right;
+        move_delta = move_delta.normalized() * MOVE_SPEED * time_delta;
+        pose.apply_translation(move_delta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::input::{InputFrame, Key, MouseKey, ScriptedInput};
+    use engine::pose_graph::PoseGraph;
+    use engine::primitives::{Float2, Float3, Quaternion};
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn controller(pose: &SharedPGNode) -> CamController {
+        let actions = ActionMap::from_file("../resources/input/camera.ron").unwrap();
+        CamController::new(pose.clone(), actions)
+    }
+
+    fn frame() -> InputFrame {
+        InputFrame::default().with_screen_size(480.0, 270.0)
+    }
+
+    // Runs the controller until the script ends, plus `settle` frames without input
+    fn run(controller: &mut CamController, input: &mut ScriptedInput, settle: usize) {
+        while input.next_frame() {
+            controller.update_camera(DT, input);
         }
-        if input.is_key_held(Key::A) {
-            move_delta -= right;
+        for _ in 0..settle {
+            controller.update_camera(DT, input);
         }
+    }
 
-        move_delta = move_delta.normalized() * WASD_MOVE_SPEED * time_delta;
-        pose.apply_translation(move_delta);
+    #[test]
+    fn test_wasd_moves_along_view() {
+        let pose = PoseGraph::root();
+        let mut controller = controller(&pose);
+        let mut input = ScriptedInput::new(vec![frame().with_key_held(Key::W); 30]);
+        run(&mut controller, &mut input, 0);
+
+        let expected = Float3::FORWARD * MOVE_SPEED * 0.5;
+        assert!(VectorOps::approx_eq(
+            pose.read().transform().position,
+            expected,
+            1e-4
+        ));
+    }
+
+    #[test]
+    fn test_mouse_look() {
+        let pose = PoseGraph::root();
+        let mut controller = controller(&pose);
+
+        // Dragging right by a quarter of the screen width turns right by half a radian
+        let drag = frame()
+            .with_mouse_pressed(MouseKey::Left)
+            .with_mouse(Float2::ZERO, Float2::new(120.0, 0.0));
+        let mut input = ScriptedInput::new([drag]);
+        run(&mut controller, &mut input, 120);
+        assert!(input.is_cursor_locked());
+
+        let forward = pose.read().transform().forward_vec();
+        let expected = Quaternion::from_y_angle(-0.5) * Float3::FORWARD;
+        assert!(VectorOps::approx_eq(forward, expected, 1e-4));
+        assert!(forward.x > 0.0);
+
+        // Q releases the cursor
+        let mut input = ScriptedInput::new([frame().with_key_pressed(Key::Q)]);
+        input.lock_cursor();
+        run(&mut controller, &mut input, 0);
+        assert!(!input.is_cursor_locked());
+    }
+
+    #[test]
+    fn test_pitch_clamped() {
+        let pose = PoseGraph::root();
+        let mut controller = controller(&pose);
+        let drag = frame()
+            .with_mouse_held(MouseKey::Left)
+            .with_mouse(Float2::ZERO, Float2::new(0.0, -2000.0));
+        let mut input = ScriptedInput::new([drag]);
+        run(&mut controller, &mut input, 240);
+
+        let up = pose.read().transform().forward_vec().y;
+        assert!((up - MAX_PITCH.sin()).abs() < 1e-3);
     }
 }
@@ -1,4 +1,4 @@
-pub use scene::TestScene;
+pub use scene::{Backend, TestScene};
 
 mod cam_controller;
 mod scene;
@@ -1,104 +1,505 @@
+use std::f32::consts::FRAC_PI_2;
 use std::sync::Arc;
 
-use engine::camera::CameraModel;
-use engine::coords::ENGINE;
-use engine::entity::Entity;
-use engine::input::Input;
+use engine::actions::ActionMap;
+use engine::animation::{
+    AnimationClip, AnimationPlayer, Interpolation, LoopMode, MorphClip, Track,
+};
+use engine::camera_path::CameraPath;
+use engine::entity::PolygonMode;
+use engine::input::{Input, Key, MouseKey};
 use engine::mesh::Mesh;
+use engine::morph::{MorphTarget, SharedMorphWeights};
+use engine::orbit::OrbitController;
+use engine::picking::{PickHit, pick};
 use engine::pose_graph::{PoseGraph, SharedPGNode};
-use engine::primitives::{Float3, Quaternion};
+use engine::primitives::{Float2, Float3, Quaternion};
 use engine::render_buffer::RenderBuffer;
+use engine::render_scale::{RenderScale, UpscaleFilter};
 use engine::scene::{Scene, SceneData};
-use engine::shader::{DepthShader, LitTextureShader, NormalShader};
-use engine::texture::Texture;
+use engine::scene_file::SceneDesc;
 
 use super::cam_controller::CamController;
-use crate::raster::rasterize_scene;
+use crate::path_tracer::PathTracer;
+use crate::raster::{DebugView, RenderSettings, rasterize_scene};
+use crate::ray_tracer::trace_scene;
+use crate::stats::RenderStats;
+
+const GIZMO_COLOR: Float3 = Float3::new(0.2, 1.0, 1.0);
+const SELECTION_COLOR: Float3 = Float3::new(1.0, 0.8, 0.1);
+const HUD_POS: Float2 = Float2::new(10.0, 10.0);
+const SPIN_PERIOD: f32 = 12.0; // Seconds per turn
+const RENDER_SCALES: [f32; 4] = [0.25, 0.5, 1.0, 2.0];
+const ORBIT_DISTANCE: f32 = 5.0; // In front of the camera, when switching to orbiting
+const TARGET_FRAME_TIME: f32 = 1.0 / 30.0; // For dynamic resolution
+const DEFAULT_WIDTH: usize = 960; // Until the window reports its size
+const DEFAULT_HEIGHT: usize = 540;
+const SCENE_FILE: &str = "resources/scenes/test_scene.ron";
+const BINDINGS_FILE: &str = "resources/input/camera.ron";
+const FLYTHROUGH_FILE: &str = "resources/paths/flythrough.ron";
+const SAVED_SCENE_FILE: &str = "resources/scenes/saved_scene.ron";
+
+// Loops a full turn around the Y axis, applied on top of `base`.
+// A negative period spins the other way.
+fn spin_clip(base: Quaternion, period: f32) -> AnimationClip {
+    let mut track = Track::new(Interpolation::Linear);
+    for i in 0..=4 {
+        let angle = i as f32 * FRAC_PI_2 * period.signum();
+        let time = i as f32 * period.abs() / 4.0;
+        track.insert(time, base * Quaternion::from_y_angle(angle));
+    }
+
+    AnimationClip {
+        rotation: Some(track),
+        loop_mode: LoopMode::Loop,
+        ..Default::default()
+    }
+}
+
+// Bends a joint back and forth around the Z axis
+fn bend_clip(angle_rad: f32, period: f32) -> AnimationClip {
+    let track = Track::new(Interpolation::Cubic)
+        .with_key(0.0, Quaternion::from_z_angle(-angle_rad))
+        .with_key(period / 4.0, Quaternion::IDENTITY)
+        .with_key(period / 2.0, Quaternion::from_z_angle(angle_rad));
+
+    AnimationClip {
+        rotation: Some(track),
+        loop_mode: LoopMode::PingPong,
+        ..Default::default()
+    }
+}
+
+// Flattens the mesh vertically and widens it to keep the volume, for a morph target
+fn squashed(mesh: &Mesh, amount: f32) -> Mesh {
+    let scale = Float3::new(1.0 + amount * 0.5, 1.0 - amount, 1.0 + amount * 0.5);
+    let mut out = mesh.clone();
+    for face in &mut out.data {
+        for v in &mut face.vertices.vertices {
+            *v *= scale;
+        }
+    }
+    out
+}
+
+// Squashes on landing, at the bottom of `bob_clip` with the same period
+fn squash_clip(period: f32) -> MorphClip {
+    let track = Track::new(Interpolation::Cubic)
+        .with_key(0.0, 1.0)
+        .with_key(period / 8.0, 0.0)
+        .with_key(period / 2.0, 0.0);
+
+    MorphClip {
+        weights: vec![Some(track)],
+        loop_mode: LoopMode::PingPong,
+    }
+}
+
+// Eases up and down around `pos`
+fn bob_clip(pos: Float3, height: f32, period: f32) -> AnimationClip {
+    let track = Track::new(Interpolation::Cubic)
+        .with_key(0.0, pos)
+        .with_key(period / 4.0, pos + Float3::Y * height * 0.5)
+        .with_key(period / 2.0, pos + Float3::Y * height);
+
+    AnimationClip {
+        position: Some(track),
+        loop_mode: LoopMode::PingPong,
+        ..Default::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    Raster,
+    RayTrace,  // Reference for the rasterizer, ignores the polygon mode and debug views
+    PathTrace, // Global illumination from the entity materials, animations are paused
+}
 
 #[derive(Debug)]
-pub struct TestScene<const WIDTH: usize, const HEIGHT: usize> {
-    data: SceneData<WIDTH, HEIGHT>,
-    cam_controller: CamController<WIDTH, HEIGHT>,
+pub struct TestScene {
+    data: SceneData,
+    cam_controller: CamController,
+    orbit: Option<OrbitController>, // Replaces the free camera while set
+    framed: usize,                  // Entity that was framed last, by name order
+    flythrough_path: Arc<CameraPath>,
+    flythrough: Option<AnimationPlayer>, // Drives the camera while playing
+    selected: Option<PickHit>,
     sun_pose: SharedPGNode,
+    animations: AnimationPlayer,
+    settings: RenderSettings,
+    backend: Backend,
+    path_tracer: PathTracer, // Accumulates while the camera stands still
+    render_scale: RenderScale,
+    show_gizmos: bool,
+    show_hud: bool,
+    stats: RenderStats, // From the last rendered frame
 }
 
-impl<const WIDTH: usize, const HEIGHT: usize> Default for TestScene<WIDTH, HEIGHT> {
+impl Default for TestScene {
     fn default() -> Self {
-        // Setup pose graph
-        let root = PoseGraph::root();
-        let dagger1_pose = PoseGraph::new("dagger1", root.clone());
-        let dagger2_pose = PoseGraph::new("dagger1", root.clone());
-        let dave_pose = PoseGraph::new("dave", dagger1_pose.clone());
-        let cam_pose = PoseGraph::new("cam", root.clone());
-
-        let sun_pose = PoseGraph::new("sun", root.clone());
-        let sun_elev = Quaternion::from_x_angle(-f32::to_radians(45.0));
-        let sun_azim = Quaternion::from_y_angle(f32::to_radians(30.0));
-
-        sun_pose.borrow_mut().apply_rotation(sun_elev * sun_azim);
-        dagger1_pose
-            .borrow_mut()
-            .apply_translation(Float3::new(2.5, -4.0, -10.0));
-        dagger2_pose
-            .borrow_mut()
-            .apply_translation(Float3::new(-2.5, -4.0, -10.0));
-        dave_pose
-            .borrow_mut()
-            .apply_translation(Float3::new(0.0, 15.0, 0.0));
-
-        // Load meshes
-        let dagger_mesh =
-            Arc::new(Mesh::from_obj_file("resources/models/dagger.obj", ENGINE).unwrap());
-        let dave_mesh = Arc::new(Mesh::from_obj_file("resources/models/dave.obj", ENGINE).unwrap());
-
-        // Load shaders
-        let dagger_texture = Texture::from_file("resources/textures/dagger.png").unwrap();
-        let dagger1_shader = Arc::new(LitTextureShader::new(dagger_texture));
-        let dagger2_shader = Arc::new(NormalShader());
-        let dave_shader = Arc::new(DepthShader());
-
-        // Create entities
-        let dagger1 = Entity::new(dagger1_pose, dagger_mesh.clone(), dagger1_shader.clone());
-        let dagger2 = Entity::new(dagger2_pose, dagger_mesh.clone(), dagger2_shader.clone());
-        let dave = Entity::new(dave_pose, dave_mesh.clone(), dave_shader.clone());
-
-        // Assemble scene data
-        let mut data = SceneData {
-            cam_model: CameraModel::new(60.0, true),
-            cam_pose: cam_pose.clone(),
-            ..Default::default()
-        };
-        data.entities.insert("dagger1".to_string(), dagger1);
-        data.entities.insert("dagger2".to_string(), dagger2);
-        data.entities.insert("dave".to_string(), dave);
+        let mut data = SceneDesc::from_file(SCENE_FILE)
+            .and_then(|desc| desc.build(DEFAULT_WIDTH, DEFAULT_HEIGHT))
+            .unwrap();
+        let root = PoseGraph::find_root(&data.cam_pose);
+        let node = |path: &str| PoseGraph::find(&root, path).unwrap();
+        let sun_pose = data.sun_pose.clone().unwrap();
+        let bindings = ActionMap::from_file(BINDINGS_FILE).unwrap();
+        let flythrough_path = CameraPath::from_file(FLYTHROUGH_FILE).unwrap();
+
+        // Morph targets are not part of the scene file
+        let dave_weights = SharedMorphWeights::default();
+        let dave = data.entities.get_mut("dave").unwrap();
+        let dave_mesh = Arc::make_mut(&mut dave.mesh);
+        let squash = MorphTarget::from_meshes("squash", dave_mesh, &squashed(dave_mesh, 0.3));
+        dave_mesh.add_morph_target(squash.unwrap()).unwrap();
+        dave.morph_weights = Some(dave_weights.clone());
+
+        // Animate
+        let sun_rotation = sun_pose.read().transform().rotation;
+        let dave_position = node("root/dagger1/dave").read().transform().position;
+        let mut animations = AnimationPlayer::default();
+        animations.bind(
+            node("root/dagger1"),
+            Arc::new(spin_clip(Quaternion::IDENTITY, SPIN_PERIOD)),
+        );
+        animations.bind(
+            sun_pose.clone(),
+            Arc::new(spin_clip(sun_rotation, -SPIN_PERIOD)),
+        );
+        animations.bind(
+            node("root/dagger1/dave"),
+            Arc::new(bob_clip(dave_position, 2.0, 4.0)),
+        );
+        animations.bind_morph(dave_weights, Arc::new(squash_clip(4.0)));
+        animations.bind(
+            node("root/tube/lower/upper"),
+            Arc::new(bend_clip(f32::to_radians(80.0), 6.0)),
+        );
 
         Self {
+            cam_controller: CamController::new(data.cam_pose.clone(), bindings),
+            orbit: None,
+            framed: 0,
+            flythrough_path: Arc::new(flythrough_path),
+            flythrough: None,
+            selected: None,
             data,
-            cam_controller: CamController::new(cam_pose),
             sun_pose,
+            animations,
+            settings: RenderSettings::default(),
+            backend: Backend::default(),
+            path_tracer: PathTracer::default(),
+            render_scale: RenderScale::default(),
+            show_gizmos: false,
+            show_hud: true,
+            stats: RenderStats::default(),
         }
     }
 }
 
-impl<const WIDTH: usize, const HEIGHT: usize> Scene<WIDTH, HEIGHT> for TestScene<WIDTH, HEIGHT> {
-    fn update_state(&mut self, time_delta: f32, input: &mut Input) {
-        let dagger1 = self.data.entities.get_mut("dagger1").unwrap();
-        dagger1
-            .pose
-            .borrow_mut()
-            .apply_rotation(Quaternion::from_y_angle(f32::to_radians(0.5)));
-        self.sun_pose
-            .borrow_mut()
-            .apply_rotation(Quaternion::from_y_angle(f32::to_radians(-0.5)));
+impl TestScene {
+    pub fn set_hud_visible(&mut self, visible: bool) {
+        self.show_hud = visible;
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    pub fn data(&self) -> &SceneData {
+        &self.data
+    }
+
+    fn toggle_orbit(&mut self) {
+        if self.orbit.take().is_some() {
+            self.cam_controller.sync_with_pose();
+            return;
+        }
+
+        let cam = PoseGraph::world_transform(&self.data.cam_pose);
+        let target = cam.position + cam.forward_vec() * ORBIT_DISTANCE;
+        let bindings = OrbitController::default_bindings();
+        self.orbit = Some(OrbitController::new(
+            self.data.cam_pose.clone(),
+            bindings,
+            target,
+        ));
+    }
 
-        self.cam_controller.update_camera(time_delta, input);
+    fn toggle_flythrough(&mut self) {
+        if self.flythrough.take().is_some() {
+            self.cam_controller.sync_with_pose();
+            return;
+        }
+
+        let mut player = AnimationPlayer::default();
+        player.bind_path(self.data.cam_pose.clone(), self.flythrough_path.clone());
+        self.flythrough = Some(player);
+        self.orbit = None;
+    }
+
+    // Selects the entity under the mouse, given in window pixels
+    fn select_at(&mut self, mouse: Float2, screen_size: Float2) {
+        let cam_model = &self.data.cam_model;
+        let render_size = Float2::new(cam_model.width() as f32, cam_model.height() as f32);
+        let pixel = mouse * render_size / screen_size;
+
+        self.selected = pick(&self.data, pixel);
+    }
+
+    // Orbits the next entity, fitting it into the view
+    fn frame_next_entity(&mut self) {
+        let mut names: Vec<_> = self.data.entities.keys().collect();
+        names.sort();
+        let Some(name) = names.get(self.framed % names.len().max(1)) else {
+            return;
+        };
+        let bounds = self.data.entities[*name].world_bounds();
+        self.framed += 1;
+
+        if self.orbit.is_none() {
+            self.toggle_orbit();
+        }
+        if let Some(orbit) = &mut self.orbit {
+            orbit.frame(&bounds, &self.data.cam_model);
+        }
+    }
+
+    fn queue_hud(&mut self, time_delta: f32) {
+        let stats = &self.stats;
+        let cam = PoseGraph::world_transform(&self.data.cam_pose).position;
+        let hud = [
+            format!(
+                "{:.1} ms ({:.0} fps)",
+                time_delta * 1000.0,
+                1.0 / time_delta
+            ),
+            format!(
+                "tris: {} submitted, {} drawn ({} clipped, {} culled)",
+                stats.triangles_submitted,
+                stats.rasterized,
+                stats.near_clipped,
+                stats.backface_culled
+            ),
+            format!(
+                "pixels: {} shaded, {} depth fails",
+                stats.pixels_shaded, stats.depth_test_failures
+            ),
+            format!(
+                "vertex: {:.2} ms raster: {:.2} ms trace: {:.2} ms",
+                stats.transform_time().as_secs_f32() * 1000.0,
+                stats.raster_time().as_secs_f32() * 1000.0,
+                stats.trace_time.as_secs_f32() * 1000.0
+            ),
+            format!(
+                "cam: ({:.1}, {:.1}, {:.1}) {}",
+                cam.x,
+                cam.y,
+                cam.z,
+                match (&self.flythrough, &self.orbit) {
+                    (Some(_), _) => "path",
+                    (None, Some(_)) => "orbit",
+                    (None, None) => "fly",
+                }
+            ),
+            format!(
+                "scale: {:.2}x {:?} dynamic: {}",
+                self.render_scale.scale(),
+                self.render_scale.filter,
+                self.render_scale.target_frame_time().is_some()
+            ),
+            format!(
+                "backend: {} prepass: {} view: {:?} mode: {:?}",
+                match self.backend {
+                    Backend::PathTrace => format!("PathTrace ({} spp)", self.path_tracer.samples()),
+                    backend => format!("{backend:?}"),
+                },
+                self.settings.depth_prepass,
+                self.settings.debug_view,
+                self.settings.polygon_mode
+            ),
+            match &self.selected {
+                Some(hit) => format!(
+                    "selected: {} (face {}, uv {:.3}, {:.3}) at {}",
+                    hit.entity, hit.face, hit.uv.x, hit.uv.y, hit.position
+                ),
+                None => "selected: none".to_string(),
+            },
+        ]
+        .into_iter()
+        .chain(stats.entities.iter().map(|e| {
+            format!(
+                "  {}: {:.2} / {:.2} ms",
+                e.name,
+                e.transform_time.as_secs_f32() * 1000.0,
+                e.raster_time.as_secs_f32() * 1000.0
+            )
+        }))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+        let debug = &mut self.data.debug;
+        let (w, h) = debug.font.text_size(&hud);
+        let margin = Float2::ONE * 4.0;
+        let size = Float2::new(w as f32, h as f32);
+        debug.filled_rect(HUD_POS - margin, HUD_POS + size + margin, Float3::ZERO);
+        debug.text(HUD_POS, hud, Float3::ONE);
+    }
+}
+
+impl Scene for TestScene {
+    fn update_state(&mut self, time_delta: f32, input: &mut dyn Input) {
+        // Moving entities would restart the accumulation every frame
+        if self.backend != Backend::PathTrace {
+            self.animations.update(time_delta);
+        }
+
+        match (&mut self.flythrough, &mut self.orbit) {
+            (Some(flythrough), _) => {
+                flythrough.update(time_delta);
+                if flythrough.is_finished() {
+                    self.toggle_flythrough();
+                }
+            }
+            (None, Some(orbit)) => orbit.update(input, &self.data.cam_model),
+            (None, None) => self.cam_controller.update_camera(time_delta, input),
+        }
+
+        // Camera modes
+        if input.is_key_down_this_frame(Key::Tab) {
+            self.toggle_orbit();
+        }
+        if input.is_key_down_this_frame(Key::F) {
+            self.flythrough = None;
+            self.frame_next_entity();
+        }
+        if input.is_key_down_this_frame(Key::P) {
+            self.toggle_flythrough();
+        }
+        if input.is_mouse_down_this_frame(MouseKey::Right) {
+            self.select_at(input.get_mouse_position(), input.screen_size());
+        }
+
+        // Debug toggles
+        if input.is_key_down_this_frame(Key::F1) {
+            self.settings.depth_prepass = !self.settings.depth_prepass;
+        }
+        if input.is_key_down_this_frame(Key::F2) {
+            self.settings.debug_view = match self.settings.debug_view {
+                DebugView::Shaded => DebugView::Overdraw,
+                DebugView::Overdraw => DebugView::Shaded,
+            };
+        }
+        if input.is_key_down_this_frame(Key::F3) {
+            self.settings.polygon_mode = match self.settings.polygon_mode {
+                PolygonMode::Fill => PolygonMode::FillWireframe,
+                PolygonMode::FillWireframe => PolygonMode::Wireframe,
+                PolygonMode::Wireframe => PolygonMode::Points,
+                PolygonMode::Points => PolygonMode::Fill,
+            };
+        }
+        if input.is_key_down_this_frame(Key::F4) {
+            self.show_gizmos = !self.show_gizmos;
+        }
+        if input.is_key_down_this_frame(Key::F5) {
+            let root = PoseGraph::find_root(&self.data.cam_pose);
+            print!("{}", PoseGraph::tree_string(&root));
+        }
+        if input.is_key_down_this_frame(Key::F6) {
+            // Saves the current poses, e.g. after moving the camera
+            match SceneDesc::from_scene(&self.data).and_then(|desc| desc.save(SAVED_SCENE_FILE)) {
+                Ok(()) => println!("Saved scene to {SAVED_SCENE_FILE}"),
+                Err(e) => println!("Failed to save scene: {e}"),
+            }
+        }
+        if input.is_key_down_this_frame(Key::F7) {
+            let scale = self.render_scale.scale();
+            let next = RENDER_SCALES.into_iter().find(|&s| s > scale);
+            self.render_scale
+                .set_scale(next.unwrap_or(RENDER_SCALES[0]));
+        }
+        if input.is_key_down_this_frame(Key::F8) {
+            self.render_scale.filter = match self.render_scale.filter {
+                UpscaleFilter::Nearest => UpscaleFilter::Bilinear,
+                UpscaleFilter::Bilinear => UpscaleFilter::Nearest,
+            };
+        }
+        if input.is_key_down_this_frame(Key::F9) {
+            let target = match self.render_scale.target_frame_time() {
+                Some(_) => None,
+                None => Some(TARGET_FRAME_TIME),
+            };
+            self.render_scale.set_target_frame_time(target);
+        }
+        if input.is_key_down_this_frame(Key::F10) {
+            self.show_hud = !self.show_hud;
+        }
+        if input.is_key_down_this_frame(Key::F11) {
+            self.backend = match self.backend {
+                Backend::Raster => Backend::RayTrace,
+                Backend::RayTrace => Backend::PathTrace,
+                Backend::PathTrace => Backend::Raster,
+            };
+            self.path_tracer.reset();
+            // Resizing the viewport would restart the accumulation
+            self.render_scale
+                .set_suspended(self.backend == Backend::PathTrace);
+        }
+
+        if self.show_gizmos {
+            let debug = &mut self.data.debug;
+            for entity in self.data.entities.values() {
+                debug.aabb(&entity.mesh.bounds(), &entity.pose, GIZMO_COLOR);
+                debug.axes(&entity.pose, 1.0);
+            }
+            debug.light_direction(&self.sun_pose, Float3::new(0.0, 0.0, -10.0), 3.0);
+
+            let cam_model = &self.data.cam_model;
+            let center = Float2::new(cam_model.width() as f32, cam_model.height() as f32) / 2.0;
+            debug.circle(center, 4.0, GIZMO_COLOR);
+        }
+
+        if let Some(entity) = self
+            .selected
+            .as_ref()
+            .and_then(|hit| self.data.entities.get(&hit.entity))
+        {
+            let debug = &mut self.data.debug;
+            debug.aabb(&entity.mesh.bounds(), &entity.pose, SELECTION_COLOR);
+        }
+
+        if self.show_hud {
+            self.queue_hud(time_delta);
+        }
 
         let sun_to_cam = PoseGraph::relative_transform(&self.sun_pose, &self.data.cam_pose);
         self.data.globals.time += time_delta;
         self.data.globals.sun_direction_cam_space = sun_to_cam.forward_vec();
     }
 
-    fn render(&mut self, buffer: &mut RenderBuffer<WIDTH, HEIGHT>) {
-        rasterize_scene(&mut self.data, buffer);
+    fn render(&mut self, buffer: &mut RenderBuffer) {
+        self.stats = match self.backend {
+            Backend::Raster => rasterize_scene(&mut self.data, buffer, &self.settings),
+            Backend::RayTrace => trace_scene(&self.data, buffer),
+            Backend::PathTrace => {
+                let stats = self.path_tracer.add_samples(&self.data, 1);
+                self.path_tracer.resolve(buffer);
+                stats
+            }
+        };
+    }
+
+    fn post_render(&mut self, buffer: &mut RenderBuffer) {
+        self.data.draw_debug(buffer);
+    }
+
+    fn resize(&mut self, width: usize, height: usize) {
+        self.data.cam_model.set_viewport(width, height);
+    }
+
+    fn render_scale(&mut self) -> Option<&mut RenderScale> {
+        Some(&mut self.render_scale)
     }
 }
@@ -0,0 +1,66 @@
+// Scenes shared by the tests of the renderers
+use engine::camera::CameraModel;
+use engine::coords::ENGINE;
+use engine::entity::Entity;
+use engine::mesh::Mesh;
+use engine::pose_graph::PoseGraph;
+use engine::primitives::{FaceData3D, Float2, Float3, Transform, Tri};
+use engine::scene::SceneData;
+use engine::shader::NormalShader;
+
+use std::sync::Arc;
+
+// Facing the camera, around the view axis at depth `-z`, colored by its normal
+pub fn face(z: f32, size: f32, normal: Float3) -> FaceData3D {
+    FaceData3D {
+        vertices: Tri::new(
+            Float3::new(-size, -size, z),
+            Float3::new(size, -size, z),
+            Float3::new(0.0, size, z),
+        ),
+        normals: Tri::new(normal, normal, normal),
+        uvs: Tri::new(Float2::ZERO, Float2::ZERO, Float2::ZERO),
+    }
+}
+
+// One entity made of `faces`, given in the space of a camera at the origin
+pub fn scene_with_faces(faces: Vec<FaceData3D>, width: usize, height: usize) -> SceneData {
+    let root = PoseGraph::root();
+    let cam = PoseGraph::new("cam", root.clone()).unwrap();
+    let entity = Entity::new(
+        root.clone(),
+        Arc::new(Mesh::new(faces)),
+        Arc::new(NormalShader()),
+    );
+    SceneData {
+        entities: [("tris".to_string(), entity)].into(),
+        cam_model: CameraModel::new(60.0, true, width, height),
+        cam_pose: cam,
+        nodes: vec![root],
+        ..Default::default()
+    }
+}
+
+// cube.obj, two units wide, once per name and transform, seen by a camera at the origin
+pub fn cube_scene(cubes: &[(&str, Transform)], width: usize, height: usize) -> SceneData {
+    let root = PoseGraph::root();
+    let cam = PoseGraph::new("cam", root.clone()).unwrap();
+    let mesh = Arc::new(Mesh::from_obj_file("../resources/models/cube.obj", ENGINE).unwrap());
+    let mut nodes = vec![root.clone()];
+    let mut entities = vec![];
+    for &(name, transform) in cubes {
+        let pose = PoseGraph::new(name, root.clone()).unwrap();
+        pose.write().set_transform(transform);
+        nodes.push(pose.clone());
+        let entity = Entity::new(pose, mesh.clone(), Arc::new(NormalShader()));
+        entities.push((name.to_string(), entity));
+    }
+
+    SceneData {
+        entities: entities.into_iter().collect(),
+        cam_model: CameraModel::new(60.0, true, width, height),
+        cam_pose: cam,
+        nodes,
+        ..Default::default()
+    }
+}